@@ -1,9 +1,824 @@
+// pyo3 0.20's #[pymethods] expansion trips this lint on newer toolchains. The generated impls
+// are emitted beside the annotated block at module level, so an allow on the block doesn't
+// reach them; only the enclosing module's level does.
+#![allow(non_local_definitions)]
+
+use numpy::PyArray1;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::f32::consts::PI;
 
+mod obstacles;
+mod recording;
+mod spatial;
+#[cfg(test)]
+mod tests;
+
+use obstacles::{Obstacle, ObstacleSet};
+use recording::{Frame, Recording};
+use spatial::SpatialGrid;
+
+/// Altitude below which a drone counts as on the ground
+const LANDED_ALTITUDE: f32 = 0.15;
+
+/// Default world bounds that drone positions are clamped to
+const WORLD_MIN: [f32; 3] = [-10.0, -10.0, 0.0];
+const WORLD_MAX: [f32; 3] = [10.0, 10.0, 5.0];
+
+/// Format version written at the start of every snapshot; restore rejects other versions
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Lowest altitude a position target may command
+const MIN_TARGET_ALTITUDE: f32 = 0.1;
+
+/// Default gravitational acceleration (m/s^2)
+const GRAVITY: f32 = 9.81;
+
+/// Default physical properties (Crazyflie 2.x class): mass (kg) and collision radius (m)
+const DEFAULT_MASS: f32 = 0.027;
+const DEFAULT_RADIUS: f32 = 0.075;
+
+/// Battery drain (%/min) of a flying drone until update_batteries is given a rate
+const DEFAULT_DRAIN_RATE: f32 = 0.5;
+
+/// Downwash cone: half-angle (rad), footprint radius at the rotor plane (m), and vertical reach (m)
+const DOWNWASH_HALF_ANGLE: f32 = 0.35;
+const DOWNWASH_FOOTPRINT: f32 = 0.1;
+const DOWNWASH_RANGE: f32 = 3.0;
+
+/// Horizontal distance (m) within which a panic-landing drone waits for the one below it
+const PANIC_LAND_CLEARANCE: f32 = 0.5;
+
+/// Battery points above the rotation threshold a reserve needs before taking an orbit slot
+const RESERVE_READY_MARGIN: f32 = 20.0;
+
+/// Altitude (m) short of the safe altitude at which a fault climb counts as complete
+const FAULT_CLIMB_TOLERANCE: f32 = 0.1;
+
+/// Horizontal distance (m) from home at which a returning failsafe drone starts landing
+const FAILSAFE_HOME_RADIUS: f32 = 0.2;
+
+/// Share of horizontal avoidance kept by low landing drones in "reduced" landing avoidance
+const LANDING_AVOIDANCE_REDUCTION: f32 = 0.25;
+
+/// Fraction of the avoidance radius below which the inverse falloff curves stop growing
+const AVOIDANCE_FALLOFF_FLOOR: f32 = 0.1;
+
+/// Decay rate (per avoidance radius) of the exponential falloff curve
+const AVOIDANCE_FALLOFF_EXP_RATE: f32 = 3.0;
+
+/// Cap on the adaptive avoidance gain (the count of neighbors inside the avoidance radius)
+const ADAPTIVE_AVOIDANCE_MAX_GAIN: f32 = 4.0;
+
+/// Share of a pair's separation taken by a drone holding its goal against one that isn't;
+/// the other drone takes the remainder
+const GOAL_HOLD_SHARE: f32 = 0.1;
+
+/// Flock: pull toward the commanded center, so the flock roams around it instead of drifting off
+const FLOCK_CENTER_PULL: f32 = 0.1;
+
+/// Flock: fraction of the velocity limit below which a moving boid speeds back up
+const FLOCK_CRUISE_FRACTION: f32 = 0.5;
+
+/// Default edge length (m) of the spatial hash cells
+const SPATIAL_CELL_SIZE: f32 = 1.0;
+
+/// Drone count from which nearest-neighbor queries use a spatial hash instead of scanning
+/// every pair
+const SPATIAL_QUERY_MIN_DRONES: usize = 64;
+
+/// Nearest drones nearest_drone checks for one that qualifies before scanning them all
+const NEAREST_DRONE_CANDIDATES: usize = 8;
+
+/// Speed (m/s) below which a position-tracking drone counts as not moving
+const STUCK_SPEED: f32 = 0.05;
+
+/// Horizontal speed (m/s) below which the minimum turn radius is not enforced, so drones can settle
+const TURN_RADIUS_MIN_SPEED: f32 = 0.1;
+
+/// Window (s) over which commanded velocity magnitude is accumulated as control effort
+const CONTROL_EFFORT_WINDOW: f32 = 1.0;
+
+/// Safe mode caps: speed (m/s), acceleration (m/s^2), tilt (rad) and yaw rate (rad/s)
+const SAFE_MODE_LIMITS: [f32; 4] = [0.5, 1.0, 0.15, 0.5];
+
+/// Safe mode volume targets are kept in: horizontal half-width and height (m) about the
+/// bounds' center
+const SAFE_MODE_EXTENT: [f32; 2] = [2.5, 2.5];
+
+/// Voronoi samples per drone, and relaxation rounds, for redistribute
+const LLOYD_SAMPLES_PER_POINT: usize = 256;
+const LLOYD_ITERATIONS: usize = 20;
+
+/// Normalize an angle to [-PI, PI]
+fn wrap_angle(angle: f32) -> f32 {
+    angle.sin().atan2(angle.cos())
+}
+
+/// SplitMix64 finalizer: deterministic pseudo-random bits from a key
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Uniform sample in (0, 1] from hashed bits
+fn hash_unit(bits: u64) -> f32 {
+    ((bits >> 40) as f32 + 1.0) / (1u64 << 24) as f32
+}
+
+/// Standard normal sample for a key (Box-Muller over two hashed uniforms)
+fn hash_gaussian(key: u64) -> f32 {
+    let u1 = hash_unit(splitmix64(key));
+    let u2 = hash_unit(splitmix64(key ^ 0x5DEE_CE66_D1CE_4E5B));
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// Sine from IEEE basic operations only (+ - * / round), so results are bit-identical across
+/// platforms; used in lockstep mode instead of the platform libm
+fn lockstep_sin(x: f32) -> f32 {
+    // Reduce to [-PI, PI], then fold onto [-PI/2, PI/2] where the Taylor series converges fast
+    let mut r = x - (x / (2.0 * PI)).round() * (2.0 * PI);
+    if r > PI / 2.0 {
+        r = PI - r;
+    } else if r < -PI / 2.0 {
+        r = -PI - r;
+    }
+    let r2 = r * r;
+    r * (1.0 + r2 * (-1.0 / 6.0 + r2 * (1.0 / 120.0 + r2 * (-1.0 / 5040.0 + r2 * (1.0 / 362_880.0 + r2 * (-1.0 / 39_916_800.0))))))
+}
+
+/// Two-argument arctangent from IEEE basic operations and sqrt (see `lockstep_sin`)
+fn lockstep_atan2(y: f32, x: f32) -> f32 {
+    let (ax, ay) = (x.abs(), y.abs());
+    if ax == 0.0 && ay == 0.0 {
+        return 0.0;
+    }
+    // atan(t) for t in [0, 1]: two half-angle reductions bring t under 0.2 for a short series
+    let mut t = ax.min(ay) / ax.max(ay);
+    for _ in 0..2 {
+        t /= 1.0 + (1.0 + t * t).sqrt();
+    }
+    let t2 = t * t;
+    let mut angle = 4.0 * t * (1.0 + t2 * (-1.0 / 3.0 + t2 * (1.0 / 5.0 + t2 * (-1.0 / 7.0 + t2 / 9.0))));
+
+    if ay > ax {
+        angle = PI / 2.0 - angle;
+    }
+    if x < 0.0 {
+        angle = PI - angle;
+    }
+    if y < 0.0 {
+        -angle
+    } else {
+        angle
+    }
+}
+
+/// Downward acceleration on a drone at `pos` from rotor wash of drones above it
+///
+/// Each drone whose cone (widening with DOWNWASH_HALF_ANGLE) covers `pos` pushes
+/// down with `strength / vertical_separation`.
+fn downwash_accel(pos: [f32; 3], airborne: &[[f32; 3]], strength: f32, config: &StepConfig) -> f32 {
+    airborne.iter().map(|other| {
+        let dz = other[2] - pos[2];
+        if dz <= 0.0 || dz > DOWNWASH_RANGE {
+            return 0.0;
+        }
+        let horizontal = ((other[0] - pos[0]).powi(2) + (other[1] - pos[1]).powi(2)).sqrt();
+        if horizontal > DOWNWASH_FOOTPRINT + dz * config.tan(DOWNWASH_HALF_ANGLE) {
+            return 0.0;
+        }
+        strength / dz.max(0.05)
+    }).sum()
+}
+
+/// Airborne drone as seen by the collision avoidance pass
+#[derive(Clone, Copy)]
+struct AvoidanceNeighbor {
+    id: usize,
+    pos: [f32; 3],
+    vel: [f32; 3],
+    radius: f32,
+    /// Within goal-priority tolerance of its target, so passing drones do the avoiding
+    holding: bool,
+    group: Option<u32>,
+}
+
+/// Separation velocity for `drone` away from `neighbors` outside its avoidance group
+///
+/// Each neighbor pushes with `strength` times the `falloff` weight of its distance, fading to
+/// zero at r = max(`radius`, sum of the pair's collision radii) so avoidance hands back smoothly to
+/// position control and large drones keep clear of each other's hulls. The distance is taken
+/// at the pair's closest approach over the next `lookahead` seconds at current velocities,
+/// so converging drones start separating before they are inside the radius; a dead-center
+/// approach sidesteps to the right of the relative motion, which for a head-on pair is
+/// opposite sides. A holding drone takes GOAL_HOLD_SHARE of the push against a drone that
+/// isn't holding, which takes the rest. When `adaptive`, `strength` is multiplied by the number
+/// of neighbors currently inside their radius (up to ADAPTIVE_AVOIDANCE_MAX_GAIN), so drones
+/// in a crowd separate harder than a lone pair.
+fn avoidance_velocity(drone: &AvoidanceNeighbor, neighbors: &[AvoidanceNeighbor], radius: f32, strength: f32, lookahead: f32, falloff: AvoidanceFalloff, adaptive: bool) -> [f32; 3] {
+    let avoided = || neighbors.iter().filter(|other| other.id != drone.id && (drone.group.is_none() || drone.group != other.group));
+    let strength = if adaptive {
+        let crowd = avoided().filter(|other| distance(drone.pos, other.pos) < radius.max(drone.radius + other.radius)).count();
+        strength * (crowd as f32).clamp(1.0, ADAPTIVE_AVOIDANCE_MAX_GAIN)
+    } else {
+        strength
+    };
+    let mut push = [0.0f32; 3];
+    for other in avoided() {
+        let radius = radius.max(drone.radius + other.radius);
+        let offset = [0, 1, 2].map(|i| drone.pos[i] - other.pos[i]);
+        let rel_vel = [0, 1, 2].map(|i| drone.vel[i] - other.vel[i]);
+        let speed_sq: f32 = rel_vel.iter().map(|v| v * v).sum();
+        let closing: f32 = offset.iter().zip(&rel_vel).map(|(o, v)| o * v).sum();
+        let t = if speed_sq > 1e-9 { (-closing / speed_sq).clamp(0.0, lookahead) } else { 0.0 };
+        let closest = [0, 1, 2].map(|i| offset[i] + rel_vel[i] * t);
+        let dist = (closest[0] * closest[0] + closest[1] * closest[1] + closest[2] * closest[2]).sqrt();
+        if dist >= radius {
+            continue;
+        }
+        let horizontal_speed = rel_vel[0].hypot(rel_vel[1]);
+        // Coincident drones split along x, lower id to the negative side
+        let away = if dist > 1e-6 {
+            closest.map(|v| v / dist)
+        } else if t > 0.0 && horizontal_speed > 1e-6 {
+            [rel_vel[1] / horizontal_speed, -rel_vel[0] / horizontal_speed, 0.0]
+        } else if drone.id < other.id {
+            [-1.0, 0.0, 0.0]
+        } else {
+            [1.0, 0.0, 0.0]
+        };
+        let offset_len = (offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2]).sqrt();
+        let closing_speed = if offset_len > 1e-6 { (-closing / offset_len).max(0.0) } else { 0.0 };
+        let share = match (drone.holding, other.holding) {
+            (true, false) => GOAL_HOLD_SHARE,
+            (false, true) => 2.0 - GOAL_HOLD_SHARE,
+            _ => 1.0,
+        };
+        let magnitude = share * (strength + closing_speed) * falloff.weight(dist / radius);
+        for (p, a) in push.iter_mut().zip(away) {
+            *p += magnitude * a;
+        }
+        // Veer right around a closing neighbor, in proportion to the closing speed, so a head-on
+        // pair can't deadlock while drones settled side by side don't circle
+        let veer = share * closing_speed * (1.0 - dist / radius);
+        push[0] -= veer * away[1];
+        push[1] += veer * away[0];
+    }
+    push
+}
+
+/// Boids desired velocity for drone `id` from `flockmates` (id, pos, vel) within the perception radius
+///
+/// Cohesion steers toward the neighbors' center of mass, alignment toward their mean velocity
+/// and separation away from those inside half the radius (weighted by 1 / distance); a weak
+/// pull toward `center` keeps the flock around its commanded area.
+fn flock_velocity(id: usize, pos: [f32; 3], vel: [f32; 3], center: [f32; 3], flockmates: &[(usize, [f32; 3], [f32; 3])], params: [f32; 4]) -> [f32; 3] {
+    let [cohesion, alignment, separation, perception] = params;
+    let mut mass_center = [0.0f32; 3];
+    let mut mean_vel = [0.0f32; 3];
+    let mut push = [0.0f32; 3];
+    let mut count = 0;
+    for &(other_id, other_pos, other_vel) in flockmates {
+        let dist = distance(pos, other_pos);
+        if other_id == id || dist >= perception {
+            continue;
+        }
+        count += 1;
+        for i in 0..3 {
+            mass_center[i] += other_pos[i];
+            mean_vel[i] += other_vel[i];
+            if dist < perception / 2.0 && dist > 1e-6 {
+                push[i] += (pos[i] - other_pos[i]) / (dist * dist);
+            }
+        }
+    }
+
+    let mut desired = vel;
+    for i in 0..3 {
+        if count > 0 {
+            desired[i] += cohesion * (mass_center[i] / count as f32 - pos[i])
+                + alignment * (mean_vel[i] / count as f32 - vel[i])
+                + separation * push[i];
+        }
+        desired[i] += FLOCK_CENTER_PULL * (center[i] - pos[i]);
+    }
+    desired
+}
+
+/// Slots for `n` drones evenly spaced along the x or y axis
+fn line_slots(n: usize, center: [f32; 3], spacing: f32, axis: &str) -> Vec<[f32; 3]> {
+    let start_offset = -(n.saturating_sub(1) as f32) * spacing / 2.0;
+
+    (0..n).map(|i| {
+        let offset = start_offset + i as f32 * spacing;
+        match axis {
+            "y" => [center[0], center[1] + offset, center[2]],
+            _ => [center[0] + offset, center[1], center[2]],
+        }
+    }).collect()
+}
+
+/// Slots for `n` drones evenly spaced around a circle (a lone drone takes the center)
+fn circle_slots(n: usize, center: [f32; 3], radius: f32) -> Vec<[f32; 3]> {
+    if n == 1 {
+        return vec![center];
+    }
+    (0..n).map(|i| {
+        let angle = 2.0 * PI * i as f32 / n as f32;
+        [center[0] + radius * angle.cos(), center[1] + radius * angle.sin(), center[2]]
+    }).collect()
+}
+
+/// Slots for `n` drones on a near-square grid
+fn grid_slots(n: usize, center: [f32; 3], spacing: f32) -> Vec<[f32; 3]> {
+    if n == 0 {
+        return Vec::new();
+    }
+    let cols = (n as f32).sqrt().ceil() as usize;
+    let rows = n.div_ceil(cols);
+
+    let start_x = -((cols - 1) as f32) * spacing / 2.0;
+    let start_y = -((rows - 1) as f32) * spacing / 2.0;
+
+    (0..n).map(|i| {
+        let row = i / cols;
+        let col = i % cols;
+        [center[0] + start_x + col as f32 * spacing, center[1] + start_y + row as f32 * spacing, center[2]]
+    }).collect()
+}
+
+/// Slots for `n` drones in a V: leader at the center, followers alternating behind
+///
+/// Two drones fly as leader and a single wingman.
+fn v_slots(n: usize, center: [f32; 3], spacing: f32) -> Vec<[f32; 3]> {
+    let angle: f32 = PI / 6.0;  // 30 degrees
+
+    (0..n).map(|i| {
+        if i == 0 {
+            return center;
+        }
+        let side = if i % 2 == 0 { 1.0 } else { -1.0 };
+        let offset_back = i.div_ceil(2) as f32;
+        [
+            center[0] - offset_back * spacing * angle.cos(),
+            center[1] + side * offset_back * spacing * angle.sin(),
+            center[2],
+        ]
+    }).collect()
+}
+
+/// Slots for `n` drones hex close-packed at `spacing` within `packing_radius` of the center
+///
+/// Nearest lattice points fill first; once the disc is full, further drones stack in
+/// layers `spacing` above it. Only lattice points that could be among the `n` nearest are
+/// generated, so a disc far wider than the drones need costs no more than a tight one.
+fn hex_slots(n: usize, center: [f32; 3], spacing: f32, packing_radius: f32) -> Vec<[f32; 3]> {
+    // The n nearest points lie within about sqrt(n / 3) rings
+    let reach = ((packing_radius / spacing).ceil() as i32).min((n as f32).sqrt().ceil() as i32) + 1;
+    let row_height = spacing * 3f32.sqrt() / 2.0;
+
+    let mut lattice: Vec<[f32; 2]> = (-reach..=reach)
+        .flat_map(|r| (-reach..=reach).map(move |q| {
+            [spacing * (q as f32 + r as f32 / 2.0), row_height * r as f32]
+        }))
+        .filter(|p| p[0].hypot(p[1]) <= packing_radius + 1e-4)
+        .collect();
+    lattice.sort_by(|a, b| a[0].hypot(a[1]).total_cmp(&b[0].hypot(b[1])));
+    if lattice.is_empty() {
+        lattice.push([0.0, 0.0]);
+    }
+
+    let per_layer = lattice.len();
+    (0..n).map(|i| {
+        let p = lattice[i % per_layer];
+        let layer = (i / per_layer) as f32;
+        [center[0] + p[0], center[1] + p[1], center[2] + layer * spacing]
+    }).collect()
+}
+
+/// Even spread of `starts` over the box `min`..`max` by Lloyd relaxation
+///
+/// Each of `iterations` rounds moves every point to the centroid of its Voronoi cell, estimated
+/// on a sample grid over the box; starting from the current positions keeps each point near
+/// where it began. A point whose cell comes up empty (coincident starts) jumps to the sample
+/// farthest from every point. Flat axes (min == max) are left flat.
+fn lloyd_slots(starts: &[[f32; 3]], min: [f32; 3], max: [f32; 3], iterations: usize) -> Vec<[f32; 3]> {
+    let n = starts.len();
+    let mut points: Vec<[f32; 3]> = starts.iter()
+        .map(|p| [0, 1, 2].map(|i| p[i].clamp(min[i], max[i])))
+        .collect();
+    if n == 0 {
+        return points;
+    }
+
+    // About LLOYD_SAMPLES_PER_POINT samples per point in cells as square as the box allows
+    let extent = [0, 1, 2].map(|i| max[i] - min[i]);
+    let spanned: Vec<f32> = extent.iter().copied().filter(|&e| e > 0.0).collect();
+    let total = (n * LLOYD_SAMPLES_PER_POINT) as f32;
+    let cell = (spanned.iter().product::<f32>() / total).powf(1.0 / spanned.len().max(1) as f32);
+    let counts = extent.map(|e| if e > 0.0 { (e / cell).ceil().max(1.0) as usize } else { 1 });
+    let axis = |i: usize, k: usize| min[i] + extent[i] * (k as f32 + 0.5) / counts[i] as f32;
+    let samples: Vec<[f32; 3]> = (0..counts[0])
+        .flat_map(|x| (0..counts[1]).flat_map(move |y| (0..counts[2]).map(move |z| [axis(0, x), axis(1, y), axis(2, z)])))
+        .collect();
+
+    for _ in 0..iterations {
+        let nearest: Vec<(usize, f32)> = samples.par_iter().map(|&s| {
+            points.iter().enumerate()
+                .map(|(i, &p)| (i, distance(s, p)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 0.0))
+        }).collect();
+
+        let mut sums = vec![([0.0f32; 3], 0usize); n];
+        for (sample, &(owner, _)) in samples.iter().zip(&nearest) {
+            for (sum, v) in sums[owner].0.iter_mut().zip(sample) {
+                *sum += v;
+            }
+            sums[owner].1 += 1;
+        }
+        let mut spare = Vec::new();
+        if sums.iter().any(|&(_, count)| count == 0) {
+            spare = (0..samples.len()).collect();
+            spare.sort_by(|&a, &b| nearest[b].1.total_cmp(&nearest[a].1));
+        }
+        let mut spare = spare.into_iter().map(|k| samples[k]);
+        for (point, (sum, count)) in points.iter_mut().zip(sums) {
+            if count > 0 {
+                *point = sum.map(|v| v / count as f32);
+            } else if let Some(sample) = spare.next() {
+                *point = sample;
+            }
+        }
+    }
+    points
+}
+
+/// Slots for a formation named by `kind` with positional `params`; None for an unknown kind
+fn formation_slots(kind: &str, n: usize, center: [f32; 3], params: &[f32]) -> Option<Vec<[f32; 3]>> {
+    let param = |i: usize, default: f32| params.get(i).copied().unwrap_or(default);
+    match kind {
+        "line" => {
+            let axis = if param(1, 0.0) == 1.0 { "y" } else { "x" };
+            Some(line_slots(n, center, param(0, 1.0), axis))
+        }
+        "circle" => Some(circle_slots(n, center, param(0, 1.5))),
+        "grid" => Some(grid_slots(n, center, param(0, 1.0))),
+        "v" => Some(v_slots(n, center, param(0, 1.0))),
+        _ => None,
+    }
+}
+
+/// Minimum-cost assignment of rows to distinct columns (Hungarian algorithm, rows <= columns)
+///
+/// Returns the column assigned to each row.
+fn min_cost_assignment(cost: &[Vec<f32>]) -> Vec<usize> {
+    let n = cost.len();
+    let m = cost.first().map_or(0, |row| row.len());
+    // 1-indexed potentials and matching, column 0 is a virtual start column
+    let mut u = vec![0.0f64; n + 1];
+    let mut v = vec![0.0f64; m + 1];
+    let mut row_of = vec![0usize; m + 1];
+    let mut way = vec![0usize; m + 1];
+
+    for row in 1..=n {
+        row_of[0] = row;
+        let mut col0 = 0;
+        let mut min_slack = vec![f64::INFINITY; m + 1];
+        let mut used = vec![false; m + 1];
+        loop {
+            used[col0] = true;
+            let row0 = row_of[col0];
+            let mut delta = f64::INFINITY;
+            let mut col1 = 0;
+            for col in 1..=m {
+                if used[col] {
+                    continue;
+                }
+                let slack = cost[row0 - 1][col - 1] as f64 - u[row0] - v[col];
+                if slack < min_slack[col] {
+                    min_slack[col] = slack;
+                    way[col] = col0;
+                }
+                if min_slack[col] < delta {
+                    delta = min_slack[col];
+                    col1 = col;
+                }
+            }
+            for col in 0..=m {
+                if used[col] {
+                    u[row_of[col]] += delta;
+                    v[col] -= delta;
+                } else {
+                    min_slack[col] -= delta;
+                }
+            }
+            col0 = col1;
+            if row_of[col0] == 0 {
+                break;
+            }
+        }
+        // Flip the augmenting path
+        while col0 != 0 {
+            let prev = way[col0];
+            row_of[col0] = row_of[prev];
+            col0 = prev;
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for col in 1..=m {
+        if row_of[col] != 0 {
+            assignment[row_of[col] - 1] = col - 1;
+        }
+    }
+    assignment
+}
+
+/// Convex hull of `points` counterclockwise from the lowest-x point (Andrew's monotone chain)
+///
+/// Collinear points reduce to the two extremes, and duplicates to a single point.
+fn convex_hull(mut points: Vec<[f32; 2]>) -> Vec<[f32; 2]> {
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: [f32; 2], a: [f32; 2], b: [f32; 2]| (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0]);
+    let chain = |points: &mut dyn Iterator<Item = [f32; 2]>| {
+        let mut chain: Vec<[f32; 2]> = Vec::new();
+        for p in points {
+            while chain.len() >= 2 && cross(chain[chain.len() - 2], chain[chain.len() - 1], p) <= 0.0 {
+                chain.pop();
+            }
+            chain.push(p);
+        }
+        // The last point starts the other chain
+        chain.pop();
+        chain
+    };
+    let mut hull = chain(&mut points.iter().copied());
+    hull.extend(chain(&mut points.iter().rev().copied()));
+    hull
+}
+
+/// Drone ids as floats, for numeric command-log args
+fn id_args(ids: &[usize]) -> Vec<f32> {
+    ids.iter().map(|&id| id as f32).collect()
+}
+
+/// Euclidean distance between two points
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// Format a float as a JSON number; NaN and infinities, which JSON can't represent, become null
+fn json_number(value: f32) -> String {
+    if value.is_finite() { value.to_string() } else { "null".to_string() }
+}
+
+/// Format floats as a JSON array
+fn json_array(values: &[f32]) -> String {
+    let items: Vec<String> = values.iter().map(|&v| json_number(v)).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Position PID gains and first-order velocity dynamics
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ControlGains {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    /// How fast velocity responds to the command (1/s)
+    pub response_rate: f32,
+    /// Linear drag on airspeed (1/s)
+    pub drag: f32,
+}
+
+impl Default for ControlGains {
+    fn default() -> Self {
+        Self { kp: 2.0, ki: 0.01, kd: 0.5, response_rate: 5.0, drag: 0.1 }
+    }
+}
+
+/// Swarm-wide settings shared by every drone during a physics step
+#[derive(Clone)]
+pub struct StepConfig {
+    /// Maximum tilt angle (rad); caps horizontal acceleration at g*tan(max_tilt)
+    pub max_tilt: f32,
+    /// Yaw controller gains (P-only by default)
+    pub yaw_kp: f32,
+    pub yaw_ki: f32,
+    pub yaw_kd: f32,
+    /// Clear PID state on every mode transition
+    pub reset_pid_on_mode_change: bool,
+    /// World bounds that positions and targets are clamped to
+    pub world_min: [f32; 3],
+    pub world_max: [f32; 3],
+    /// Gravitational acceleration (m/s^2)
+    pub gravity: f32,
+    /// Per-mode velocity limits (m/s, scene-scaled, before the speed multiplier) replacing the global max
+    pub mode_velocity_limits: HashMap<DroneMode, f32>,
+    /// Swarm speed multiplier, applied to per-mode limits
+    pub velocity_scale: f32,
+    /// Health envelope [horizontal, vertical_min, vertical_max]; None derives it from the bounds
+    pub health_margins: Option<[f32; 3]>,
+    /// Landing flare [altitude, max descent rate]: below the altitude, descent is capped
+    pub landing_flare: Option<[f32; 2]>,
+    /// Yaw rotational dynamics [inertia, damping]; None applies the controller output as yaw rate
+    pub yaw_dynamics: Option<[f32; 2]>,
+    /// Scalar field followed by drones in FieldFollow mode
+    pub potential_field: Option<PotentialField>,
+    /// Wind velocity by altitude as (altitude, wind) samples sorted by altitude
+    pub wind_profile: Vec<(f32, [f32; 3])>,
+    /// Current gust speed added along the horizontal wind direction (set each step by the swarm)
+    pub wind_gust: f32,
+    /// Tightest horizontal turn (m) allowed in Goto; 0 lets drones change direction freely
+    pub min_turn_radius: f32,
+    /// Caps on acceleration magnitude (m/s^2) and its rate of change (m/s^3); infinite by default
+    pub max_accel: f32,
+    pub max_jerk: f32,
+    /// Hard caps on speed (m/s) and yaw rate (rad/s) over every mode and multiplier
+    pub max_speed: f32,
+    pub max_yaw_rate: f32,
+    /// Crash detection [crash distance (m), ground impact speed (m/s)]; None disables it
+    pub crash_detection: Option<[f32; 2]>,
+    /// Region inside the world bounds that positions and targets are also confined to
+    pub geofence: Option<Geofence>,
+    /// Safe mode volume [min, max] that position targets are kept in; positions are not
+    /// confined to it, so drones outside fly back in
+    pub safe_volume: Option<[[f32; 3]; 2]>,
+    /// Cylinders drones are kept out of (by their collision radius)
+    pub obstacles: ObstacleSet,
+    /// Bit-reproducible stepping: sequential updates and transcendental-free trig
+    pub lockstep: bool,
+    /// Control gains for drones without their own override
+    pub control_gains: ControlGains,
+    /// Boids weights [cohesion, alignment, separation] and perception radius (m)
+    pub flock_params: [f32; 4],
+}
+
+impl Default for StepConfig {
+    fn default() -> Self {
+        Self {
+            max_tilt: PI / 2.0,
+            yaw_kp: 2.0,
+            yaw_ki: 0.0,
+            yaw_kd: 0.0,
+            reset_pid_on_mode_change: false,
+            world_min: WORLD_MIN,
+            world_max: WORLD_MAX,
+            gravity: GRAVITY,
+            mode_velocity_limits: HashMap::new(),
+            velocity_scale: 1.0,
+            health_margins: None,
+            landing_flare: None,
+            yaw_dynamics: None,
+            potential_field: None,
+            wind_profile: Vec::new(),
+            wind_gust: 0.0,
+            min_turn_radius: 0.0,
+            max_accel: f32::INFINITY,
+            max_jerk: f32::INFINITY,
+            max_speed: f32::INFINITY,
+            max_yaw_rate: f32::INFINITY,
+            crash_detection: None,
+            geofence: None,
+            safe_volume: None,
+            obstacles: ObstacleSet::default(),
+            lockstep: false,
+            control_gains: ControlGains::default(),
+            flock_params: [0.5, 0.3, 0.5, 1.5],
+        }
+    }
+}
+
+impl StepConfig {
+    /// Velocity limit for a mode: its configured limit, else the global `max_vel`
+    fn mode_max_vel(&self, mode: DroneMode, max_vel: f32) -> f32 {
+        self.mode_velocity_limits.get(&mode).map_or(max_vel, |&limit| limit * self.velocity_scale)
+    }
+
+    /// Clamp a position target into the world bounds and geofence
+    fn clamp_target(&self, pos: [f32; 3]) -> [f32; 3] {
+        let mut target = [
+            pos[0].clamp(self.world_min[0], self.world_max[0]),
+            pos[1].clamp(self.world_min[1], self.world_max[1]),
+            pos[2].clamp(MIN_TARGET_ALTITUDE, self.world_max[2]),
+        ];
+        if let Some(fence) = &self.geofence {
+            let [x, y] = fence.project(target[0], target[1]);
+            target = [x, y, target[2].clamp(fence.min_z.max(MIN_TARGET_ALTITUDE), fence.max_z.max(MIN_TARGET_ALTITUDE))];
+        }
+        self.clamp_to_safe_volume(target)
+    }
+
+    /// Clamp a position target into the safe mode volume, if safe mode is on
+    fn clamp_to_safe_volume(&self, pos: [f32; 3]) -> [f32; 3] {
+        match self.safe_volume {
+            Some([min, max]) => [0, 1, 2].map(|i| pos[i].clamp(min[i], max[i])),
+            None => pos,
+        }
+    }
+
+    /// Clamp a drone position into the world bounds, then back onto the geofence edge and
+    /// under its ceiling (the floor only limits targets, so drones can still land)
+    fn clamp_position(&self, pos: [f32; 3]) -> [f32; 3] {
+        let mut clamped = [0, 1, 2].map(|i| pos[i].clamp(self.world_min[i], self.world_max[i]));
+        if let Some(fence) = &self.geofence {
+            let [x, y] = fence.project(clamped[0], clamped[1]);
+            clamped = [x, y, clamped[2].min(fence.max_z)];
+        }
+        clamped
+    }
+
+    /// Health envelope [horizontal, vertical_min, vertical_max]; defaults to 5m beyond the
+    /// horizontal bounds and above the ceiling
+    fn health_limits(&self) -> [f32; 3] {
+        self.health_margins.unwrap_or_else(|| {
+            let horizontal = self.world_min[..2].iter().chain(&self.world_max[..2])
+                .fold(0.0f32, |m, v| m.max(v.abs()));
+            [horizontal + 5.0, self.world_min[2], self.world_max[2] + 5.0]
+        })
+    }
+
+    /// Wind at altitude `z` plus the current gust (along +x when there is no horizontal wind)
+    fn wind_at(&self, z: f32) -> [f32; 3] {
+        let mut wind = self.profile_wind_at(z);
+        if self.wind_gust != 0.0 {
+            let speed = self.hypot(wind[0], wind[1]);
+            let direction = if speed > 1e-6 { [wind[0] / speed, wind[1] / speed] } else { [1.0, 0.0] };
+            wind[0] += self.wind_gust * direction[0];
+            wind[1] += self.wind_gust * direction[1];
+        }
+        wind
+    }
+
+    /// Profile wind at altitude `z`, linearly interpolated and held constant beyond the profile ends
+    fn profile_wind_at(&self, z: f32) -> [f32; 3] {
+        let profile = &self.wind_profile;
+        let (Some(first), Some(last)) = (profile.first(), profile.last()) else {
+            return [0.0, 0.0, 0.0];
+        };
+        if z <= first.0 {
+            return first.1;
+        }
+        if z >= last.0 {
+            return last.1;
+        }
+        let upper = profile.iter().position(|&(altitude, _)| altitude >= z).unwrap_or(profile.len() - 1);
+        let (z0, w0) = profile[upper - 1];
+        let (z1, w1) = profile[upper];
+        let t = if z1 > z0 { (z - z0) / (z1 - z0) } else { 0.0 };
+        [0, 1, 2].map(|i| w0[i] + t * (w1[i] - w0[i]))
+    }
+
+    /// Horizontal acceleration limit implied by the tilt cap
+    fn max_horizontal_accel(&self) -> f32 {
+        if self.max_tilt >= PI / 2.0 {
+            f32::INFINITY
+        } else {
+            self.gravity * self.tan(self.max_tilt)
+        }
+    }
+
+    // Step-path math: the platform libm normally, the transcendental-free versions under lockstep
+
+    fn sin(&self, x: f32) -> f32 {
+        if self.lockstep { lockstep_sin(x) } else { x.sin() }
+    }
+
+    fn cos(&self, x: f32) -> f32 {
+        if self.lockstep { lockstep_sin(x + PI / 2.0) } else { x.cos() }
+    }
+
+    fn tan(&self, x: f32) -> f32 {
+        if self.lockstep { lockstep_sin(x) / lockstep_sin(x + PI / 2.0) } else { x.tan() }
+    }
+
+    fn atan2(&self, y: f32, x: f32) -> f32 {
+        if self.lockstep { lockstep_atan2(y, x) } else { y.atan2(x) }
+    }
+
+    fn hypot(&self, x: f32, y: f32) -> f32 {
+        if self.lockstep { (x * x + y * y).sqrt() } else { x.hypot(y) }
+    }
+
+    fn wrap_angle(&self, angle: f32) -> f32 {
+        if self.lockstep { angle - (angle / (2.0 * PI)).round() * (2.0 * PI) } else { wrap_angle(angle) }
+    }
+}
+
 /// Drone operational modes
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum DroneMode {
     Idle,
     Takeoff,
@@ -12,10 +827,153 @@ pub enum DroneMode {
     Goto,
     Velocity,
     Monitor,
+    FieldFollow,
+    Flock,
 }
 
-/// Individual drone state and physics
+impl DroneMode {
+    /// Lowercase name used at the Python API boundary
+    pub fn name(&self) -> &'static str {
+        match self {
+            DroneMode::Idle => "idle",
+            DroneMode::Takeoff => "takeoff",
+            DroneMode::Landing => "landing",
+            DroneMode::Hover => "hover",
+            DroneMode::Goto => "goto",
+            DroneMode::Velocity => "velocity",
+            DroneMode::Monitor => "monitor",
+            DroneMode::FieldFollow => "field_follow",
+            DroneMode::Flock => "flock",
+        }
+    }
+
+    /// Parse a mode from its lowercase name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "idle" => Some(DroneMode::Idle),
+            "takeoff" => Some(DroneMode::Takeoff),
+            "landing" => Some(DroneMode::Landing),
+            "hover" => Some(DroneMode::Hover),
+            "goto" => Some(DroneMode::Goto),
+            "velocity" => Some(DroneMode::Velocity),
+            "monitor" => Some(DroneMode::Monitor),
+            "field_follow" => Some(DroneMode::FieldFollow),
+            "flock" => Some(DroneMode::Flock),
+            _ => None,
+        }
+    }
+}
+
+/// 2D scalar field sampled on a square grid spanning `min`..`max` (row-major, rows along y)
+#[derive(Clone)]
+pub struct PotentialField {
+    grid: Vec<f32>,
+    resolution: usize,
+    min: [f32; 2],
+    max: [f32; 2],
+}
+
+impl PotentialField {
+    /// Bilinearly interpolated value at (x, y), clamped to the grid edges
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let last = (self.resolution - 1) as f32;
+        let gx = ((x - self.min[0]) / (self.max[0] - self.min[0]) * last).clamp(0.0, last);
+        let gy = ((y - self.min[1]) / (self.max[1] - self.min[1]) * last).clamp(0.0, last);
+        let (x0, y0) = (gx.floor() as usize, gy.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.resolution - 1), (y0 + 1).min(self.resolution - 1));
+        let (tx, ty) = (gx - x0 as f32, gy - y0 as f32);
+
+        let at = |cx: usize, cy: usize| self.grid[cy * self.resolution + cx];
+        let bottom = at(x0, y0) + tx * (at(x1, y0) - at(x0, y0));
+        let top = at(x0, y1) + tx * (at(x1, y1) - at(x0, y1));
+        bottom + ty * (top - bottom)
+    }
+
+    /// Central-difference gradient at (x, y), one grid cell wide
+    fn gradient(&self, x: f32, y: f32) -> [f32; 2] {
+        let hx = (self.max[0] - self.min[0]) / (self.resolution - 1) as f32;
+        let hy = (self.max[1] - self.min[1]) / (self.resolution - 1) as f32;
+        [
+            (self.sample(x + hx, y) - self.sample(x - hx, y)) / (2.0 * hx),
+            (self.sample(x, y + hy) - self.sample(x, y - hy)) / (2.0 * hy),
+        ]
+    }
+}
+
+/// Horizontal polygon (vertices in order, either winding) with an altitude band
 #[derive(Clone)]
+pub struct Geofence {
+    vertices: Vec<[f32; 2]>,
+    min_z: f32,
+    max_z: f32,
+}
+
+impl Geofence {
+    /// Even-odd point-in-polygon test
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let n = self.vertices.len();
+        let mut inside = false;
+        for i in 0..n {
+            let a = self.vertices[i];
+            let b = self.vertices[(i + 1) % n];
+            if (a[1] > y) != (b[1] > y) && x < a[0] + (y - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// (x, y) if inside, else the nearest point on the polygon's edges
+    fn project(&self, x: f32, y: f32) -> [f32; 2] {
+        if self.contains(x, y) {
+            return [x, y];
+        }
+        let n = self.vertices.len();
+        (0..n)
+            .map(|i| {
+                let a = self.vertices[i];
+                let b = self.vertices[(i + 1) % n];
+                let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+                let len_sq = dx * dx + dy * dy;
+                let t = if len_sq > 0.0 { (((x - a[0]) * dx + (y - a[1]) * dy) / len_sq).clamp(0.0, 1.0) } else { 0.0 };
+                [a[0] + t * dx, a[1] + t * dy]
+            })
+            .min_by(|p, q| {
+                let d = |p: &[f32; 2]| (p[0] - x) * (p[0] - x) + (p[1] - y) * (p[1] - y);
+                d(p).total_cmp(&d(q))
+            })
+            .unwrap_or([x, y])
+    }
+}
+
+/// Minimum-jerk (quintic) interpolation from `start` to `end` at time `t` of `duration`;
+/// velocity and acceleration are zero at both ends
+fn min_jerk(start: [f32; 3], end: [f32; 3], duration: f32, t: f32) -> [f32; 3] {
+    let tau = if duration > 0.0 { (t / duration).clamp(0.0, 1.0) } else { 1.0 };
+    let s = tau.powi(3) * (10.0 - 15.0 * tau + 6.0 * tau * tau);
+    [0, 1, 2].map(|i| start[i] + s * (end[i] - start[i]))
+}
+
+/// Time-parameterized path fed to a Goto drone as a moving target
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SmoothTrajectory {
+    start: [f32; 3],
+    end: [f32; 3],
+    duration: f32,
+    elapsed: f32,
+}
+
+/// Queue of Goto targets flown in order (set_path)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WaypointPath {
+    waypoints: Vec<[f32; 3]>,
+    index: usize,
+    looping: bool,
+    tolerance: f32,
+}
+
+/// Individual drone state and physics
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Drone {
     pub id: usize,
     pub pos: [f32; 3],
@@ -29,539 +987,4226 @@ pub struct Drone {
     pub battery: f32,
     pub healthy: bool,
 
+    // UI selection flag; metadata only, never read by physics
+    pub selected: bool,
+
+    // Physical properties
+    pub mass: f32,
+    pub radius: f32,
+
+    // Scales this drone's velocity limit on top of the swarm-wide speed
+    pub speed_multiplier: f32,
+
+    // Frozen drones skip physics entirely and hold their pose
+    pub frozen: bool,
+
+    // Paused drones skip physics like frozen ones, but only resume_drone releases them
+    pub paused: bool,
+
+    // Crashed drones are unhealthy and held in Idle until reset; ground impacts are flagged
+    // during the step and reported by the swarm
+    pub crashed: bool,
+    ground_impact: bool,
+
+    // Fraction of each swarm step this drone's physics advances (slow motion below 1)
+    pub time_scale: f32,
+
+    // FieldFollow: velocity per unit field gradient (negative descends the field)
+    pub field_gain: f32,
+
+    // Smooth Goto path being tracked (goto_smooth)
+    pub trajectory: Option<SmoothTrajectory>,
+
+    // Waypoint queue advanced on arrival; cleared when the drone leaves Goto
+    pub path: Option<WaypointPath>,
+
+    // World point the yaw controller keeps in frame, whatever the position mode
+    pub track_point: Option<[f32; 3]>,
+
+    // Synchronized landing: [descent rate, max descent rate] the target altitude ramps down at
+    pub landing_pace: Option<[f32; 2]>,
+
+    // Share of the gap to the latest goto target closed each step (1 = jump straight to it),
+    // and that target while the filtered one is still catching up
+    pub target_filter: f32,
+    pub commanded_target: Option<[f32; 3]>,
+
+    // Personality multipliers on velocity response rate, drag and yaw speed
+    pub personality: [f32; 3],
+
+    // Progress of the automatic fault response; latched until reset
+    pub fault_phase: Option<FaultPhase>,
+
+    // Where the drone spawned; the low-battery failsafe returns here
+    pub home: [f32; 3],
+    pub failsafe_stage: Option<FailsafeStage>,
+
+    // Sim time the current mode was entered; changes are flagged here and stamped by the swarm
+    mode_entered_time: f32,
+    mode_changed: bool,
+
     // Monitor mode state
     pub monitor_radius: f32,
     pub monitor_altitude: f32,
     pub monitor_angle: f32,
 
+    // Formation slot: offset from the formation anchor (centroid of the commanded slots)
+    pub formation_offset: Option<[f32; 3]>,
+    pub formation_anchor: [f32; 3],
+
     // PID state for position control
     pid_integral: [f32; 3],
     pid_prev_error: [f32; 3],
-}
 
-impl Drone {
-    pub fn new(id: usize, x: f32, y: f32, z: f32) -> Self {
-        Self {
-            id,
-            pos: [x, y, z],
-            vel: [0.0, 0.0, 0.0],
-            yaw: 0.0,
-            yaw_rate: 0.0,
-            mode: DroneMode::Idle,
-            target_pos: [x, y, z],
-            target_vel: [0.0, 0.0, 0.0],
+    // PID state for yaw control
+    yaw_integral: f32,
+    yaw_prev_error: f32,
+
+    // Position controller output (velocity correction) magnitude integrated over CONTROL_EFFORT_WINDOW
+    control_effort: f32,
+
+    // Time (s) spent tracking a position target while moving slower than STUCK_SPEED
+    stuck_time: f32,
+
+    // External acceleration (disturbances such as downwash), set each step
+    pub external_accel: [f32; 3],
+
+    // Separation velocity from nearby drones (collision avoidance), set each step
+    pub avoidance_vel: [f32; 3],
+
+    // Drones sharing an avoidance group don't avoid each other; None avoids every drone
+    pub avoidance_group: Option<u32>,
+
+    // Acceleration applied last step, for the jerk limit
+    pub prev_accel: [f32; 3],
+
+    // GPS dropout: drift rate (m/s) of the dead-reckoned estimate, and its accumulated error
+    pub gps_drift_rate: Option<f32>,
+    pub position_error: [f32; 3],
+
+    // Barometric altitude drift [amplitude (m), period (s)] and time since it started; it
+    // sets the vertical estimate error
+    pub baro_drift: Option<[f32; 2]>,
+    pub baro_time: f32,
+
+    // Recent positions (oldest first) for rendering motion trails
+    pub trail: VecDeque<[f32; 3]>,
+
+    // Per-drone sensor noise override (pos_stddev, vel_stddev); None uses the swarm setting
+    pub sensor_noise: Option<[f32; 2]>,
+
+    // Per-drone control gains override; None uses the swarm setting
+    pub control_gains: Option<ControlGains>,
+
+    // Mission totals: path length (m), rotor thrust integrated over time (N*s, an energy
+    // proxy) and time spent out of Idle (s)
+    pub distance_traveled: f32,
+    pub energy_used: f32,
+    pub flight_time: f32,
+}
+
+impl Drone {
+    pub fn new(id: usize, x: f32, y: f32, z: f32) -> Self {
+        Self {
+            id,
+            pos: [x, y, z],
+            vel: [0.0, 0.0, 0.0],
+            yaw: 0.0,
+            yaw_rate: 0.0,
+            mode: DroneMode::Idle,
+            target_pos: [x, y, z],
+            target_vel: [0.0, 0.0, 0.0],
             target_yaw: 0.0,
             battery: 100.0,
             healthy: true,
+            selected: false,
+            mass: DEFAULT_MASS,
+            speed_multiplier: 1.0,
+            frozen: false,
+            paused: false,
+            crashed: false,
+            ground_impact: false,
+            time_scale: 1.0,
+            field_gain: 1.0,
+            trajectory: None,
+            path: None,
+            track_point: None,
+            landing_pace: None,
+            target_filter: 1.0,
+            commanded_target: None,
+            personality: [1.0, 1.0, 1.0],
+            fault_phase: None,
+            home: [x, y, z],
+            failsafe_stage: None,
+            mode_entered_time: 0.0,
+            mode_changed: false,
+            radius: DEFAULT_RADIUS,
             monitor_radius: 2.0,
             monitor_altitude: 1.5,
             monitor_angle: 0.0,
+            formation_offset: None,
+            formation_anchor: [0.0, 0.0, 0.0],
             pid_integral: [0.0, 0.0, 0.0],
             pid_prev_error: [0.0, 0.0, 0.0],
+            yaw_integral: 0.0,
+            yaw_prev_error: 0.0,
+            control_effort: 0.0,
+            stuck_time: 0.0,
+            sensor_noise: None,
+            control_gains: None,
+            distance_traveled: 0.0,
+            energy_used: 0.0,
+            flight_time: 0.0,
+            external_accel: [0.0, 0.0, 0.0],
+            avoidance_vel: [0.0, 0.0, 0.0],
+            avoidance_group: None,
+            prev_accel: [0.0, 0.0, 0.0],
+            trail: VecDeque::new(),
+            gps_drift_rate: None,
+            baro_drift: None,
+            baro_time: 0.0,
+            position_error: [0.0, 0.0, 0.0],
+        }
+    }
+
+    /// Reset PID controller state
+    pub fn reset_pid(&mut self) {
+        self.pid_integral = [0.0, 0.0, 0.0];
+        self.pid_prev_error = [0.0, 0.0, 0.0];
+        self.yaw_integral = 0.0;
+        self.yaw_prev_error = 0.0;
+    }
+
+    /// Re-target the assigned formation slot, if any
+    fn retarget_formation_slot(&mut self) {
+        if let Some(offset) = self.formation_offset {
+            if matches!(self.mode, DroneMode::Goto | DroneMode::Hover) {
+                self.target_pos = [
+                    self.formation_anchor[0] + offset[0],
+                    self.formation_anchor[1] + offset[1],
+                    self.formation_anchor[2] + offset[2],
+                ];
+            }
+        }
+    }
+
+    /// Switch mode, optionally clearing controller state if the mode actually changes
+    fn set_mode(&mut self, mode: DroneMode, reset_pid_on_change: bool) {
+        if self.mode != mode {
+            self.mode = mode;
+            self.mode_changed = true;
+            self.path = None;
+            self.landing_pace = None;
+            self.commanded_target = None;
+            if reset_pid_on_change {
+                self.reset_pid();
+            }
+        }
+    }
+
+    /// Move the position target without restarting the controller (continuous maneuvers)
+    fn follow_target(&mut self, pos: [f32; 3], config: &StepConfig) {
+        self.set_mode(DroneMode::Goto, config.reset_pid_on_mode_change);
+        self.formation_offset = None;
+        self.trajectory = None;
+        self.path = None;
+        self.commanded_target = None;
+        self.target_pos = config.clamp_target(pos);
+    }
+
+    /// This drone's position, velocity and size for the avoidance pass, holding its goal if it
+    /// is hovering or in goto within `goal_tolerance` of the target
+    fn avoidance_neighbor(&self, goal_tolerance: Option<f32>) -> AvoidanceNeighbor {
+        let holding = goal_tolerance.is_some_and(|tolerance| {
+            matches!(self.mode, DroneMode::Hover | DroneMode::Goto) && self.target_error() <= tolerance
+        });
+        AvoidanceNeighbor { id: self.id, pos: self.pos, vel: self.vel, radius: self.radius, holding, group: self.avoidance_group }
+    }
+
+    /// Position the controller believes the drone is at (truth plus localization error)
+    fn estimated_pos(&self) -> [f32; 3] {
+        [
+            self.pos[0] + self.position_error[0],
+            self.pos[1] + self.position_error[1],
+            self.pos[2] + self.position_error[2],
+        ]
+    }
+
+    /// Position PID gains and velocity dynamics: the drone's override, else the swarm's
+    fn gains(&self, config: &StepConfig) -> ControlGains {
+        self.control_gains.unwrap_or(config.control_gains)
+    }
+
+    /// Compute velocity command using PID position control
+    fn compute_position_control(&mut self, dt: f32, max_vel: f32, config: &StepConfig) -> [f32; 3] {
+        let ControlGains { kp, ki, kd, .. } = self.gains(config);
+
+        let mut vel_cmd = [0.0f32; 3];
+        let estimated_pos = self.estimated_pos();
+
+        for i in 0..3 {
+            let error = self.target_pos[i] - estimated_pos[i];
+
+            // Proportional
+            let p_term = kp * error;
+
+            // Integral with anti-windup
+            self.pid_integral[i] += error * dt;
+            self.pid_integral[i] = self.pid_integral[i].clamp(-1.0, 1.0);
+            let i_term = ki * self.pid_integral[i];
+
+            // Derivative
+            let d_term = if dt > 0.0 {
+                kd * (error - self.pid_prev_error[i]) / dt
+            } else {
+                0.0
+            };
+
+            self.pid_prev_error[i] = error;
+
+            vel_cmd[i] = (p_term + i_term + d_term).clamp(-max_vel, max_vel);
+        }
+
+        let correction = (vel_cmd[0].powi(2) + vel_cmd[1].powi(2) + vel_cmd[2].powi(2)).sqrt();
+        self.control_effort += correction * dt;
+        vel_cmd
+    }
+
+    /// Update drone physics for one timestep
+    pub fn step(&mut self, dt: f32, max_vel: f32, monitor_center: Option<[f32; 3]>, monitor_orbit_speed: f32, config: &StepConfig) {
+        let max_vel = (config.mode_max_vel(self.mode, max_vel) * self.speed_multiplier).min(config.max_speed);
+        let (start_pos, start_vel, flying) = (self.pos, self.vel, self.mode != DroneMode::Idle);
+
+        // A crashed drone ignores commands and coasts to rest
+        if self.crashed {
+            self.set_mode(DroneMode::Idle, config.reset_pid_on_mode_change);
+        }
+
+        // Without GPS the dead-reckoned estimate drifts in a fixed per-drone direction
+        if let Some(drift_rate) = self.gps_drift_rate {
+            let heading = 2.0 * PI * hash_unit(splitmix64(self.id as u64));
+            self.position_error[0] += drift_rate * config.cos(heading) * dt;
+            self.position_error[1] += drift_rate * config.sin(heading) * dt;
+        }
+
+        // Baro bias wanders sinusoidally from zero, so the controlled altitude drifts with it
+        if let Some([amplitude, period]) = self.baro_drift {
+            self.baro_time += dt;
+            self.position_error[2] = amplitude * config.sin(2.0 * PI * self.baro_time / period);
+        }
+
+        // Filtered goto: the effective target eases toward the commanded one
+        if let Some(commanded) = self.commanded_target {
+            self.target_pos = [0, 1, 2].map(|i| self.target_pos[i] + self.target_filter * (commanded[i] - self.target_pos[i]));
+        }
+
+        // Leaky integral: effort from older than the window fades out
+        self.control_effort *= (1.0 - dt / CONTROL_EFFORT_WINDOW).max(0.0);
+
+        if self.mode == DroneMode::Goto {
+            let estimated = self.estimated_pos();
+            if let Some(path) = &mut self.path {
+                if distance(estimated, self.target_pos) <= path.tolerance {
+                    path.index += 1;
+                    if path.index == path.waypoints.len() && path.looping {
+                        path.index = 0;
+                    }
+                    match path.waypoints.get(path.index) {
+                        Some(&next) => self.target_pos = next,
+                        None => self.path = None,
+                    }
+                }
+            }
+            if let Some(trajectory) = &mut self.trajectory {
+                trajectory.elapsed += dt;
+                self.target_pos = min_jerk(trajectory.start, trajectory.end, trajectory.duration, trajectory.elapsed);
+                if trajectory.elapsed >= trajectory.duration {
+                    self.trajectory = None;
+                }
+            }
+        }
+
+        // Targets inside an obstacle are blocked at its wall; in safe mode, targets (however they
+        // were set) stay inside its volume
+        if matches!(self.mode, DroneMode::Takeoff | DroneMode::Goto | DroneMode::Hover) {
+            self.target_pos = config.clamp_to_safe_volume(self.target_pos);
+            self.target_pos = config.obstacles.block_target(self.pos, self.target_pos, self.radius);
+        }
+
+        match self.mode {
+            DroneMode::Idle => {
+                let wind = config.wind_at(self.pos[2]);
+                if self.pos[2] > LANDED_ALTITUDE && wind != [0.0, 0.0, 0.0] {
+                    // Unpowered but airborne: drag carries the drone along with the wind
+                    let drag = self.gains(config).drag * self.personality[1];
+                    for ((v, p), w) in self.vel.iter_mut().zip(&mut self.pos).zip(wind) {
+                        *v += drag * (w - *v) * dt;
+                        *p += *v * dt;
+                    }
+                } else {
+                    // Slow down to stop
+                    self.vel[0] *= 0.95;
+                    self.vel[1] *= 0.95;
+                    self.vel[2] *= 0.95;
+                }
+            }
+
+            DroneMode::Takeoff | DroneMode::Landing | DroneMode::Goto | DroneMode::Hover => {
+                // Position control mode; a paced landing tracks its descending target with feedforward
+                let pace = self.landing_pace.filter(|_| self.mode == DroneMode::Landing);
+                if let Some([rate, _]) = pace {
+                    self.target_pos[2] -= rate * dt;
+                }
+                let mut vel_cmd = self.compute_position_control(dt, max_vel, config);
+                if let Some([rate, max_rate]) = pace {
+                    vel_cmd[2] = (vel_cmd[2] - rate).max(-max_rate);
+                }
+                if let (DroneMode::Landing, Some([flare_altitude, flare_rate])) = (self.mode, config.landing_flare) {
+                    if self.pos[2] < flare_altitude {
+                        vel_cmd[2] = vel_cmd[2].max(-flare_rate);
+                    }
+                }
+                self.apply_velocity_control(vel_cmd, dt, config);
+
+                // Check for mode transitions
+                let estimated_pos = self.estimated_pos();
+                let dist = ((self.target_pos[0] - estimated_pos[0]).powi(2)
+                          + (self.target_pos[1] - estimated_pos[1]).powi(2)
+                          + (self.target_pos[2] - estimated_pos[2]).powi(2)).sqrt();
+
+                if self.mode == DroneMode::Landing && self.pos[2] < LANDED_ALTITUDE {
+                    self.set_mode(DroneMode::Idle, config.reset_pid_on_mode_change);
+                    self.vel = [0.0, 0.0, 0.0];
+                } else if self.mode == DroneMode::Takeoff && dist < 0.1 {
+                    self.set_mode(DroneMode::Hover, config.reset_pid_on_mode_change);
+                }
+            }
+
+            DroneMode::Velocity => {
+                // Direct velocity control
+                let mut vel_cmd = self.target_vel;
+                if config.mode_velocity_limits.contains_key(&DroneMode::Velocity) {
+                    vel_cmd = vel_cmd.map(|v| v.clamp(-max_vel, max_vel));
+                }
+                self.apply_velocity_control(vel_cmd, dt, config);
+            }
+
+            DroneMode::FieldFollow => {
+                // Hold altitude by position control; move horizontally along the field gradient
+                self.target_pos[0] = self.pos[0];
+                self.target_pos[1] = self.pos[1];
+                let mut vel_cmd = self.compute_position_control(dt, max_vel, config);
+                if let Some(field) = &config.potential_field {
+                    let gradient = field.gradient(self.pos[0], self.pos[1]);
+                    let mut horizontal = [self.field_gain * gradient[0], self.field_gain * gradient[1]];
+                    let speed = config.hypot(horizontal[0], horizontal[1]);
+                    if speed > max_vel {
+                        horizontal = horizontal.map(|v| v * max_vel / speed);
+                    }
+                    vel_cmd[0] = horizontal[0];
+                    vel_cmd[1] = horizontal[1];
+                }
+                self.apply_velocity_control(vel_cmd, dt, config);
+            }
+
+            DroneMode::Flock => {
+                // Boids velocity (set by the swarm each step), held between cruise speed and the limit
+                let mut vel_cmd = self.target_vel;
+                let speed = (vel_cmd[0] * vel_cmd[0] + vel_cmd[1] * vel_cmd[1] + vel_cmd[2] * vel_cmd[2]).sqrt();
+                let cruise = FLOCK_CRUISE_FRACTION * max_vel;
+                if speed > max_vel {
+                    vel_cmd = vel_cmd.map(|v| v * max_vel / speed);
+                } else if speed > 1e-3 && speed < cruise {
+                    vel_cmd = vel_cmd.map(|v| v * cruise / speed);
+                }
+                if config.hypot(vel_cmd[0], vel_cmd[1]) > 0.1 {
+                    self.target_yaw = config.atan2(vel_cmd[1], vel_cmd[0]);
+                }
+                self.apply_velocity_control(vel_cmd, dt, config);
+            }
+
+            DroneMode::Monitor => {
+                // Orbital surveillance mode
+                if let Some(center) = monitor_center {
+                    // Update angle
+                    self.monitor_angle += monitor_orbit_speed * dt;
+                    if self.monitor_angle > 2.0 * PI {
+                        self.monitor_angle -= 2.0 * PI;
+                    }
+
+                    // Calculate orbital position
+                    self.target_pos[0] = center[0] + self.monitor_radius * config.cos(self.monitor_angle);
+                    self.target_pos[1] = center[1] + self.monitor_radius * config.sin(self.monitor_angle);
+                    self.target_pos[2] = self.monitor_altitude;
+
+                    // Face towards center
+                    let dx = center[0] - self.target_pos[0];
+                    let dy = center[1] - self.target_pos[1];
+                    self.target_yaw = config.atan2(dy, dx);
+
+                    // Use position control to reach orbital position
+                    let vel_cmd = self.compute_position_control(dt, max_vel, config);
+                    self.apply_velocity_control(vel_cmd, dt, config);
+                }
+            }
+        }
+
+        // A tracked point overrides the mode's heading (skipped directly above or below it)
+        if let Some(point) = self.track_point {
+            let dx = point[0] - self.pos[0];
+            let dy = point[1] - self.pos[1];
+            if config.hypot(dx, dy) > 1e-3 {
+                self.target_yaw = config.atan2(dy, dx);
+            }
+        }
+
+        // Update yaw: Velocity mode turns at its commanded rate; other modes servo to target_yaw,
+        // the controller output being a rate command, or a torque with inertia
+        if self.mode != DroneMode::Velocity || self.track_point.is_some() {
+            let yaw_cmd = self.compute_yaw_control(dt, config) * self.personality[2];
+            self.yaw_rate = match config.yaw_dynamics {
+                Some([inertia, damping]) => self.yaw_rate + (yaw_cmd - damping * self.yaw_rate) / inertia * dt,
+                None => yaw_cmd,
+            };
+        }
+        self.yaw_rate = self.yaw_rate.clamp(-config.max_yaw_rate, config.max_yaw_rate);
+        self.yaw += self.yaw_rate * dt;
+
+        if let Some([_, impact_speed]) = config.crash_detection {
+            // Reached the floor this step (it was above it a step ago) coming down too fast
+            let floor = config.world_min[2];
+            self.ground_impact |= !self.crashed && self.pos[2] <= floor
+                && self.pos[2] - self.vel[2] * dt > floor && self.vel[2] < -impact_speed;
+        }
+
+        // Clamp position to world bounds and geofence, then out of any obstacle
+        self.pos = config.clamp_position(self.pos);
+        config.obstacles.push_out(&mut self.pos, &mut self.vel, self.radius);
+
+        // Update health based on bounds and battery
+        let [horizontal, vertical_min, vertical_max] = config.health_limits();
+        self.healthy = self.pos[0].abs() < horizontal
+                    && self.pos[1].abs() < horizontal
+                    && self.pos[2] >= vertical_min
+                    && self.pos[2] <= vertical_max
+                    && self.battery > 0.0
+                    && !self.crashed;
+
+        let tracking = !matches!(self.mode, DroneMode::Idle | DroneMode::Velocity | DroneMode::FieldFollow | DroneMode::Flock);
+        let speed = (self.vel[0].powi(2) + self.vel[1].powi(2) + self.vel[2].powi(2)).sqrt();
+        self.stuck_time = if tracking && speed < STUCK_SPEED { self.stuck_time + dt } else { 0.0 };
+
+        // Telemetry: thrust is what produced this step's acceleration against gravity
+        self.distance_traveled += distance(start_pos, self.pos);
+        if flying && dt > 0.0 {
+            let accel = [0, 1, 2].map(|i| (self.vel[i] - start_vel[i]) / dt);
+            let thrust = self.mass * (accel[0].powi(2) + accel[1].powi(2) + (accel[2] + config.gravity).powi(2)).sqrt();
+            self.energy_used += thrust * dt;
+            self.flight_time += dt;
+        }
+    }
+
+    /// Distance from the drone to its position target
+    fn target_error(&self) -> f32 {
+        ((self.target_pos[0] - self.pos[0]).powi(2)
+            + (self.target_pos[1] - self.pos[1]).powi(2)
+            + (self.target_pos[2] - self.pos[2]).powi(2)).sqrt()
+    }
+
+    /// Compute yaw rate command using PID heading control
+    fn compute_yaw_control(&mut self, dt: f32, config: &StepConfig) -> f32 {
+        let yaw_error = config.wrap_angle(self.target_yaw - self.yaw);
+
+        self.yaw_integral = (self.yaw_integral + yaw_error * dt).clamp(-1.0, 1.0);
+
+        // Difference of two wrapped errors can itself cross the ±PI seam
+        let d_term = if dt > 0.0 {
+            config.yaw_kd * config.wrap_angle(yaw_error - self.yaw_prev_error) / dt
+        } else {
+            0.0
+        };
+        self.yaw_prev_error = yaw_error;
+
+        (config.yaw_kp * yaw_error + config.yaw_ki * self.yaw_integral + d_term).clamp(-PI, PI)
+    }
+
+    /// Apply velocity control with simple dynamics
+    fn apply_velocity_control(&mut self, target_vel: [f32; 3], dt: f32, config: &StepConfig) {
+        // Velocity response (like a first-order system)
+        let gains = self.gains(config);
+
+        let target_vel = [0, 1, 2].map(|i| target_vel[i] + self.avoidance_vel[i]);
+
+        // Drag acts on airspeed, so wind pushes the drone along
+        let wind = config.wind_at(self.pos[2]);
+        let response_rate = gains.response_rate * self.personality[0];
+        let drag = gains.drag * self.personality[1];
+        let mut accel = [0.0f32; 3];
+        for i in 0..3 {
+            accel[i] = response_rate * (target_vel[i] - self.vel[i]) - drag * (self.vel[i] - wind[i]) + self.external_accel[i];
+        }
+
+        // Horizontal acceleration is limited by how far the drone can tilt
+        let max_horizontal = config.max_horizontal_accel();
+        let horizontal = (accel[0] * accel[0] + accel[1] * accel[1]).sqrt();
+        if horizontal > max_horizontal {
+            let scale = max_horizontal / horizontal;
+            accel[0] *= scale;
+            accel[1] *= scale;
+        }
+
+        // Acceleration and jerk limits; both are magnitudes of the full vector
+        let magnitude = (accel[0] * accel[0] + accel[1] * accel[1] + accel[2] * accel[2]).sqrt();
+        if magnitude > config.max_accel {
+            accel = accel.map(|a| a * config.max_accel / magnitude);
+        }
+        let change = [0, 1, 2].map(|i| accel[i] - self.prev_accel[i]);
+        let change_magnitude = (change[0] * change[0] + change[1] * change[1] + change[2] * change[2]).sqrt();
+        let max_change = config.max_jerk * dt;
+        if change_magnitude > max_change {
+            accel = [0, 1, 2].map(|i| self.prev_accel[i] + change[i] * max_change / change_magnitude);
+        }
+        self.prev_accel = accel;
+
+        let previous_heading = config.atan2(self.vel[1], self.vel[0]);
+        let previous_speed = config.hypot(self.vel[0], self.vel[1]);
+        for (v, a) in self.vel.iter_mut().zip(accel) {
+            *v += a * dt;
+        }
+
+        // Goto arcs no tighter than the minimum turn radius: heading follows the command at no
+        // more than speed / radius, and the drone keeps up to the commanded speed through the turn
+        // instead of braking to reverse in place
+        if config.min_turn_radius > 0.0 && self.mode == DroneMode::Goto && previous_speed > TURN_RADIUS_MIN_SPEED {
+            let command_speed = config.hypot(target_vel[0], target_vel[1]);
+            let speed = config.hypot(self.vel[0], self.vel[1]).max(previous_speed.min(command_speed));
+            let max_turn = speed / config.min_turn_radius * dt;
+            let turn = config.wrap_angle(config.atan2(target_vel[1], target_vel[0]) - previous_heading);
+            if speed > TURN_RADIUS_MIN_SPEED && turn.abs() > max_turn {
+                let heading = previous_heading + max_turn.copysign(turn);
+                self.vel[0] = speed * config.cos(heading);
+                self.vel[1] = speed * config.sin(heading);
+            }
+        }
+
+        // Integrate position
+        self.pos[0] += self.vel[0] * dt;
+        self.pos[1] += self.vel[1] * dt;
+        self.pos[2] += self.vel[2] * dt;
+    }
+}
+
+/// Circular ground pad where landed, idle drones recharge
+#[derive(Clone)]
+pub struct ChargeZone {
+    pub center: [f32; 2],
+    pub radius: f32,
+    pub charge_rate: f32,
+}
+
+impl ChargeZone {
+    fn contains(&self, pos: [f32; 3]) -> bool {
+        let dx = pos[0] - self.center[0];
+        let dy = pos[1] - self.center[1];
+        dx * dx + dy * dy <= self.radius * self.radius
+    }
+}
+
+/// How goto/formation targets outside the world bounds are handled
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OutOfBoundsPolicy {
+    /// Clamp the target onto the boundary
+    Clamp,
+    /// Ignore the command for that drone
+    Reject,
+    /// Raise ValueError
+    Error,
+}
+
+/// What happens to an event recorded while the event buffer is at capacity
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EventBufferPolicy {
+    /// Discard the oldest buffered event to make room
+    DropOldest,
+    /// Discard the new event
+    DropNewest,
+    /// Discard the new event and raise on the next drain_events
+    Error,
+}
+
+/// How formation commands match drones to slots
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SlotAssignment {
+    /// Drone order: the i-th drone takes the i-th slot
+    Index,
+    /// Minimum total travel distance
+    Nearest,
+    /// Minimum latest arrival (distance / per-drone speed), then minimum total travel time
+    Fastest,
+}
+
+/// Horizontal collision avoidance for landing drones near the ground
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LandingAvoidance {
+    /// Same as in flight
+    Full,
+    /// Horizontal push scaled by LANDING_AVOIDANCE_REDUCTION
+    Reduced,
+    /// Only the vertical component, so drones can settle onto neighboring pads
+    VerticalOnly,
+}
+
+impl LandingAvoidance {
+    /// Factor applied to the horizontal avoidance of a landing drone below the altitude
+    fn horizontal_factor(&self) -> f32 {
+        match self {
+            LandingAvoidance::Full => 1.0,
+            LandingAvoidance::Reduced => LANDING_AVOIDANCE_REDUCTION,
+            LandingAvoidance::VerticalOnly => 0.0,
+        }
+    }
+}
+
+/// How collision avoidance between drones weakens with distance, as a weight that is zero at
+/// the avoidance radius
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AvoidanceFalloff {
+    /// 1 - d / r: 1 at contact
+    Linear,
+    /// r / d - 1: rises steeply close in
+    Inverse,
+    /// (r / d)^2 - 1: steeper still
+    InverseSquare,
+    /// exp(-k d / r), shifted and scaled to run from 1 at contact to 0 at the radius; softer
+    /// than linear away from contact
+    Exponential,
+}
+
+impl AvoidanceFalloff {
+    /// Weight at `fraction` = distance / radius in [0, 1); the inverse curves stop growing
+    /// below AVOIDANCE_FALLOFF_FLOOR
+    fn weight(&self, fraction: f32) -> f32 {
+        let floored = fraction.max(AVOIDANCE_FALLOFF_FLOOR);
+        match self {
+            AvoidanceFalloff::Linear => 1.0 - fraction,
+            AvoidanceFalloff::Inverse => 1.0 / floored - 1.0,
+            AvoidanceFalloff::InverseSquare => 1.0 / (floored * floored) - 1.0,
+            AvoidanceFalloff::Exponential => {
+                let tail = (-AVOIDANCE_FALLOFF_EXP_RATE).exp();
+                ((-AVOIDANCE_FALLOFF_EXP_RATE * fraction).exp() - tail) / (1.0 - tail)
+            }
+        }
+    }
+}
+
+/// Mapping from remaining battery energy (%) to the reported percentage
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DischargeCurve {
+    /// Reported percentage equals remaining energy
+    Linear,
+    /// LiPo-like: an early sag off full charge, a long plateau, then a cliff when nearly empty
+    Lipo,
+}
+
+/// (energy, reported) breakpoints of the LiPo curve, linearly interpolated
+const LIPO_CURVE: [(f32, f32); 8] = [
+    (0.0, 0.0), (5.0, 20.0), (10.0, 35.0), (20.0, 50.0),
+    (50.0, 70.0), (80.0, 85.0), (95.0, 94.0), (100.0, 100.0),
+];
+
+impl DischargeCurve {
+    /// Reported percentage for `energy` % of charge remaining
+    fn reported(&self, energy: f32) -> f32 {
+        match self {
+            DischargeCurve::Linear => energy,
+            DischargeCurve::Lipo => {
+                let energy = energy.clamp(0.0, 100.0);
+                LIPO_CURVE.windows(2)
+                    .find(|w| energy <= w[1].0)
+                    .map_or(100.0, |w| {
+                        let ((e0, r0), (e1, r1)) = (w[0], w[1]);
+                        r0 + (energy - e0) / (e1 - e0) * (r1 - r0)
+                    })
+            }
+        }
+    }
+}
+
+/// One group in `multi_formation`: (ids, formation type, center, params)
+type FormationAssignment = (Vec<usize>, String, [f32; 3], Vec<f32>);
+
+/// Saved per-drone targets for undoing a command
+#[derive(Clone)]
+struct TargetSnapshot {
+    target_pos: [f32; 3],
+    target_yaw: f32,
+    mode: DroneMode,
+    formation_offset: Option<[f32; 3]>,
+    formation_anchor: [f32; 3],
+}
+
+impl TargetSnapshot {
+    fn from_drone(drone: &Drone) -> Self {
+        Self {
+            target_pos: drone.target_pos,
+            target_yaw: drone.target_yaw,
+            mode: drone.mode,
+            formation_offset: drone.formation_offset,
+            formation_anchor: drone.formation_anchor,
+        }
+    }
+
+    fn restore(&self, drone: &mut Drone) {
+        drone.target_pos = self.target_pos;
+        drone.target_yaw = self.target_yaw;
+        drone.mode_changed |= drone.mode != self.mode;
+        drone.mode = self.mode;
+        drone.formation_offset = self.formation_offset;
+        drone.formation_anchor = self.formation_anchor;
+        drone.trajectory = None;
+        drone.path = None;
+        drone.commanded_target = None;
+        drone.reset_pid();
+    }
+}
+
+/// Continuous swarm maneuver that re-targets drones every step from `sim_time`
+#[derive(Clone)]
+pub enum Maneuver {
+    /// Radial formation whose radius oscillates between two bounds
+    Breathe {
+        center: [f32; 3],
+        min_radius: f32,
+        max_radius: f32,
+        period: f32,
+        start_time: f32,
+    },
+    /// Drones evenly spaced along a closed polygon, circulating at constant speed
+    Patrol {
+        vertices: Vec<[f32; 2]>,
+        altitude: f32,
+        speed: f32,
+        start_time: f32,
+    },
+    /// Traveling vertical sine wave across the swarm; horizontal positions held at their start
+    Wave {
+        direction: [f32; 2],
+        wavelength: f32,
+        amplitude: f32,
+        speed: f32,
+        base_altitude: f32,
+        start_time: f32,
+        anchors: Vec<[f32; 2]>,
+    },
+    /// Recorded per-drone positions replayed as targets, interpolated between frames
+    Playback {
+        frames: Vec<Vec<[f32; 3]>>,
+        fps: f32,
+        start_time: f32,
+    },
+    /// Every position-holding drone's target (and formation anchor) translating at a constant velocity
+    Cruise {
+        velocity: [f32; 3],
+    },
+    /// Targets blended from each drone's start to its slot in a new formation over `duration`
+    Morph {
+        start: Vec<[f32; 3]>,
+        end: Vec<[f32; 3]>,
+        duration: f32,
+        easing: Easing,
+        start_time: f32,
+    },
+}
+
+/// Easing curve mapping normalized time to blend progress; every curve runs from exactly 0 to 1
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Easing {
+    Linear,
+    /// Quadratic: starts slow
+    EaseIn,
+    /// Quadratic: ends slow
+    EaseOut,
+    /// Cubic: slow at both ends
+    EaseInOut,
+    /// Overshoots the end in decaying bounces, like a dropped ball
+    Bounce,
+}
+
+impl Easing {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            "bounce" => Some(Easing::Bounce),
+            _ => None,
+        }
+    }
+
+    /// Progress at normalized time `t`, clamped to [0, 1] at the ends
+    fn apply(&self, t: f32) -> f32 {
+        if t <= 0.0 {
+            return 0.0;
+        }
+        if t >= 1.0 {
+            return 1.0;
+        }
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => if t < 0.5 { 4.0 * t * t * t } else { 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0 },
+            Easing::Bounce => {
+                // Four arcs of a ball bouncing up to the end value (Penner's ease-out bounce)
+                let (n, d) = (7.5625, 2.75);
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+/// Condition a mission objective checks against the swarm state
+#[derive(Clone)]
+enum ObjectiveKind {
+    /// Every drone horizontally within `radius` of `center`
+    AllInZone { center: [f32; 2], radius: f32 },
+    /// Every drone holding a formation slot is within `tolerance` of its target
+    FormationAchieved { tolerance: f32 },
+    /// Every drone is idle on the ground
+    AllLanded,
+}
+
+impl ObjectiveKind {
+    fn is_met(&self, drones: &[Drone]) -> bool {
+        match self {
+            ObjectiveKind::AllInZone { center, radius } => drones.iter().all(|d| {
+                (d.pos[0] - center[0]).hypot(d.pos[1] - center[1]) <= *radius
+            }),
+            ObjectiveKind::FormationAchieved { tolerance } => {
+                let mut slotted = drones.iter().filter(|d| d.formation_offset.is_some()).peekable();
+                slotted.peek().is_some() && slotted.all(|d| d.target_error() <= *tolerance)
+            }
+            ObjectiveKind::AllLanded => drones.iter().all(|d| {
+                d.mode == DroneMode::Idle && d.pos[2] < LANDED_ALTITUDE
+            }),
+        }
+    }
+}
+
+/// Named mission objective; stays complete once met
+#[derive(Clone)]
+struct Objective {
+    name: String,
+    kind: ObjectiveKind,
+    complete: bool,
+}
+
+/// An orbit slot left by a drone that rotated out to recharge
+#[derive(Clone, Copy)]
+struct OrbitVacancy {
+    radius: f32,
+    altitude: f32,
+    angle: f32,
+    vacated_at: f32,
+}
+
+/// Automatic response to a drone fault (empty battery or GPS dropout)
+#[derive(Clone, Copy)]
+struct FaultResponse {
+    climb_first: bool,
+    safe_altitude: f32,
+}
+
+/// Where a faulted drone is in its response
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FaultPhase {
+    /// Climbing to the safe altitude before landing
+    Climb,
+    /// Landing, or handed back to the operator
+    Descend,
+}
+
+/// Low-battery failsafe stage of a drone (set_failsafe); latched until recharged or reset
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FailsafeStage {
+    /// Flying to `target`, above home at the altitude the failsafe triggered
+    ReturnHome { target: [f32; 3] },
+    /// Landing (at home, or in place if the battery fell below the land threshold)
+    Land,
+}
+
+/// Battery rotation state for `monitor_with_rotation`
+#[derive(Clone)]
+struct MonitorRotation {
+    low_battery_threshold: f32,
+    pool: Vec<usize>,
+    vacancies: Vec<OrbitVacancy>,
+}
+
+/// Point at arc length `s` along the closed polygon through `vertices`
+fn point_on_perimeter(vertices: &[[f32; 2]], s: f32) -> [f32; 2] {
+    let n = vertices.len();
+    let mut remaining = s;
+    for i in 0..n {
+        let a = vertices[i];
+        let b = vertices[(i + 1) % n];
+        let len = ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt();
+        if remaining <= len && len > 0.0 {
+            let t = remaining / len;
+            return [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+        }
+        remaining -= len;
+    }
+    vertices[0]
+}
+
+/// Total length of the closed polygon through `vertices`
+fn perimeter_length(vertices: &[[f32; 2]]) -> f32 {
+    let n = vertices.len();
+    (0..n)
+        .map(|i| {
+            let a = vertices[i];
+            let b = vertices[(i + 1) % n];
+            ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt()
+        })
+        .sum()
+}
+
+/// Python-exposed drone state (for returning to Python)
+#[pyclass]
+#[derive(Clone)]
+pub struct PyDroneState {
+    #[pyo3(get)]
+    pub id: usize,
+    #[pyo3(get)]
+    pub pos: [f32; 3],
+    #[pyo3(get)]
+    pub vel: [f32; 3],
+    #[pyo3(get)]
+    pub yaw: f32,
+    #[pyo3(get)]
+    pub battery: f32,
+    #[pyo3(get)]
+    pub healthy: bool,
+    #[pyo3(get)]
+    pub selected: bool,
+}
+
+/// Timing stats from `RustSwarm.benchmark`
+#[pyclass]
+#[derive(Clone)]
+pub struct PyBenchmarkResult {
+    #[pyo3(get)]
+    pub num_drones: usize,
+    #[pyo3(get)]
+    pub steps: usize,
+    #[pyo3(get)]
+    pub total_time: f64,
+    #[pyo3(get)]
+    pub steps_per_sec: f64,
+    #[pyo3(get)]
+    pub us_per_drone_step: f64,
+    #[pyo3(get)]
+    pub threads: usize,
+}
+
+/// Cumulative per-drone mission stats from `RustSwarm.get_telemetry`
+#[pyclass]
+#[derive(Clone)]
+pub struct PyDroneTelemetry {
+    #[pyo3(get)]
+    pub id: usize,
+    #[pyo3(get)]
+    pub distance_traveled: f32,
+    #[pyo3(get)]
+    pub energy_used: f32,
+    #[pyo3(get)]
+    pub flight_time: f32,
+}
+
+/// Delta header flag: the message carries every field of every drone
+const DELTA_KEYFRAME: u8 = 1;
+
+/// Per-drone field mask bits in a state delta record
+const DELTA_POS: u8 = 1;
+const DELTA_VEL: u8 = 2;
+const DELTA_YAW: u8 = 4;
+const DELTA_BATTERY: u8 = 8;
+const DELTA_HEALTHY: u8 = 16;
+const DELTA_ALL: u8 = DELTA_POS | DELTA_VEL | DELTA_YAW | DELTA_BATTERY | DELTA_HEALTHY;
+
+/// Drone state quantized for network sync: pos/vel in mm(/s), yaw in 1e-4 rad, battery in 0.01%
+#[derive(Clone, Copy, PartialEq, Default)]
+struct QuantizedState {
+    pos: [i32; 3],
+    vel: [i32; 3],
+    yaw: i32,
+    battery: u16,
+    healthy: bool,
+}
+
+impl QuantizedState {
+    fn from_state(state: &PyDroneState) -> Self {
+        Self {
+            pos: state.pos.map(|v| (v * 1000.0).round() as i32),
+            vel: state.vel.map(|v| (v * 1000.0).round() as i32),
+            yaw: (state.yaw * 1e4).round() as i32,
+            battery: (state.battery.clamp(0.0, 100.0) * 100.0).round() as u16,
+            healthy: state.healthy,
+        }
+    }
+
+    fn to_state(self, id: usize) -> PyDroneState {
+        PyDroneState {
+            id,
+            pos: self.pos.map(|v| v as f32 / 1000.0),
+            vel: self.vel.map(|v| v as f32 / 1000.0),
+            yaw: self.yaw as f32 / 1e4,
+            battery: self.battery as f32 / 100.0,
+            healthy: self.healthy,
+            // Selection is local UI state and isn't part of the sync stream
+            selected: false,
+        }
+    }
+
+    /// Mask of fields that differ from `other`
+    fn changed_fields(&self, other: &Self) -> u8 {
+        let mut mask = 0;
+        if self.pos != other.pos { mask |= DELTA_POS; }
+        if self.vel != other.vel { mask |= DELTA_VEL; }
+        if self.yaw != other.yaw { mask |= DELTA_YAW; }
+        if self.battery != other.battery { mask |= DELTA_BATTERY; }
+        if self.healthy != other.healthy { mask |= DELTA_HEALTHY; }
+        mask
+    }
+}
+
+/// Encode `current` against the last synced states (little-endian)
+///
+/// Layout: flags u8, drone count u32, sim_time f32, record count u32, then per changed
+/// drone: id u32, field mask u8, and the masked fields in bit order (pos 3xi32, vel 3xi32,
+/// yaw i32, battery u16, healthy u8). Without a matching previous snapshot it is a keyframe.
+fn encode_state_delta(previous: Option<&[QuantizedState]>, current: &[QuantizedState], sim_time: f32) -> Vec<u8> {
+    let previous = previous.filter(|p| p.len() == current.len());
+    let mut records = Vec::new();
+    let mut record_count = 0u32;
+
+    for (id, state) in current.iter().enumerate() {
+        let mask = previous.map_or(DELTA_ALL, |p| state.changed_fields(&p[id]));
+        if mask == 0 {
+            continue;
+        }
+        record_count += 1;
+        records.extend_from_slice(&(id as u32).to_le_bytes());
+        records.push(mask);
+        if mask & DELTA_POS != 0 {
+            state.pos.iter().for_each(|v| records.extend_from_slice(&v.to_le_bytes()));
+        }
+        if mask & DELTA_VEL != 0 {
+            state.vel.iter().for_each(|v| records.extend_from_slice(&v.to_le_bytes()));
+        }
+        if mask & DELTA_YAW != 0 {
+            records.extend_from_slice(&state.yaw.to_le_bytes());
+        }
+        if mask & DELTA_BATTERY != 0 {
+            records.extend_from_slice(&state.battery.to_le_bytes());
+        }
+        if mask & DELTA_HEALTHY != 0 {
+            records.push(state.healthy as u8);
+        }
+    }
+
+    let mut out = Vec::with_capacity(13 + records.len());
+    out.push(if previous.is_none() { DELTA_KEYFRAME } else { 0 });
+    out.extend_from_slice(&(current.len() as u32).to_le_bytes());
+    out.extend_from_slice(&sim_time.to_le_bytes());
+    out.extend_from_slice(&record_count.to_le_bytes());
+    out.extend_from_slice(&records);
+    out
+}
+
+/// Apply an encoded delta to `states`; returns the message's sim_time
+fn decode_state_delta(states: &mut Vec<QuantizedState>, data: &[u8]) -> Result<f32, String> {
+    let mut cursor = 0;
+    let mut take = |n: usize| -> Result<&[u8], String> {
+        let bytes = data.get(cursor..cursor + n).ok_or("truncated state delta")?;
+        cursor += n;
+        Ok(bytes)
+    };
+    let read_u32 = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    let read_i32 = |b: &[u8]| i32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+
+    let flags = take(1)?[0];
+    let count = read_u32(take(4)?) as usize;
+    let sim_time = f32::from_le_bytes(take(4)?.try_into().unwrap());
+    let record_count = read_u32(take(4)?);
+
+    if flags & DELTA_KEYFRAME != 0 {
+        *states = vec![QuantizedState::default(); count];
+    } else if states.len() != count {
+        return Err("state delta does not match the decoder's drone count; a keyframe is needed".to_string());
+    }
+
+    for _ in 0..record_count {
+        let id = read_u32(take(4)?) as usize;
+        let mask = take(1)?[0];
+        let state = states.get_mut(id).ok_or("state delta references an unknown drone")?;
+        if mask & DELTA_POS != 0 {
+            for v in state.pos.iter_mut() {
+                *v = read_i32(take(4)?);
+            }
+        }
+        if mask & DELTA_VEL != 0 {
+            for v in state.vel.iter_mut() {
+                *v = read_i32(take(4)?);
+            }
+        }
+        if mask & DELTA_YAW != 0 {
+            state.yaw = read_i32(take(4)?);
+        }
+        if mask & DELTA_BATTERY != 0 {
+            let b = take(2)?;
+            state.battery = u16::from_le_bytes([b[0], b[1]]);
+        }
+        if mask & DELTA_HEALTHY != 0 {
+            state.healthy = take(1)?[0] != 0;
+        }
+    }
+    Ok(sim_time)
+}
+
+/// Receiver side of `RustSwarm.get_state_delta`: rebuilds drone states from applied deltas
+#[pyclass]
+#[derive(Default)]
+pub struct StateDeltaDecoder {
+    states: Vec<QuantizedState>,
+    sim_time: f32,
+}
+
+#[pymethods]
+impl StateDeltaDecoder {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply one delta message (must start from a keyframe)
+    pub fn apply(&mut self, data: Vec<u8>) -> PyResult<()> {
+        self.sim_time = decode_state_delta(&mut self.states, &data).map_err(PyValueError::new_err)?;
+        Ok(())
+    }
+
+    /// Reconstructed drone states (quantized)
+    pub fn get_states(&self) -> Vec<PyDroneState> {
+        self.states.iter().enumerate().map(|(id, s)| s.to_state(id)).collect()
+    }
+
+    /// Simulation time of the last applied delta
+    pub fn get_time(&self) -> f32 {
+        self.sim_time
+    }
+}
+
+/// Analytics record of a received command: (sim_time, command name, numeric args)
+type CommandLogEntry = (f32, String, Vec<f32>);
+
+/// Reported incident: (sim_time, event type, drone ids involved)
+type SwarmEvent = (f32, String, Vec<usize>);
+
+/// Checkpoint written by `snapshot`: the full drone vector plus swarm clock and monitor state
+#[derive(Serialize, Deserialize)]
+struct SwarmSnapshot {
+    version: u32,
+    sim_time: f32,
+    step_count: u64,
+    monitor_center: Option<[f32; 3]>,
+    drones: Vec<Drone>,
+}
+
+/// Limits overridden by safe mode, restored when it is turned off
+#[derive(Clone, Copy)]
+struct SafeModeRestore {
+    max_speed: f32,
+    max_accel: f32,
+    max_tilt: f32,
+    max_yaw_rate: f32,
+}
+
+/// The main swarm physics engine
+#[pyclass]
+pub struct RustSwarm {
+    drones: Vec<Drone>,
+    sim_time: f32,
+    physics_dt: f32,
+    max_velocity: f32,
+    speed_multiplier: f32,
+    monitor_center: Option<[f32; 3]>,
+    monitor_orbit_speed: f32,
+    hold_formation: bool,
+    charge_zones: Vec<ChargeZone>,
+    // Flat battery drain (%/min) from the last update_batteries, and per-mode overrides
+    drain_rate: f32,
+    mode_drain_rates: HashMap<DroneMode, f32>,
+    config: StepConfig,
+    maneuver: Option<Maneuver>,
+    scene_scale: f32,
+    // World bounds [min, max] before scene scaling (m)
+    world_bounds: [[f32; 3]; 2],
+    safe_mode: Option<SafeModeRestore>,
+    step_count: u64,
+    sensor_noise: [f32; 2],
+    noise_seed: u64,
+    undo_history: VecDeque<Vec<TargetSnapshot>>,
+    undo_depth: usize,
+    downwash_strength: Option<f32>,
+    collision_avoidance: Option<[f32; 3]>,
+    landing_avoidance: (LandingAvoidance, f32),
+    // Arrival tolerance within which drones keep their goal against avoidance; None disables it
+    goal_priority: Option<f32>,
+    avoidance_falloff: AvoidanceFalloff,
+    adaptive_avoidance: bool,
+    wind_gust: Option<[f32; 2]>,
+    discharge_curve: DischargeCurve,
+    out_of_bounds_policy: OutOfBoundsPolicy,
+    slot_assignment: SlotAssignment,
+    enforce_slot_spacing: bool,
+    trail_length: usize,
+    monitor_rotation: Option<MonitorRotation>,
+    panic_landing: bool,
+    objectives: Vec<Objective>,
+    last_sync: Option<Vec<QuantizedState>>,
+    roles: Vec<(usize, [f32; 3])>,
+    length_unit: f32,
+    command_log: Option<Vec<CommandLogEntry>>,
+    recording: Option<Recording>,
+    events: VecDeque<SwarmEvent>,
+    // Event buffer capacity and overflow policy; None keeps every event
+    event_buffer: Option<(usize, EventBufferPolicy)>,
+    // Events discarded under the "error" policy since the last drain
+    dropped_events: usize,
+    fault_response: Option<FaultResponse>,
+    failsafe: Option<[f32; 2]>,
+    spatial_grid: SpatialGrid,
+}
+
+#[pymethods]
+impl RustSwarm {
+    #[new]
+    #[pyo3(signature = (num_drones, physics_hz=240))]
+    pub fn new(num_drones: usize, physics_hz: u32) -> Self {
+        let grid_size = (num_drones as f32).sqrt().ceil() as usize;
+        let spacing = 0.5;
+
+        let mut drones = Vec::with_capacity(num_drones);
+        for i in 0..num_drones {
+            let row = i / grid_size;
+            let col = i % grid_size;
+            let x = (col as f32 - grid_size as f32 / 2.0) * spacing;
+            let y = (row as f32 - grid_size as f32 / 2.0) * spacing;
+            let z = 0.1;
+            drones.push(Drone::new(i, x, y, z));
+        }
+        let mut spatial_grid = SpatialGrid::new(SPATIAL_CELL_SIZE, WORLD_MIN, WORLD_MAX);
+        spatial_grid.rebuild(drones.iter().map(|d| (d.id, d.pos)));
+
+        Self {
+            drones,
+            sim_time: 0.0,
+            physics_dt: 1.0 / physics_hz as f32,
+            max_velocity: 2.0,
+            speed_multiplier: 1.0,
+            monitor_center: None,
+            monitor_orbit_speed: 0.3,
+            hold_formation: false,
+            charge_zones: Vec::new(),
+            drain_rate: DEFAULT_DRAIN_RATE,
+            mode_drain_rates: HashMap::new(),
+            config: StepConfig::default(),
+            maneuver: None,
+            scene_scale: 1.0,
+            world_bounds: [WORLD_MIN, WORLD_MAX],
+            safe_mode: None,
+            step_count: 0,
+            sensor_noise: [0.0, 0.0],
+            noise_seed: 0,
+            undo_history: VecDeque::new(),
+            undo_depth: 16,
+            downwash_strength: None,
+            collision_avoidance: None,
+            landing_avoidance: (LandingAvoidance::Full, 1.0),
+            goal_priority: None,
+            avoidance_falloff: AvoidanceFalloff::Linear,
+            adaptive_avoidance: false,
+            wind_gust: None,
+            discharge_curve: DischargeCurve::Linear,
+            out_of_bounds_policy: OutOfBoundsPolicy::Clamp,
+            slot_assignment: SlotAssignment::Index,
+            enforce_slot_spacing: true,
+            trail_length: 0,
+            monitor_rotation: None,
+            panic_landing: false,
+            objectives: Vec::new(),
+            last_sync: None,
+            roles: Vec::new(),
+            length_unit: 1.0,
+            command_log: None,
+            recording: None,
+            events: VecDeque::new(),
+            event_buffer: None,
+            dropped_events: 0,
+            fault_response: None,
+            failsafe: None,
+            spatial_grid,
+        }
+    }
+
+    /// Step physics for all drones (parallelized with rayon, sequential in lockstep mode)
+    pub fn step(&mut self) -> f32 {
+        // Changes commanded since the last step happened at the current time
+        self.stamp_mode_changes();
+        self.apply_maneuver();
+        self.rotate_monitor_drones();
+        self.sequence_panic_landing();
+        self.respond_to_faults();
+        self.apply_failsafe();
+        if let Some([amplitude, frequency]) = self.wind_gust {
+            self.config.wind_gust = amplitude * self.config.sin(2.0 * PI * frequency * self.sim_time);
+        }
+
+        let dt = self.physics_dt;
+        self.config.obstacles.advance(dt);
+        let max_vel = self.max_velocity * self.speed_multiplier * self.scene_scale;
+        let monitor_center = self.monitor_center;
+        let monitor_orbit_speed = self.monitor_orbit_speed;
+        let hold_formation = self.hold_formation;
+        let config = &self.config;
+
+        // Read-only snapshot of airborne drones for neighbor interactions
+        let downwash_strength = self.downwash_strength;
+        let trail_length = self.trail_length;
+        let airborne: Vec<[f32; 3]> = if downwash_strength.is_some() {
+            self.drones.iter().filter(|d| d.mode != DroneMode::Idle).map(|d| d.pos).collect()
+        } else {
+            Vec::new()
+        };
+        let collision_avoidance = self.collision_avoidance;
+        let (landing_avoidance, landing_avoidance_altitude) = self.landing_avoidance;
+        let goal_priority = self.goal_priority;
+        let avoidance_falloff = self.avoidance_falloff;
+        let adaptive_avoidance = self.adaptive_avoidance;
+        let flockmates: Vec<(usize, [f32; 3], [f32; 3])> = self.drones.iter()
+            .filter(|d| d.mode == DroneMode::Flock)
+            .map(|d| (d.id, d.pos, d.vel))
+            .collect();
+        let neighbors: Vec<AvoidanceNeighbor> = if collision_avoidance.is_some() {
+            self.drones.iter().filter(|d| d.mode != DroneMode::Idle).map(|d| d.avoidance_neighbor(goal_priority)).collect()
+        } else {
+            Vec::new()
+        };
+
+        let update = |drone: &mut Drone| {
+            if hold_formation {
+                drone.retarget_formation_slot();
+            }
+            drone.external_accel = match downwash_strength {
+                Some(strength) => [0.0, 0.0, -downwash_accel(drone.pos, &airborne, strength, config)],
+                None => [0.0, 0.0, 0.0],
+            };
+            drone.avoidance_vel = match collision_avoidance {
+                Some([radius, strength, lookahead]) if drone.mode != DroneMode::Idle => {
+                    let mut push = avoidance_velocity(&drone.avoidance_neighbor(goal_priority), &neighbors, radius, strength, lookahead, avoidance_falloff, adaptive_avoidance);
+                    let dodge = config.obstacles.avoidance_velocity(drone.pos, drone.vel, drone.radius, radius, strength, lookahead);
+                    for (p, d) in push.iter_mut().zip(dodge) {
+                        *p += d;
+                    }
+                    if drone.mode == DroneMode::Landing && drone.pos[2] < landing_avoidance_altitude {
+                        let factor = landing_avoidance.horizontal_factor();
+                        push[0] *= factor;
+                        push[1] *= factor;
+                    }
+                    push
+                }
+                _ => [0.0, 0.0, 0.0],
+            };
+            if drone.mode == DroneMode::Flock {
+                drone.target_vel = flock_velocity(drone.id, drone.pos, drone.vel, drone.target_pos, &flockmates, config.flock_params);
+            }
+            drone.step(dt * drone.time_scale, max_vel, monitor_center, monitor_orbit_speed, config);
+
+            if trail_length > 0 {
+                if drone.trail.len() == trail_length {
+                    drone.trail.pop_front();
+                }
+                drone.trail.push_back(drone.pos);
+            }
+        };
+
+        // Parallel update of all drones, or in id order for lockstep
+        if config.lockstep {
+            self.drones.iter_mut().filter(|drone| !drone.frozen && !drone.paused).for_each(update);
+        } else {
+            self.drones.par_iter_mut().filter(|drone| !drone.frozen && !drone.paused).for_each(update);
+        }
+
+        for objective in self.objectives.iter_mut().filter(|o| !o.complete) {
+            objective.complete = objective.kind.is_met(&self.drones);
+        }
+        self.rebuild_spatial_grid();
+
+        self.sim_time += dt;
+        self.step_count += 1;
+        self.detect_crashes();
+        self.stamp_mode_changes();
+        self.record_frame();
+        self.sim_time
+    }
+
+    /// Step physics multiple times (for speed multiplier)
+    pub fn step_multiple(&mut self, steps: u32) -> f32 {
+        for _ in 0..steps {
+            self.step();
+        }
+        self.sim_time
+    }
+
+    /// Get all drone states
+    pub fn get_states(&self) -> Vec<PyDroneState> {
+        self.drones.iter().map(|d| {
+            let (pos, vel) = self.sensed_pos_vel(d);
+            PyDroneState {
+                id: d.id,
+                pos: self.report_point(pos),
+                vel: self.report_point(vel),
+                yaw: d.yaw,
+                battery: self.discharge_curve.reported(d.battery),
+                healthy: d.healthy,
+                selected: d.selected,
+            }
+        }).collect()
+    }
+
+    /// Cumulative telemetry since spawn or reset: distance traveled, energy used (thrust
+    /// impulse, N*s) and flight time (s out of Idle)
+    pub fn get_telemetry(&self) -> Vec<PyDroneTelemetry> {
+        self.drones.iter().map(|d| PyDroneTelemetry {
+            id: d.id,
+            distance_traveled: self.report_len(d.distance_traveled),
+            energy_used: d.energy_used,
+            flight_time: d.flight_time,
+        }).collect()
+    }
+
+    /// All drone states as numpy arrays, without building a Python object per drone
+    ///
+    /// Dict of "pos" and "vel" (N, 3) float32, "yaw" and "battery" (N,) float32, and
+    /// "healthy" and "selected" (N,) bool, in id order with the same values as `get_states`.
+    pub fn get_states_array<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let n = self.drones.len();
+        let mut pos = Vec::with_capacity(n * 3);
+        let mut vel = Vec::with_capacity(n * 3);
+        for drone in &self.drones {
+            let (p, v) = self.sensed_pos_vel(drone);
+            pos.extend(self.report_point(p));
+            vel.extend(self.report_point(v));
+        }
+
+        let states = PyDict::new(py);
+        states.set_item("pos", PyArray1::from_vec(py, pos).reshape([n, 3])?)?;
+        states.set_item("vel", PyArray1::from_vec(py, vel).reshape([n, 3])?)?;
+        states.set_item("yaw", PyArray1::from_iter(py, self.drones.iter().map(|d| d.yaw)))?;
+        states.set_item("battery", PyArray1::from_iter(py, self.drones.iter().map(|d| self.discharge_curve.reported(d.battery))))?;
+        states.set_item("healthy", PyArray1::from_iter(py, self.drones.iter().map(|d| d.healthy)))?;
+        states.set_item("selected", PyArray1::from_iter(py, self.drones.iter().map(|d| d.selected)))?;
+        Ok(states)
+    }
+
+    /// Mark drones as selected (or deselected) for UI highlighting; no effect on physics
+    pub fn set_selected(&mut self, ids: Vec<usize>, selected: bool) {
+        for &id in &ids {
+            if let Some(drone) = self.drones.get_mut(id) {
+                drone.selected = selected;
+            }
+        }
+    }
+
+    /// Ids of the selected drones, ascending
+    pub fn selected_ids(&self) -> Vec<usize> {
+        self.drones.iter().filter(|d| d.selected).map(|d| d.id).collect()
+    }
+
+    /// Record every command from now on (or stop and discard the log)
+    ///
+    /// Convenience wrappers (`*_all`, `waypoint`) are logged as the commands they expand to.
+    pub fn set_command_logging(&mut self, enabled: bool) {
+        if !enabled {
+            self.command_log = None;
+        } else if self.command_log.is_none() {
+            self.command_log = Some(Vec::new());
+        }
+    }
+
+    /// Logged commands in order as (sim_time, command name, numeric args); drone ids
+    /// appear as numbers, string args are omitted
+    pub fn get_command_log(&self) -> Vec<CommandLogEntry> {
+        self.command_log.clone().unwrap_or_default()
+    }
+
+    /// Compact binary diff of `get_states` against the previous call, quantized (see
+    /// StateDeltaDecoder); the first call, and any after a respawn, is a full keyframe
+    pub fn get_state_delta(&mut self) -> Vec<u8> {
+        let current: Vec<QuantizedState> = self.get_states().iter().map(QuantizedState::from_state).collect();
+        let delta = encode_state_delta(self.last_sync.as_deref(), &current, self.sim_time);
+        self.last_sync = Some(current);
+        delta
+    }
+
+    /// Detect crashes each step: two drones closer than `crash_distance` (or touching, for
+    /// larger drones), or one hitting the ground faster than `impact_speed` (m/s)
+    ///
+    /// Crashed drones turn unhealthy, stop responding to commands and coast to rest until reset;
+    /// each crash is reported once by drain_events as "collision" or "ground_impact".
+    #[pyo3(signature = (enabled, crash_distance=0.15, impact_speed=2.0))]
+    pub fn set_crash_detection(&mut self, enabled: bool, crash_distance: f32, impact_speed: f32) {
+        self.config.crash_detection = enabled.then_some([
+            self.scale_len(crash_distance.max(0.0)),
+            self.scale_len(impact_speed.abs()),
+        ]);
+    }
+
+    /// Events since the last call as (sim_time, event type, ids), oldest first; clears them
+    ///
+    /// Under the "error" buffer policy, raises ValueError once if events were discarded since
+    /// the last drain; the buffered events are returned by the next call.
+    pub fn drain_events(&mut self) -> PyResult<Vec<SwarmEvent>> {
+        let dropped = std::mem::take(&mut self.dropped_events);
+        if dropped > 0 {
+            return Err(PyValueError::new_err(format!("event buffer full, {} events discarded", dropped)));
+        }
+        Ok(std::mem::take(&mut self.events).into())
+    }
+
+    /// Cap the event buffer at `capacity` events (0 removes the cap); once full, "drop_oldest"
+    /// makes room by discarding the oldest event, "drop_newest" discards the new one and "error"
+    /// discards it and raises on the next drain_events
+    pub fn set_event_buffer_policy(&mut self, capacity: usize, policy: &str) -> PyResult<()> {
+        let policy = match policy {
+            "drop_oldest" => EventBufferPolicy::DropOldest,
+            "drop_newest" => EventBufferPolicy::DropNewest,
+            "error" => EventBufferPolicy::Error,
+            _ => return Err(PyValueError::new_err(format!("unknown event buffer policy '{}'", policy))),
+        };
+        self.event_buffer = (capacity > 0).then_some((capacity, policy));
+        // Events already over the new capacity go as they would have on arrival
+        while self.events.len() > capacity && capacity > 0 {
+            match policy {
+                EventBufferPolicy::DropOldest => self.events.pop_front(),
+                EventBufferPolicy::DropNewest => self.events.pop_back(),
+                EventBufferPolicy::Error => {
+                    self.dropped_events += 1;
+                    self.events.pop_back()
+                }
+            };
+        }
+        Ok(())
+    }
+
+    /// Start recording drone states every `interval` steps, discarding any previous recording;
+    /// the current state is frame 0
+    #[pyo3(signature = (interval=1))]
+    pub fn start_recording(&mut self, interval: u64) {
+        self.recording = Some(Recording {
+            interval: interval.max(1),
+            active: true,
+            frames: vec![Frame::capture(&self.drones, self.sim_time)],
+        });
+    }
+
+    /// Stop adding frames; the recording stays available to seek_frame
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            recording.active = false;
+        }
+    }
+
+    /// Number of recorded frames
+    pub fn recording_length(&self) -> usize {
+        self.recording.as_ref().map_or(0, |r| r.frames.len())
+    }
+
+    /// Set the live swarm to recorded frame `index` (positions, velocities, yaw, battery,
+    /// health and mode, plus sim_time) and return its sim time
+    ///
+    /// Targets and controller state are not recorded, so stepping after a seek continues the
+    /// current commands from the recorded pose rather than replaying the run.
+    pub fn seek_frame(&mut self, index: usize) -> PyResult<f32> {
+        let frame = self.recording.as_ref()
+            .and_then(|r| r.frames.get(index))
+            .ok_or_else(|| PyValueError::new_err(format!("no recorded frame {}", index)))?;
+        if frame.drone_count() != self.drones.len() {
+            return Err(PyValueError::new_err(format!(
+                "frame {} has {} drones but the swarm has {}", index, frame.drone_count(), self.drones.len()
+            )));
+        }
+        frame.apply(&mut self.drones);
+        self.sim_time = frame.sim_time;
+        self.rebuild_spatial_grid();
+        Ok(self.sim_time)
+    }
+
+    /// Checkpoint the exact simulation state (every drone including controller internals,
+    /// sim_time and the monitor center) as versioned binary for `restore`
+    ///
+    /// Configuration and swarm-level commands (maneuvers, failsafe, objectives) are not included.
+    pub fn snapshot(&self) -> PyResult<Vec<u8>> {
+        let snapshot = SwarmSnapshot {
+            version: SNAPSHOT_VERSION,
+            sim_time: self.sim_time,
+            step_count: self.step_count,
+            monitor_center: self.monitor_center,
+            drones: self.drones.clone(),
+        };
+        bincode::serialize(&snapshot).map_err(|e| PyValueError::new_err(format!("snapshot failed: {}", e)))
+    }
+
+    /// Roll back to a `snapshot`; the swarm must have the same number of drones
+    pub fn restore(&mut self, data: Vec<u8>) -> PyResult<()> {
+        let version: u32 = bincode::deserialize(&data)
+            .map_err(|e| PyValueError::new_err(format!("invalid snapshot: {}", e)))?;
+        if version != SNAPSHOT_VERSION {
+            return Err(PyValueError::new_err(format!(
+                "snapshot version {} is not supported (expected {})", version, SNAPSHOT_VERSION
+            )));
+        }
+        let snapshot: SwarmSnapshot = bincode::deserialize(&data)
+            .map_err(|e| PyValueError::new_err(format!("invalid snapshot: {}", e)))?;
+        if snapshot.drones.len() != self.drones.len() {
+            return Err(PyValueError::new_err(format!(
+                "snapshot has {} drones but the swarm has {}", snapshot.drones.len(), self.drones.len()
+            )));
+        }
+
+        self.sim_time = snapshot.sim_time;
+        self.step_count = snapshot.step_count;
+        self.monitor_center = snapshot.monitor_center;
+        self.drones = snapshot.drones;
+        self.rebuild_spatial_grid();
+        Ok(())
+    }
+
+    /// Make the next `get_state_delta` a keyframe (e.g. when a new client joins)
+    pub fn request_keyframe(&mut self) {
+        self.last_sync = None;
+    }
+
+    /// Set swarm-wide Gaussian sensor noise on reported positions/velocities (deterministic per seed)
+    #[pyo3(signature = (pos_stddev, vel_stddev, seed=0))]
+    pub fn set_sensor_noise(&mut self, pos_stddev: f32, vel_stddev: f32, seed: u64) {
+        self.sensor_noise = [pos_stddev.max(0.0), vel_stddev.max(0.0)];
+        self.noise_seed = seed;
+    }
+
+    /// Set position PID gains and velocity dynamics (response rate and drag, 1/s) for the swarm
+    ///
+    /// Defaults: kp=2.0, ki=0.01, kd=0.5, response_rate=5.0, drag=0.1.
+    pub fn set_control_params(&mut self, kp: f32, ki: f32, kd: f32, response_rate: f32, drag: f32) {
+        self.config.control_gains = ControlGains { kp, ki, kd, response_rate: response_rate.max(0.0), drag: drag.max(0.0) };
+    }
+
+    /// Override control gains for one drone, e.g. a sluggish leader among snappy followers
+    pub fn set_drone_control_params(&mut self, id: usize, kp: f32, ki: f32, kd: f32, response_rate: f32, drag: f32) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.control_gains = Some(ControlGains { kp, ki, kd, response_rate: response_rate.max(0.0), drag: drag.max(0.0) });
+        }
+    }
+
+    /// Drop a drone's control gains override so it follows the swarm setting again
+    pub fn clear_drone_control_params(&mut self, id: usize) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.control_gains = None;
+        }
+    }
+
+    /// Override sensor noise for one drone (0.0 for a clean sensor)
+    pub fn set_drone_noise(&mut self, id: usize, pos_stddev: f32, vel_stddev: f32) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.sensor_noise = Some([pos_stddev.max(0.0), vel_stddev.max(0.0)]);
+        }
+    }
+
+    /// Drop a drone's sensor noise override so it follows the swarm setting again
+    pub fn clear_drone_noise(&mut self, id: usize) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.sensor_noise = None;
+        }
+    }
+
+    /// Seconds the drone has spent in its current mode (0 for an unknown id)
+    pub fn time_in_mode(&self, id: usize) -> f32 {
+        match self.drones.get(id) {
+            Some(drone) if !drone.mode_changed => self.sim_time - drone.mode_entered_time,
+            _ => 0.0,
+        }
+    }
+
+    /// Get a drone's control effort: the position controller's velocity correction magnitude
+    /// integrated over the last ~1s (0 while flying on velocity commands)
+    pub fn get_control_effort(&self, id: usize) -> f32 {
+        self.drones.get(id).map_or(0.0, |d| d.control_effort)
+    }
+
+    /// Estimate seconds of flight left at the drain rate of the drone's current mode
+    ///
+    /// 0 for an empty battery; f32::MAX for a drone that isn't draining (idle or charging).
+    pub fn get_flight_time_remaining(&self, id: usize) -> f32 {
+        let Some(drone) = self.drones.get(id) else {
+            return 0.0;
+        };
+        let drain_rate = self.drain_rate_of(drone);
+        if drone.battery <= 0.0 {
+            0.0
+        } else if drain_rate <= 0.0 {
+            f32::MAX
+        } else {
+            drone.battery / drain_rate * 60.0
+        }
+    }
+
+    /// Drones more than `min_error` from their target that have barely moved for `min_duration` s
+    pub fn get_stuck_drones(&self, min_error: f32, min_duration: f32) -> Vec<usize> {
+        self.drones.iter()
+            .filter(|d| d.stuck_time >= min_duration && d.target_error() > self.scale_len(min_error))
+            .map(|d| d.id)
+            .collect()
+    }
+
+    /// Whether every position-controlled drone (takeoff, goto, hover) is within `tolerance` of its
+    /// target and nearly stationary; drones in other modes are ignored
+    ///
+    /// A filtered goto counts its commanded target, not the one still easing toward it.
+    pub fn formation_complete(&self, tolerance: f32) -> bool {
+        let tolerance = self.scale_len(tolerance);
+        self.drones.iter()
+            .filter(|d| matches!(d.mode, DroneMode::Takeoff | DroneMode::Goto | DroneMode::Hover))
+            .all(|d| {
+                let target = d.commanded_target.unwrap_or(d.target_pos);
+                let speed = (d.vel[0].powi(2) + d.vel[1].powi(2) + d.vel[2].powi(2)).sqrt();
+                distance(d.pos, target) <= tolerance && speed < STUCK_SPEED
+            })
+    }
+
+    /// Number of drones in each mode, keyed by mode name; modes nobody is in are left out
+    pub fn mode_histogram(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for drone in &self.drones {
+            *counts.entry(drone.mode.name().to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Describe the static world (bounds, geofence, charge zones, obstacles) as JSON for
+    /// renderers; the geofence is null when unset
+    pub fn get_scene_description(&self) -> String {
+        let charge_zones: Vec<String> = self.charge_zones.iter().map(|z| {
+            format!(
+                "{{\"center\":{},\"radius\":{},\"charge_rate\":{}}}",
+                json_array(&z.center.map(|v| self.report_len(v))), json_number(self.report_len(z.radius)), json_number(z.charge_rate)
+            )
+        }).collect();
+        let obstacles: Vec<String> = self.config.obstacles.iter().map(|o| {
+            format!(
+                "{{\"center\":{},\"velocity\":{},\"radius\":{},\"height\":{}}}",
+                json_array(&o.center.map(|v| self.report_len(v))), json_array(&o.velocity.map(|v| self.report_len(v))),
+                json_number(self.report_len(o.radius)), json_number(self.report_len(o.height))
+            )
+        }).collect();
+        let geofence = self.config.geofence.as_ref().map_or_else(|| "null".to_string(), |fence| {
+            let vertices: Vec<String> = fence.vertices.iter().map(|v| json_array(&v.map(|c| self.report_len(c)))).collect();
+            format!(
+                "{{\"vertices\":[{}],\"min_z\":{},\"max_z\":{}}}",
+                vertices.join(","), json_number(self.report_len(fence.min_z)), json_number(self.report_len(fence.max_z))
+            )
+        });
+
+        format!(
+            "{{\"bounds\":{{\"min\":{},\"max\":{}}},\"geofence\":{},\"charge_zones\":[{}],\"obstacles\":[{}]}}",
+            json_array(&self.report_point(self.config.world_min)),
+            json_array(&self.report_point(self.config.world_max)),
+            geofence,
+            charge_zones.join(","),
+            obstacles.join(",")
+        )
+    }
+
+    /// Total swarm energy as (kinetic, potential) in joules, from per-drone mass and gravity
+    pub fn get_total_energy(&self) -> (f32, f32) {
+        let gravity = self.config.gravity;
+        self.drones.par_iter()
+            .map(|d| {
+                let speed_sq = d.vel[0] * d.vel[0] + d.vel[1] * d.vel[1] + d.vel[2] * d.vel[2];
+                (0.5 * d.mass * speed_sq, d.mass * gravity * d.pos[2])
+            })
+            .reduce(|| (0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1))
+    }
+
+    /// Set how many recent positions each drone keeps for motion trails (0 disables)
+    pub fn set_trail_length(&mut self, n: usize) {
+        self.trail_length = n;
+        for drone in &mut self.drones {
+            while drone.trail.len() > n {
+                drone.trail.pop_front();
+            }
+        }
+    }
+
+    /// Get a drone's recent positions, oldest to newest
+    pub fn get_trail(&self, id: usize) -> Vec<[f32; 3]> {
+        self.drones.get(id).map_or_else(Vec::new, |d| d.trail.iter().map(|&p| self.report_point(p)).collect())
+    }
+
+    /// Distances between the given drones, upper triangle in row-major order:
+    /// (0,1), (0,2), ..., (0,n-1), (1,2), ... indexing the valid ids in the order given
+    pub fn pairwise_distances(&self, ids: Vec<usize>) -> Vec<f32> {
+        let positions: Vec<[f32; 3]> = ids.iter().filter_map(|&id| self.drones.get(id).map(|d| d.pos)).collect();
+        positions.iter().enumerate()
+            .flat_map(|(i, a)| positions[i + 1..].iter().map(move |b| self.report_len(distance(*a, *b))))
+            .collect()
+    }
+
+    /// Closest pair among the given drones as (id_a, id_b, distance); None for fewer than two
+    ///
+    /// Repeated ids count once. From SPATIAL_QUERY_MIN_DRONES drones on, each drone's nearest
+    /// neighbor comes from a spatial hash over the group instead of a scan of every pair.
+    pub fn min_pairwise_distance(&self, ids: Vec<usize>) -> Option<(usize, usize, f32)> {
+        let mut seen = HashSet::new();
+        let drones: Vec<&Drone> = ids.iter().filter(|&&id| seen.insert(id)).filter_map(|&id| self.drones.get(id)).collect();
+        let (a, b, dist) = if drones.len() < SPATIAL_QUERY_MIN_DRONES {
+            drones.iter().enumerate()
+                .flat_map(|(i, a)| drones[i + 1..].iter().map(move |b| (a.id, b.id, distance(a.pos, b.pos))))
+                .min_by(|x, y| x.2.total_cmp(&y.2))?
+        } else {
+            let mut grid = SpatialGrid::new(self.spatial_grid.cell_size(), self.config.world_min, self.config.world_max);
+            grid.rebuild(drones.iter().map(|d| (d.id, d.pos)));
+            drones.par_iter()
+                .filter_map(|a| {
+                    let b = *grid.k_nearest(a.pos, 1, Some(a.id)).first()?;
+                    Some((a.id.min(b), a.id.max(b), distance(a.pos, self.drones[b].pos)))
+                })
+                .min_by(|x, y| x.2.total_cmp(&y.2).then((x.0, x.1).cmp(&(y.0, y.1))))?
+        };
+        Some((a, b, self.report_len(dist)))
+    }
+
+    /// Dispersion of the swarm: RMS distance of drones from their centroid (0 when empty)
+    pub fn get_spread(&self) -> f32 {
+        let n = self.drones.len();
+        if n == 0 {
+            return 0.0;
+        }
+        let sum = self.drones.par_iter()
+            .map(|d| d.pos)
+            .reduce(|| [0.0; 3], |a, b| [a[0] + b[0], a[1] + b[1], a[2] + b[2]]);
+        let centroid = sum.map(|v| v / n as f32);
+        let squares: f32 = self.drones.par_iter().map(|d| distance(d.pos, centroid).powi(2)).sum();
+        self.report_len((squares / n as f32).sqrt())
+    }
+
+    /// 2D convex hull of drone positions (x, y), counterclockwise; collinear drones give the
+    /// two extremes, a single drone one point
+    pub fn get_convex_hull(&self) -> Vec<[f32; 2]> {
+        let points = self.drones.iter().map(|d| [self.report_len(d.pos[0]), self.report_len(d.pos[1])]).collect();
+        convex_hull(points)
+    }
+
+    /// Pairs (id_a < id_b) currently in contact: closer than the sum of their collision radii
+    pub fn get_collisions(&self) -> Vec<(usize, usize)> {
+        self.drones.iter().enumerate()
+            .flat_map(|(i, a)| self.drones[i + 1..].iter().map(move |b| (a, b)))
+            .filter(|(a, b)| distance(a.pos, b.pos) < a.radius + b.radius)
+            .map(|(a, b)| (a.id, b.id))
+            .collect()
+    }
+
+    /// Converging pairs (id_a < id_b, seconds to contact) that will come closer than the sum of
+    /// their collision radii within `horizon` seconds at their current velocities, soonest first
+    ///
+    /// Pairs already in contact and still closing report 0; avoidance plays no part in the
+    /// prediction.
+    pub fn get_collision_warnings(&self, horizon: f32) -> Vec<(usize, usize, f32)> {
+        let mut warnings: Vec<(usize, usize, f32)> = self.drones.iter().enumerate()
+            .flat_map(|(i, a)| self.drones[i + 1..].iter().map(move |b| (a, b)))
+            .filter_map(|(a, b)| {
+                let rel_pos = [0, 1, 2].map(|i| b.pos[i] - a.pos[i]);
+                let rel_vel = [0, 1, 2].map(|i| b.vel[i] - a.vel[i]);
+                let speed_sq: f32 = rel_vel.iter().map(|v| v * v).sum();
+                let closing: f32 = rel_pos.iter().zip(&rel_vel).map(|(p, v)| p * v).sum();
+                if speed_sq < 1e-9 || closing >= 0.0 || -closing / speed_sq > horizon {
+                    return None;
+                }
+                // Contact when |rel_pos + rel_vel t| reaches the combined radii
+                let contact = a.radius + b.radius;
+                let dist_sq: f32 = rel_pos.iter().map(|p| p * p).sum();
+                let discriminant = closing * closing - speed_sq * (dist_sq - contact * contact);
+                if discriminant <= 0.0 {
+                    return None;
+                }
+                let time = ((-closing - discriminant.sqrt()) / speed_sq).max(0.0);
+                Some((a.id, b.id, time))
+            })
+            .collect();
+        warnings.sort_by(|a, b| a.2.total_cmp(&b.2));
+        warnings
+    }
+
+    /// Set the spatial hash cell size (default 1m); the grid is rebuilt every step
+    pub fn set_spatial_cell_size(&mut self, cell_size: f32) {
+        self.spatial_grid = SpatialGrid::new(self.scale_len(cell_size), self.config.world_min, self.config.world_max);
+        self.rebuild_spatial_grid();
+    }
+
+    /// Ids of drones within `radius` of (x, y, z), ascending (spatial hash lookup)
+    pub fn query_radius(&self, x: f32, y: f32, z: f32, radius: f32) -> Vec<usize> {
+        self.spatial_grid.query_radius(self.scale_point([x, y, z]), self.scale_len(radius))
+    }
+
+    /// The `k` drones nearest to drone `id`, closest first (spatial hash lookup)
+    pub fn k_nearest(&self, id: usize, k: usize) -> Vec<usize> {
+        self.drones.get(id).map_or_else(Vec::new, |d| self.spatial_grid.k_nearest(d.pos, k, Some(id)))
+    }
+
+    /// Closest drone to `point` as (id, distance); unhealthy drones are skipped unless
+    /// `include_failed`. None when no drone qualifies
+    ///
+    /// From SPATIAL_QUERY_MIN_DRONES drones on, the nearest few come from the spatial hash and
+    /// every drone is scanned only if none of them qualifies.
+    #[pyo3(signature = (point, include_failed=false))]
+    pub fn nearest_drone(&self, point: [f32; 3], include_failed: bool) -> Option<(usize, f32)> {
+        let point = self.scale_point(point);
+        let qualifies = |d: &&Drone| include_failed || d.healthy;
+        let hashed = (self.drones.len() >= SPATIAL_QUERY_MIN_DRONES)
+            .then(|| self.spatial_grid.k_nearest(point, NEAREST_DRONE_CANDIDATES, None))
+            .and_then(|candidates| candidates.into_iter().map(|id| &self.drones[id]).find(qualifies));
+        let nearest = hashed.or_else(|| {
+            self.drones.iter()
+                .filter(qualifies)
+                .min_by(|a, b| distance(a.pos, point).total_cmp(&distance(b.pos, point)))
+        })?;
+        Some((nearest.id, self.report_len(distance(nearest.pos, point))))
+    }
+
+    /// How well the swarm covers a target shape: mean distance from each target point to its
+    /// nearest drone (0 for no points, infinite for an empty swarm)
+    pub fn shape_match_error(&self, target_points: Vec<[f32; 3]>) -> f32 {
+        if target_points.is_empty() {
+            return 0.0;
+        }
+        let total: f32 = target_points.iter().map(|&p| {
+            let p = self.scale_point(p);
+            self.drones.iter().map(|d| distance(d.pos, p)).fold(f32::INFINITY, f32::min)
+        }).sum();
+        self.report_len(total / target_points.len() as f32)
+    }
+
+    /// Principal axis of the swarm footprint (2D PCA): (axis yaw in [-PI/2, PI/2], major extent, minor extent)
+    pub fn get_formation_orientation(&self) -> (f32, f32, f32) {
+        let n = self.drones.len();
+        if n == 0 {
+            return (0.0, 0.0, 0.0);
+        }
+
+        let mean_x = self.drones.iter().map(|d| d.pos[0]).sum::<f32>() / n as f32;
+        let mean_y = self.drones.iter().map(|d| d.pos[1]).sum::<f32>() / n as f32;
+
+        let (mut sxx, mut syy, mut sxy) = (0.0f32, 0.0f32, 0.0f32);
+        for d in &self.drones {
+            let dx = d.pos[0] - mean_x;
+            let dy = d.pos[1] - mean_y;
+            sxx += dx * dx;
+            syy += dy * dy;
+            sxy += dx * dy;
+        }
+
+        // Eigenvector of the 2x2 covariance with the largest eigenvalue
+        let yaw = 0.5 * (2.0 * sxy).atan2(sxx - syy);
+        let (major, minor) = ([yaw.cos(), yaw.sin()], [-yaw.sin(), yaw.cos()]);
+
+        let extent = |axis: [f32; 2]| {
+            let (lo, hi) = self.drones.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), d| {
+                let p = (d.pos[0] - mean_x) * axis[0] + (d.pos[1] - mean_y) * axis[1];
+                (lo.min(p), hi.max(p))
+            });
+            hi - lo
+        };
+
+        (yaw, self.report_len(extent(major)), self.report_len(extent(minor)))
+    }
+
+    /// Get simulation time
+    pub fn get_time(&self) -> f32 {
+        self.sim_time
+    }
+
+    /// Get number of drones
+    pub fn num_drones(&self) -> usize {
+        self.drones.len()
+    }
+
+    /// Set speed multiplier
+    pub fn set_speed(&mut self, multiplier: f32) {
+        self.log_command("set_speed", &[multiplier]);
+        self.speed_multiplier = multiplier;
+        self.max_velocity = 2.0 * multiplier;
+        self.config.velocity_scale = self.speed_multiplier;
+    }
+
+    /// Set one drone's speed multiplier, applied on top of the swarm-wide speed
+    pub fn set_drone_speed(&mut self, id: usize, multiplier: f32) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.speed_multiplier = multiplier.max(0.0);
+        }
+    }
+
+    /// Set the speed multiplier for a group of drones
+    pub fn set_group_speed(&mut self, ids: Vec<usize>, multiplier: f32) {
+        for id in ids {
+            self.set_drone_speed(id, multiplier);
+        }
+    }
+
+    /// Set a velocity limit (m/s) for one mode, e.g. slow "landing" or fast "goto"
+    pub fn set_mode_velocity_limit(&mut self, mode: &str, max_vel: f32) -> PyResult<()> {
+        let mode = DroneMode::from_name(mode)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown mode '{}'", mode)))?;
+        self.config.mode_velocity_limits.insert(mode, self.scale_len(max_vel.max(0.0)));
+        Ok(())
+    }
+
+    /// Remove all per-mode velocity limits so every mode uses the global max
+    pub fn clear_mode_velocity_limits(&mut self) {
+        self.config.mode_velocity_limits.clear();
+    }
+
+    /// Set a battery drain rate (%/min) for one mode, e.g. a hungrier "goto" than "hover"
+    pub fn set_mode_drain_rate(&mut self, mode: &str, drain_rate: f32) -> PyResult<()> {
+        let mode = DroneMode::from_name(mode)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown mode '{}'", mode)))?;
+        self.mode_drain_rates.insert(mode, drain_rate.max(0.0));
+        Ok(())
+    }
+
+    /// Remove all per-mode drain rates so every flying drone drains at the flat rate
+    pub fn clear_mode_drain_rates(&mut self) {
+        self.mode_drain_rates.clear();
+    }
+
+    /// Keep formations rigid: Goto/Hover drones continuously re-target their last formation slot
+    ///
+    /// Slots are fixed relative to the anchor recorded when the formation was commanded (the
+    /// centroid of its slots), which only cruise_formation moves. The anchor does not follow the
+    /// group's current centroid, so a formation pushed off as a whole flies back to where it was
+    /// commanded instead of re-forming where it ended up.
+    pub fn hold_formation(&mut self, enabled: bool) {
+        self.hold_formation = enabled;
+    }
+
+    /// Set the maximum tilt angle (radians), limiting horizontal acceleration
+    pub fn set_max_tilt(&mut self, radians: f32) {
+        self.config.max_tilt = radians.clamp(0.0, PI / 2.0);
+    }
+
+    /// Set yaw controller gains (defaults: kp=2.0, ki=0.0, kd=0.0)
+    pub fn set_yaw_pid(&mut self, kp: f32, ki: f32, kd: f32) {
+        self.config.yaw_kp = kp;
+        self.config.yaw_ki = ki;
+        self.config.yaw_kd = kd;
+    }
+
+    /// Clear PID state on every mode transition, not just on commands that restart control
+    pub fn set_reset_pid_on_mode_change(&mut self, enabled: bool) {
+        self.config.reset_pid_on_mode_change = enabled;
+    }
+
+    /// Set the length unit used at the API boundary: "m", "cm", "ft" or "in"
+    ///
+    /// Command positions, lengths and velocities are read in this unit and reported
+    /// positions, velocities and distances are returned in it; physics stays in meters.
+    pub fn set_units(&mut self, length_unit: &str) -> PyResult<()> {
+        self.length_unit = match length_unit {
+            "m" => 1.0,
+            "cm" => 0.01,
+            "ft" => 0.3048,
+            "in" => 0.0254,
+            _ => return Err(PyValueError::new_err(format!("unknown length unit '{}'", length_unit))),
+        };
+        Ok(())
+    }
+
+    /// Scale the scene: command positions, spacings, speeds and world bounds are multiplied by `scale`
+    pub fn set_scene_scale(&mut self, scale: f32) {
+        let scale = scale.max(1e-3);
+        self.scene_scale = scale;
+        self.apply_world_bounds();
+    }
+
+    /// Set the box drones and targets are clamped to (default x, y in [-10, 10], z in [0, 5]);
+    /// the default health envelope follows it
+    pub fn set_world_bounds(&mut self, min: [f32; 3], max: [f32; 3]) -> PyResult<()> {
+        if (0..3).any(|i| min[i] >= max[i]) {
+            return Err(PyValueError::new_err("world bounds min must be below max on every axis"));
+        }
+        self.world_bounds = [min, max].map(|p| p.map(|v| v * self.length_unit));
+        self.apply_world_bounds();
+        Ok(())
+    }
+
+    /// Confine drones to a horizontal polygon between `min_z` and `max_z` (inside the world bounds)
+    ///
+    /// Targets outside it are handled by the out-of-bounds policy (clamped to the nearest edge,
+    /// rejected or raised as errors) and drones pushed outside are projected back onto the nearest edge.
+    pub fn set_geofence_polygon(&mut self, vertices: Vec<[f32; 2]>, min_z: f32, max_z: f32) -> PyResult<()> {
+        if vertices.len() < 3 {
+            return Err(PyValueError::new_err("geofence polygon needs at least 3 vertices"));
+        }
+        if max_z < min_z {
+            return Err(PyValueError::new_err("geofence max_z must be >= min_z"));
+        }
+        let scale = self.command_scale();
+        self.config.geofence = Some(Geofence {
+            vertices: vertices.into_iter().map(|v| v.map(|c| c * scale)).collect(),
+            min_z: min_z * scale,
+            max_z: max_z * scale,
+        });
+        Ok(())
+    }
+
+    /// Confine drones to an ellipse (approximated by a `segments`-gon) between `min_z` and `max_z`
+    #[pyo3(signature = (center, radii, min_z, max_z, segments=64))]
+    pub fn set_geofence_ellipse(&mut self, center: [f32; 2], radii: [f32; 2], min_z: f32, max_z: f32, segments: usize) -> PyResult<()> {
+        let n = segments.max(3);
+        let vertices = (0..n)
+            .map(|k| {
+                let angle = 2.0 * PI * k as f32 / n as f32;
+                [center[0] + radii[0] * angle.cos(), center[1] + radii[1] * angle.sin()]
+            })
+            .collect();
+        self.set_geofence_polygon(vertices, min_z, max_z)
+    }
+
+    /// Remove the geofence, leaving only the world bounds
+    pub fn clear_geofence(&mut self) {
+        self.config.geofence = None;
+    }
+
+    /// Enable rotor downwash: drones below another within its cone are pushed down by `strength / dz`
+    #[pyo3(signature = (enabled, strength=1.0))]
+    pub fn set_downwash(&mut self, enabled: bool, strength: f32) {
+        self.downwash_strength = enabled.then_some(strength);
+    }
+
+    /// Enable inter-drone separation: airborne drones within `radius` (m) of each other are
+    /// pushed apart at up to `strength` (m/s), fading to zero at the radius
+    ///
+    /// Drones and obstacles are judged where they will be over the next `lookahead` seconds at
+    /// current velocities, so converging drones and moving obstacles are cleared early; 0 reacts
+    /// only to current positions.
+    #[pyo3(signature = (enabled, radius=0.4, strength=1.0, lookahead=0.0))]
+    pub fn set_collision_avoidance(&mut self, enabled: bool, radius: f32, strength: f32, lookahead: f32) {
+        self.collision_avoidance = enabled.then(|| [
+            self.scale_len(radius.max(1e-3)),
+            self.scale_len(strength.max(0.0)),
+            lookahead.max(0.0),
+        ]);
+    }
+
+    /// How collision avoidance treats landing drones below `altitude` (m): "full", "reduced"
+    /// (weaker horizontal push) or "vertical_only" (no horizontal push), so neighbors can touch down
+    #[pyo3(signature = (mode, altitude=1.0))]
+    pub fn set_landing_avoidance(&mut self, mode: &str, altitude: f32) -> PyResult<()> {
+        let mode = match mode {
+            "full" => LandingAvoidance::Full,
+            "reduced" => LandingAvoidance::Reduced,
+            "vertical_only" => LandingAvoidance::VerticalOnly,
+            _ => return Err(PyValueError::new_err(format!("unknown landing avoidance mode '{}'", mode))),
+        };
+        self.landing_avoidance = (mode, self.scale_len(altitude));
+        Ok(())
+    }
+
+    /// Give drones hovering or in goto within `tolerance` (m) of their target priority in
+    /// collision avoidance: they barely yield to a passing drone, which steers around them
+    #[pyo3(signature = (enabled, tolerance=0.1))]
+    pub fn set_goal_priority(&mut self, enabled: bool, tolerance: f32) {
+        self.goal_priority = enabled.then(|| self.scale_len(tolerance.max(0.0)));
+    }
+
+    /// How drone-to-drone avoidance weakens toward the radius: "linear" (default), "inverse",
+    /// "inverse_square" or "exponential"; the inverse curves push much harder at close range
+    pub fn set_avoidance_falloff(&mut self, curve: &str) -> PyResult<()> {
+        self.avoidance_falloff = match curve {
+            "linear" => AvoidanceFalloff::Linear,
+            "inverse" => AvoidanceFalloff::Inverse,
+            "inverse_square" => AvoidanceFalloff::InverseSquare,
+            "exponential" => AvoidanceFalloff::Exponential,
+            _ => return Err(PyValueError::new_err(format!("unknown avoidance falloff '{}'", curve))),
+        };
+        Ok(())
+    }
+
+    /// Scale each drone's avoidance strength by how many neighbors are inside the avoidance
+    /// radius (up to 4x), so dense clusters separate harder without over-driving sparse ones
+    pub fn set_adaptive_avoidance(&mut self, enabled: bool) {
+        self.adaptive_avoidance = enabled;
+    }
+
+    /// Put drone `id` in avoidance `group`: collision avoidance ignores other members, so a
+    /// tight formation isn't pushed off its slots, but still acts against other groups,
+    /// ungrouped drones and obstacles
+    pub fn set_avoidance_group(&mut self, id: usize, group: u32) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.avoidance_group = Some(group);
+        }
+    }
+
+    /// Take drone `id` out of its avoidance group, so it avoids every drone again
+    pub fn clear_avoidance_group(&mut self, id: usize) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.avoidance_group = None;
+        }
+    }
+
+    /// Set a drone's mass (kg) and collision radius (m)
+    pub fn set_physical_properties(&mut self, id: usize, mass: f32, radius: f32) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.mass = mass.max(1e-6);
+            drone.radius = radius.max(0.0);
+        }
+    }
+
+    /// Soften touchdowns: below `flare_altitude` (m) landing descent is capped at
+    /// `flare_descent_rate` (m/s); a non-positive altitude turns the flare off
+    pub fn set_landing_flare(&mut self, flare_altitude: f32, flare_descent_rate: f32) {
+        self.config.landing_flare = (flare_altitude > 0.0).then(|| [
+            self.scale_len(flare_altitude),
+            self.scale_len(flare_descent_rate.abs()),
+        ]);
+    }
+
+    /// Give yaw rotational inertia: the yaw controller commands angular acceleration
+    /// (output - damping * yaw_rate) / inertia, so heavy drones turn with momentum; a
+    /// non-positive inertia restores the instant rate response
+    pub fn set_yaw_dynamics(&mut self, inertia: f32, damping: f32) {
+        self.config.yaw_dynamics = (inertia > 0.0).then(|| [inertia.max(1e-3), damping.max(0.0)]);
+    }
+
+    /// Set an altitude-dependent wind profile: `winds[k]` (m/s) blows at `altitudes[k]` (m),
+    /// interpolated in between; empty lists clear the wind
+    pub fn set_wind_profile(&mut self, altitudes: Vec<f32>, winds: Vec<[f32; 3]>) -> PyResult<()> {
+        if altitudes.len() != winds.len() {
+            return Err(PyValueError::new_err("altitudes and winds must have the same length"));
+        }
+        let scale = self.command_scale();
+        let mut profile: Vec<(f32, [f32; 3])> = altitudes.into_iter()
+            .zip(winds)
+            .map(|(z, w)| (z * scale, w.map(|v| v * scale)))
+            .collect();
+        profile.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.config.wind_profile = profile;
+        Ok(())
+    }
+
+    /// Set a uniform wind (m/s) at every altitude, replacing any wind profile; zero clears it
+    pub fn set_wind(&mut self, vx: f32, vy: f32, vz: f32) {
+        let wind = self.scale_point([vx, vy, vz]);
+        self.config.wind_profile = if wind == [0.0, 0.0, 0.0] { Vec::new() } else { vec![(0.0, wind)] };
+    }
+
+    /// Add gusts of `amplitude` (m/s) along the wind direction, varying sinusoidally with
+    /// sim_time at `frequency` (Hz); a zero amplitude disables them
+    pub fn set_wind_gust(&mut self, amplitude: f32, frequency: f32) {
+        self.wind_gust = (amplitude != 0.0).then_some([self.scale_len(amplitude), frequency]);
+        self.config.wind_gust = 0.0;
+    }
+
+    /// Vary each drone's response rate, drag and yaw speed by up to +/-`magnitude` (fraction)
+    /// around the defaults, deterministically per seed; disabling restores uniform dynamics
+    #[pyo3(signature = (enabled, magnitude=0.1, seed=0))]
+    pub fn set_personality_variation(&mut self, enabled: bool, magnitude: f32, seed: u64) {
+        let magnitude = magnitude.clamp(0.0, 0.9);
+        for drone in &mut self.drones {
+            drone.personality = [0u64, 1, 2].map(|k| {
+                if !enabled {
+                    return 1.0;
+                }
+                let u = hash_unit(splitmix64(seed ^ splitmix64(drone.id as u64 * 3 + k)));
+                1.0 + magnitude * (2.0 * u - 1.0)
+            });
+        }
+    }
+
+    /// Land drones that fault (empty battery or GPS dropout), optionally climbing to `safe_altitude` first
+    ///
+    /// The climb completes before the descent starts. A new command during the climb hands the
+    /// drone back to the operator.
+    #[pyo3(signature = (climb_first, safe_altitude=3.0))]
+    pub fn set_fault_response(&mut self, climb_first: bool, safe_altitude: f32) {
+        self.fault_response = Some(FaultResponse {
+            climb_first,
+            safe_altitude: self.scale_len(safe_altitude),
+        });
+    }
+
+    /// Low-battery failsafe: below `return_home_pct` (reported %) a drone flies back over its
+    /// spawn point at its current altitude and lands there; below `land_pct` it lands in place
+    ///
+    /// Once triggered the failsafe has precedence over manual commands (and over
+    /// `set_fault_response`) for that drone until it recharges above `return_home_pct` or the
+    /// swarm is reset; commands to it are overridden on the next step.
+    pub fn set_failsafe(&mut self, return_home_pct: f32, land_pct: f32) {
+        self.failsafe = Some([return_home_pct, land_pct.min(return_home_pct)]);
+    }
+
+    /// Turn the low-battery failsafe off and release any drones it holds
+    pub fn clear_failsafe(&mut self) {
+        self.failsafe = None;
+        for drone in &mut self.drones {
+            drone.failsafe_stage = None;
+        }
+    }
+
+    /// Lockstep mode for networked simulation: drones update in id order and the step math uses
+    /// only IEEE basic operations, so identical command sequences give bit-identical states on
+    /// every platform. Slower than the default; compare runs with `get_state_hash`.
+    pub fn set_lockstep(&mut self, enabled: bool) {
+        self.config.lockstep = enabled;
+    }
+
+    /// Hash of every drone's exact pos, vel, yaw, battery and mode plus the sim time
+    pub fn get_state_hash(&self) -> u64 {
+        let mut hash = splitmix64(self.sim_time.to_bits() as u64);
+        for d in &self.drones {
+            let bits = d.pos.iter().chain(&d.vel).chain([&d.yaw, &d.battery]).map(|v| v.to_bits() as u64);
+            for b in bits.chain([d.mode as u64]) {
+                hash = splitmix64(hash ^ b);
+            }
+        }
+        hash
+    }
+
+    /// Make Goto paths arc no tighter than `radius` instead of reversing in place (0 disables)
+    pub fn set_min_turn_radius(&mut self, radius: f32) {
+        self.config.min_turn_radius = self.scale_len(radius.max(0.0));
+    }
+
+    /// Limit how fast velocity (`max_accel`, m/s^2) and acceleration (`max_jerk`, m/s^3) can
+    /// change; infinite values (the default) disable a limit
+    pub fn set_dynamics_limits(&mut self, max_accel: f32, max_jerk: f32) {
+        self.config.max_accel = self.scale_len(max_accel.abs());
+        self.config.max_jerk = self.scale_len(max_jerk.abs());
+    }
+
+    /// Cap speed, acceleration, tilt and yaw rate to gentle presets and keep position targets in
+    /// a small indoor volume about the bounds' center; disabling restores the previous limits
+    ///
+    /// Limits already tighter than the presets are kept. Drones are never moved: those outside
+    /// the volume fly back in under the new caps, while velocity-commanded drones are only
+    /// slowed. Limits changed while safe mode is on are overwritten when it is turned off.
+    pub fn enable_safe_mode(&mut self, enabled: bool) {
+        if enabled == self.safe_mode.is_some() {
+            return;
+        }
+        if let Some(saved) = self.safe_mode.take() {
+            self.config.max_speed = saved.max_speed;
+            self.config.max_accel = saved.max_accel;
+            self.config.max_tilt = saved.max_tilt;
+            self.config.max_yaw_rate = saved.max_yaw_rate;
+            self.config.safe_volume = None;
+            return;
+        }
+
+        self.safe_mode = Some(SafeModeRestore {
+            max_speed: self.config.max_speed,
+            max_accel: self.config.max_accel,
+            max_tilt: self.config.max_tilt,
+            max_yaw_rate: self.config.max_yaw_rate,
+        });
+        let [speed, accel, tilt, yaw_rate] = SAFE_MODE_LIMITS;
+        self.config.max_speed = self.config.max_speed.min(speed * self.scene_scale);
+        self.config.max_accel = self.config.max_accel.min(accel * self.scene_scale);
+        self.config.max_tilt = self.config.max_tilt.min(tilt);
+        self.config.max_yaw_rate = self.config.max_yaw_rate.min(yaw_rate);
+
+        let [half_width, height] = SAFE_MODE_EXTENT.map(|v| v * self.scene_scale);
+        let (mut min, mut max) = (self.config.world_min, self.config.world_max);
+        for i in 0..2 {
+            let center = (min[i] + max[i]) / 2.0;
+            min[i] = min[i].max(center - half_width);
+            max[i] = max[i].min(center + half_width);
+        }
+        max[2] = max[2].min(min[2] + height);
+        self.config.safe_volume = Some([min, max]);
+    }
+
+    /// Set gravitational acceleration (m/s^2)
+    pub fn set_gravity(&mut self, gravity: f32) {
+        self.config.gravity = gravity;
+    }
+
+    /// Set the envelope a drone must stay inside to count as healthy: |x|, |y| below
+    /// `horizontal` and altitude within [vertical_min, vertical_max]
+    pub fn set_health_margins(&mut self, horizontal: f32, vertical_min: f32, vertical_max: f32) {
+        self.config.health_margins = Some([horizontal, vertical_min, vertical_max].map(|v| self.scale_len(v)));
+    }
+
+    /// Change the physics rate mid-run
+    ///
+    /// Controller state is stored as errors and accumulated integrals (not per-step rates)
+    /// and maneuvers run on sim_time, so the next step simply continues with the new dt.
+    pub fn set_physics_hz(&mut self, physics_hz: u32) -> PyResult<()> {
+        if physics_hz == 0 {
+            return Err(PyValueError::new_err("physics_hz must be greater than 0"));
+        }
+        self.physics_dt = 1.0 / physics_hz as f32;
+        Ok(())
+    }
+
+    /// Set how remaining energy maps to the reported battery percentage: "linear" or "lipo"
+    ///
+    /// Drain and charging stay linear in energy; reported states and the monitor rotation
+    /// thresholds see the curve.
+    pub fn set_discharge_curve(&mut self, curve: &str) -> PyResult<()> {
+        self.discharge_curve = match curve {
+            "linear" => DischargeCurve::Linear,
+            "lipo" => DischargeCurve::Lipo,
+            _ => return Err(PyValueError::new_err(format!("unknown discharge curve '{}'", curve))),
+        };
+        Ok(())
+    }
+
+    /// Set how out-of-bounds goto/formation targets are handled: "clamp", "reject" or "error"
+    pub fn set_out_of_bounds_policy(&mut self, policy: &str) -> PyResult<()> {
+        self.out_of_bounds_policy = match policy {
+            "clamp" => OutOfBoundsPolicy::Clamp,
+            "reject" => OutOfBoundsPolicy::Reject,
+            "error" => OutOfBoundsPolicy::Error,
+            _ => return Err(PyValueError::new_err(format!("unknown out-of-bounds policy '{}'", policy))),
+        };
+        Ok(())
+    }
+
+    /// Set how formations match drones to slots: "index" (drone order), "nearest" (minimum
+    /// total distance) or "fastest" (earliest assembly given per-drone speed multipliers)
+    pub fn set_formation_assignment(&mut self, mode: &str) -> PyResult<()> {
+        self.slot_assignment = match mode {
+            "index" => SlotAssignment::Index,
+            "nearest" => SlotAssignment::Nearest,
+            "fastest" => SlotAssignment::Fastest,
+            _ => return Err(PyValueError::new_err(format!("unknown formation assignment '{}'", mode))),
+        };
+        Ok(())
+    }
+
+    /// Expand formations whose slots would be closer than a drone diameter (on by default)
+    ///
+    /// Slots are scaled about their centroid until the closest pair is one diameter (of the
+    /// largest drone in the formation) apart; slots pushed outside the world bounds are then
+    /// handled by the out-of-bounds policy.
+    pub fn set_formation_spacing_enforcement(&mut self, enabled: bool) {
+        self.enforce_slot_spacing = enabled;
+    }
+
+    /// Simulate GPS dropout: controllers of `ids` track a dead-reckoned estimate drifting at `drift_rate` m/s
+    ///
+    /// Disabling restores GPS and clears the accumulated horizontal estimate error.
+    #[pyo3(signature = (ids, enabled, drift_rate=0.05))]
+    pub fn set_gps_dropout(&mut self, ids: Vec<usize>, enabled: bool, drift_rate: f32) {
+        let drift_rate = self.scale_len(drift_rate);
+        for &id in &ids {
+            if let Some(drone) = self.drones.get_mut(id) {
+                drone.gps_drift_rate = enabled.then_some(drift_rate);
+                if !enabled {
+                    drone.position_error[0] = 0.0;
+                    drone.position_error[1] = 0.0;
+                }
+            }
+        }
+    }
+
+    /// Simulate barometric drift: the altitude `ids` control on is off by a deterministic bias
+    /// swinging +/-`drift_amplitude` (m) over `drift_period` (s), so their true altitude wanders
+    ///
+    /// A zero amplitude or period removes the drift and its bias.
+    pub fn set_baro_drift(&mut self, ids: Vec<usize>, drift_amplitude: f32, drift_period: f32) {
+        let enabled = drift_amplitude != 0.0 && drift_period > 0.0;
+        let drift_amplitude = self.scale_len(drift_amplitude);
+        for &id in &ids {
+            if let Some(drone) = self.drones.get_mut(id) {
+                drone.baro_drift = enabled.then_some([drift_amplitude, drift_period]);
+                drone.baro_time = 0.0;
+                drone.position_error[2] = 0.0;
+            }
+        }
+    }
+
+    /// Command: Takeoff
+    #[pyo3(signature = (ids, altitude=1.0))]
+    pub fn takeoff(&mut self, ids: Vec<usize>, altitude: f32) {
+        self.log_command("takeoff", &[id_args(&ids), vec![altitude]].concat());
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        let altitude = self.scale_len(altitude);
+        for &id in &ids {
+            if id < self.drones.len() {
+                let drone = &mut self.drones[id];
+                drone.target_pos = [drone.pos[0], drone.pos[1], altitude];
+                drone.target_yaw = 0.0;
+                drone.set_mode(DroneMode::Takeoff, reset_on_change);
+                drone.formation_offset = None;
+                drone.reset_pid();
+            }
+        }
+    }
+
+    /// Command: Takeoff all
+    #[pyo3(signature = (altitude=1.0))]
+    pub fn takeoff_all(&mut self, altitude: f32) {
+        let ids: Vec<usize> = (0..self.drones.len()).collect();
+        self.takeoff(ids, altitude);
+    }
+
+    /// Command: Land
+    pub fn land(&mut self, ids: Vec<usize>) {
+        self.log_command("land", &id_args(&ids));
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        for &id in &ids {
+            if id < self.drones.len() {
+                let drone = &mut self.drones[id];
+                drone.target_pos = [drone.pos[0], drone.pos[1], 0.05];
+                drone.target_yaw = 0.0;
+                drone.set_mode(DroneMode::Landing, reset_on_change);
+                drone.formation_offset = None;
+                drone.landing_pace = None;
+                drone.reset_pid();
+            }
+        }
+    }
+
+    /// Command: Land every airborne drone so all touch down `touchdown_time` seconds from now
+    ///
+    /// Each descends at the constant rate its altitude needs, so higher drones drop faster;
+    /// a drone that would exceed `max_descent_rate` (m/s) descends at that rate and lands late.
+    #[pyo3(signature = (touchdown_time, max_descent_rate=1.0))]
+    pub fn land_synchronized(&mut self, touchdown_time: f32, max_descent_rate: f32) {
+        self.log_command("land_synchronized", &[touchdown_time, max_descent_rate]);
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        let max_rate = self.scale_len(max_descent_rate.abs());
+        for drone in &mut self.drones {
+            if drone.mode == DroneMode::Idle {
+                continue;
+            }
+            let height = (drone.pos[2] - LANDED_ALTITUDE).max(0.0);
+            let rate = if touchdown_time > 0.0 { (height / touchdown_time).min(max_rate) } else { max_rate };
+            drone.target_pos = drone.pos;
+            drone.target_yaw = drone.yaw;
+            drone.set_mode(DroneMode::Landing, reset_on_change);
+            drone.formation_offset = None;
+            drone.landing_pace = Some([rate, max_rate]);
+            drone.reset_pid();
+        }
+    }
+
+    /// Freeze every drone not in `ids`: they skip physics and hold their pose until unfrozen
+    pub fn freeze_except(&mut self, ids: Vec<usize>) {
+        self.log_command("freeze_except", &id_args(&ids));
+        let keep: HashSet<usize> = ids.into_iter().collect();
+        for drone in &mut self.drones {
+            drone.frozen = !keep.contains(&drone.id);
+        }
+    }
+
+    /// Pause one drone's physics, keeping its exact state (including velocity) until resumed;
+    /// independent of freeze_except / unfreeze_all
+    pub fn pause_drone(&mut self, id: usize) {
+        self.log_command("pause_drone", &[id as f32]);
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.paused = true;
+        }
+    }
+
+    /// Resume a paused drone: it continues its command from where it stopped
+    pub fn resume_drone(&mut self, id: usize) {
+        self.log_command("resume_drone", &[id as f32]);
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.paused = false;
+        }
+    }
+
+    /// Run a group of drones at a different time scale, e.g. 0.5 for half-speed slow motion
+    pub fn set_time_scale(&mut self, ids: Vec<usize>, time_scale: f32) {
+        self.log_command("set_time_scale", &[id_args(&ids), vec![time_scale]].concat());
+        for &id in &ids {
+            if let Some(drone) = self.drones.get_mut(id) {
+                drone.time_scale = time_scale.max(0.0);
+            }
+        }
+    }
+
+    /// Resume physics for all frozen drones
+    pub fn unfreeze_all(&mut self) {
+        self.log_command("unfreeze_all", &[]);
+        for drone in &mut self.drones {
+            drone.frozen = false;
+        }
+    }
+
+    /// Command: Land every drone in place, lowest first
+    ///
+    /// Slower than `land_all`: a drone holds its altitude while another unlanded drone is
+    /// below it within PANIC_LAND_CLEARANCE, so stacked drones never descend onto each other.
+    pub fn panic_land(&mut self) {
+        self.log_command("panic_land", &[]);
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        self.maneuver = None;
+        self.monitor_center = None;
+        self.monitor_rotation = None;
+        for drone in &mut self.drones {
+            if drone.mode == DroneMode::Idle {
+                continue;
+            }
+            drone.target_pos = [drone.pos[0], drone.pos[1], 0.05];
+            drone.target_yaw = drone.yaw;
+            drone.set_mode(DroneMode::Landing, reset_on_change);
+            drone.formation_offset = None;
+            drone.reset_pid();
+        }
+        self.panic_landing = true;
+        self.sequence_panic_landing();
+    }
+
+    /// Command: Land all
+    pub fn land_all(&mut self) {
+        let ids: Vec<usize> = (0..self.drones.len()).collect();
+        self.land(ids);
+    }
+
+    /// Command: Hover
+    pub fn hover(&mut self, ids: Vec<usize>) {
+        self.log_command("hover", &id_args(&ids));
+        self.hold_position(&ids);
+    }
+
+    /// Command: Hover all
+    pub fn hover_all(&mut self) {
+        let ids: Vec<usize> = (0..self.drones.len()).collect();
+        self.hover(ids);
+    }
+
+    /// Command: Goto position
+    #[pyo3(signature = (id, x, y, z, yaw=0.0))]
+    pub fn goto(&mut self, id: usize, x: f32, y: f32, z: f32, yaw: f32) -> PyResult<()> {
+        self.log_command("goto", &[id as f32, x, y, z, yaw]);
+        self.push_undo();
+        // A filtered drone already in Goto keeps its effective target and eases to the new one
+        let effective = self.drones.get(id)
+            .filter(|d| d.target_filter < 1.0 && d.mode == DroneMode::Goto)
+            .map(|d| d.target_pos);
+        self.goto_target(id, x, y, z, yaw)?;
+        if let (Some(effective), Some(drone)) = (effective, self.drones.get_mut(id)) {
+            if drone.target_pos != effective {
+                drone.commanded_target = Some(drone.target_pos);
+                drone.target_pos = effective;
+            }
+        }
+        Ok(())
+    }
+
+    /// Smooth goto targets for drone `id` with a first-order filter: each step the effective
+    /// target closes `alpha` (0..1] of the gap to the latest one, so jittery streams of goto
+    /// calls from an external tracker don't shake the drone; 1 (default) follows immediately
+    pub fn set_target_filter(&mut self, id: usize, alpha: f32) {
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.target_filter = alpha.clamp(1e-3, 1.0);
+            if alpha >= 1.0 {
+                if let Some(commanded) = drone.commanded_target.take() {
+                    drone.target_pos = commanded;
+                }
+            }
+        }
+    }
+
+    /// Command: Fly to `target` along a minimum-jerk path taking `duration` seconds
+    #[pyo3(signature = (id, target, duration, yaw=0.0))]
+    pub fn goto_smooth(&mut self, id: usize, target: [f32; 3], duration: f32, yaw: f32) -> PyResult<()> {
+        self.log_command("goto_smooth", &[id as f32, target[0], target[1], target[2], duration, yaw]);
+        self.push_undo();
+        self.goto_target(id, target[0], target[1], target[2], yaw)?;
+
+        // The out-of-bounds policy may have rejected the target
+        let end = self.config.clamp_target(self.scale_point(target));
+        if let Some(drone) = self.drones.get_mut(id) {
+            if drone.mode == DroneMode::Goto && drone.target_pos == end {
+                drone.target_pos = drone.pos;
+                drone.trajectory = Some(SmoothTrajectory { start: drone.pos, end, duration: duration.max(0.0), elapsed: 0.0 });
+            }
+        }
+        Ok(())
+    }
+
+    /// Command: Fly through `waypoints` in order, advancing within `arrival_tolerance` of each;
+    /// a looping path restarts from the first waypoint, otherwise the drone holds the last
+    #[pyo3(signature = (id, waypoints, looping=false, arrival_tolerance=0.1))]
+    pub fn set_path(&mut self, id: usize, waypoints: Vec<[f32; 3]>, looping: bool, arrival_tolerance: f32) -> PyResult<()> {
+        let mut args = vec![id as f32, looping as u8 as f32, arrival_tolerance];
+        args.extend(waypoints.iter().flatten());
+        self.log_command("set_path", &args);
+        if id >= self.drones.len() || waypoints.is_empty() {
+            return Ok(());
+        }
+
+        let mut scaled = Vec::with_capacity(waypoints.len());
+        for &waypoint in &waypoints {
+            let requested = self.scale_point(waypoint);
+            let target = self.config.clamp_target(requested);
+            if target != requested {
+                match self.out_of_bounds_policy {
+                    OutOfBoundsPolicy::Clamp => {}
+                    OutOfBoundsPolicy::Reject => return Ok(()),
+                    OutOfBoundsPolicy::Error => self.check_in_bounds(waypoint)?,
+                }
+            }
+            scaled.push(target);
+        }
+
+        self.push_undo();
+        let [x, y, z] = waypoints[0];
+        let yaw = self.drones[id].target_yaw;
+        self.goto_target(id, x, y, z, yaw)?;
+        self.drones[id].path = Some(WaypointPath {
+            waypoints: scaled,
+            index: 0,
+            looping,
+            tolerance: self.scale_len(arrival_tolerance.max(0.0)),
+        });
+        Ok(())
+    }
+
+    /// Index of the waypoint a drone is flying to, or None when it has no active path
+    pub fn current_waypoint_index(&self, id: usize) -> Option<usize> {
+        self.drones.get(id)?.path.as_ref().map(|path| path.index)
+    }
+
+    /// Command: Keep yawing a drone toward `point` every step, independent of its position
+    /// commands (heading only: the drone model has no camera pitch)
+    pub fn track_point(&mut self, id: usize, point: [f32; 3]) {
+        self.log_command("track_point", &[id as f32, point[0], point[1], point[2]]);
+        let point = self.scale_point(point);
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.track_point = Some(point);
+        }
+    }
+
+    /// Move the point a tracking drone aims at; ignored if the drone is not tracking
+    pub fn update_track_point(&mut self, id: usize, point: [f32; 3]) {
+        self.log_command("update_track_point", &[id as f32, point[0], point[1], point[2]]);
+        let point = self.scale_point(point);
+        if let Some(tracked) = self.drones.get_mut(id).and_then(|d| d.track_point.as_mut()) {
+            *tracked = point;
+        }
+    }
+
+    /// Command: Stop tracking; the drone's mode steers its heading again
+    pub fn clear_track_point(&mut self, id: usize) {
+        self.log_command("clear_track_point", &[id as f32]);
+        if let Some(drone) = self.drones.get_mut(id) {
+            drone.track_point = None;
+        }
+    }
+
+    /// Command: Set velocity
+    #[pyo3(signature = (id, vx, vy, vz, yaw_rate=0.0))]
+    pub fn velocity(&mut self, id: usize, vx: f32, vy: f32, vz: f32, yaw_rate: f32) {
+        self.log_command("velocity", &[id as f32, vx, vy, vz, yaw_rate]);
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        let scale = self.command_scale();
+        if id < self.drones.len() {
+            let drone = &mut self.drones[id];
+            let max_v = 2.0;
+            drone.target_vel = [
+                vx.clamp(-max_v, max_v) * scale,
+                vy.clamp(-max_v, max_v) * scale,
+                vz.clamp(-max_v, max_v) * scale,
+            ];
+            drone.yaw_rate = yaw_rate.clamp(-PI, PI);
+            drone.set_mode(DroneMode::Velocity, reset_on_change);
+            drone.formation_offset = None;
+        }
+    }
+
+    /// Set the 2D scalar field for FieldFollow: `resolution` x `resolution` values, row-major
+    /// with rows along y, spanning bounds ((x_min, y_min), (x_max, y_max))
+    pub fn set_potential_field(&mut self, grid: Vec<f32>, resolution: usize, bounds: ([f32; 2], [f32; 2])) -> PyResult<()> {
+        if resolution < 2 || grid.len() != resolution * resolution {
+            return Err(PyValueError::new_err("grid must hold resolution^2 values with resolution >= 2"));
+        }
+        let (min, max) = bounds;
+        if max[0] <= min[0] || max[1] <= min[1] {
+            return Err(PyValueError::new_err("field bounds must have max > min"));
+        }
+        let scale = self.command_scale();
+        self.config.potential_field = Some(PotentialField {
+            grid,
+            resolution,
+            min: min.map(|v| v * scale),
+            max: max.map(|v| v * scale),
+        });
+        Ok(())
+    }
+
+    /// Command: Follow the potential field gradient at the current altitude
+    ///
+    /// Horizontal velocity is `gain` times the gradient (capped at max velocity); a negative
+    /// gain descends the field instead.
+    #[pyo3(signature = (ids, gain=1.0))]
+    pub fn field_follow(&mut self, ids: Vec<usize>, gain: f32) {
+        self.log_command("field_follow", &[id_args(&ids), vec![gain]].concat());
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        for &id in &ids {
+            if let Some(drone) = self.drones.get_mut(id) {
+                drone.target_pos = self.config.clamp_target(drone.pos);
+                drone.field_gain = gain;
+                drone.set_mode(DroneMode::FieldFollow, reset_on_change);
+                drone.formation_offset = None;
+            }
         }
     }
 
-    /// Reset PID controller state
-    pub fn reset_pid(&mut self) {
-        self.pid_integral = [0.0, 0.0, 0.0];
-        self.pid_prev_error = [0.0, 0.0, 0.0];
+    /// Command: Flock - drones cohere, align and separate like boids around `center`
+    pub fn flock(&mut self, ids: Vec<usize>, center: [f32; 3]) {
+        self.log_command("flock", &[id_args(&ids), center.to_vec()].concat());
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        let center = self.config.clamp_target(self.scale_point(center));
+        for &id in &ids {
+            if let Some(drone) = self.drones.get_mut(id) {
+                drone.target_pos = center;
+                drone.set_mode(DroneMode::Flock, reset_on_change);
+                drone.formation_offset = None;
+                drone.trajectory = None;
+            }
+        }
     }
 
-    /// Compute velocity command using PID position control
-    fn compute_position_control(&mut self, dt: f32, max_vel: f32) -> [f32; 3] {
-        const KP: f32 = 2.0;
-        const KI: f32 = 0.01;
-        const KD: f32 = 0.5;
+    /// Set the boids weights and how far (m) a drone sees its flockmates
+    pub fn set_flock_params(&mut self, cohesion: f32, alignment: f32, separation: f32, perception_radius: f32) {
+        self.config.flock_params = [cohesion, alignment, separation, self.scale_len(perception_radius.max(0.0))];
+    }
 
-        let mut vel_cmd = [0.0f32; 3];
+    /// Command: Formation - Line, of `ids` (default all drones) in list order
+    #[pyo3(signature = (center, spacing=1.0, axis="x", ids=None))]
+    pub fn formation_line(&mut self, center: [f32; 3], spacing: f32, axis: &str, ids: Option<Vec<usize>>) -> PyResult<()> {
+        self.log_command("formation_line", &[vec![center[0], center[1], center[2], spacing, if axis == "y" { 1.0 } else { 0.0 }], id_args(ids.as_deref().unwrap_or_default())].concat());
+        self.push_undo();
+        let ids = self.formation_ids(ids);
+        let slots = line_slots(ids.len(), center, spacing, axis);
+        self.apply_formation_slots(&ids, &slots)
+    }
 
-        for i in 0..3 {
-            let error = self.target_pos[i] - self.pos[i];
+    /// Command: Formation - Circle, of `ids` (default all drones) in list order
+    #[pyo3(signature = (center, radius=1.5, ids=None))]
+    pub fn formation_circle(&mut self, center: [f32; 3], radius: f32, ids: Option<Vec<usize>>) -> PyResult<()> {
+        self.log_command("formation_circle", &[vec![center[0], center[1], center[2], radius], id_args(ids.as_deref().unwrap_or_default())].concat());
+        self.push_undo();
+        let ids = self.formation_ids(ids);
+        let slots = circle_slots(ids.len(), center, radius);
+        self.apply_formation_slots(&ids, &slots)
+    }
 
-            // Proportional
-            let p_term = KP * error;
+    /// Command: Formation - Grid, of `ids` (default all drones) in list order
+    #[pyo3(signature = (center, spacing=1.0, ids=None))]
+    pub fn formation_grid(&mut self, center: [f32; 3], spacing: f32, ids: Option<Vec<usize>>) -> PyResult<()> {
+        self.log_command("formation_grid", &[vec![center[0], center[1], center[2], spacing], id_args(ids.as_deref().unwrap_or_default())].concat());
+        self.push_undo();
+        let ids = self.formation_ids(ids);
+        let slots = grid_slots(ids.len(), center, spacing);
+        self.apply_formation_slots(&ids, &slots)
+    }
 
-            // Integral with anti-windup
-            self.pid_integral[i] += error * dt;
-            self.pid_integral[i] = self.pid_integral[i].clamp(-1.0, 1.0);
-            let i_term = KI * self.pid_integral[i];
+    /// Command: Formation - V shape, of `ids` (default all drones) in list order
+    #[pyo3(signature = (center, spacing=1.0, ids=None))]
+    pub fn formation_v(&mut self, center: [f32; 3], spacing: f32, ids: Option<Vec<usize>>) -> PyResult<()> {
+        self.log_command("formation_v", &[vec![center[0], center[1], center[2], spacing], id_args(ids.as_deref().unwrap_or_default())].concat());
+        self.push_undo();
+        let ids = self.formation_ids(ids);
+        let slots = v_slots(ids.len(), center, spacing);
+        self.apply_formation_slots(&ids, &slots)
+    }
 
-            // Derivative
-            let d_term = if dt > 0.0 {
-                KD * (error - self.pid_prev_error[i]) / dt
-            } else {
-                0.0
-            };
+    /// Command: Divide the swarm among `targets`, minimizing total travel with balanced groups
+    ///
+    /// Each target takes at most ceil(n / targets) + `size_tolerance` drones; every group
+    /// gathers in a circle of `group_radius` around its target. Returns each drone's target index.
+    #[pyo3(signature = (targets, size_tolerance=0, group_radius=1.0))]
+    pub fn optimal_split(&mut self, targets: Vec<[f32; 3]>, size_tolerance: usize, group_radius: f32) -> PyResult<Vec<usize>> {
+        self.log_command("optimal_split", &[targets.concat(), vec![size_tolerance as f32, group_radius]].concat());
+        if targets.is_empty() {
+            return Err(PyValueError::new_err("optimal_split needs at least one target"));
+        }
+        let n = self.drones.len();
+        let capacity = n.div_ceil(targets.len()) + size_tolerance;
 
-            self.pid_prev_error[i] = error;
+        // One column per seat: target k owns columns k*capacity..(k+1)*capacity
+        let scaled: Vec<[f32; 3]> = targets.iter().map(|&t| self.scale_point(t)).collect();
+        let cost: Vec<Vec<f32>> = self.drones.iter().map(|d| {
+            scaled.iter().flat_map(|&t| std::iter::repeat_n(distance(d.pos, t), capacity)).collect()
+        }).collect();
+        let assignment: Vec<usize> = min_cost_assignment(&cost).into_iter().map(|col| col / capacity).collect();
 
-            vel_cmd[i] = (p_term + i_term + d_term).clamp(-max_vel, max_vel);
+        self.push_undo();
+        for (k, &target) in targets.iter().enumerate() {
+            let ids: Vec<usize> = (0..n).filter(|&id| assignment[id] == k).collect();
+            let slots = circle_slots(ids.len(), target, group_radius);
+            self.apply_formation_slots(&ids, &slots)?;
         }
+        Ok(assignment)
+    }
 
-        vel_cmd
+    /// Assign drones fixed body-frame offsets from a formation anchor (replaces earlier roles)
+    pub fn set_roles(&mut self, roles: Vec<(usize, [f32; 3])>) {
+        self.roles = roles.into_iter().filter(|&(id, _)| id < self.drones.len()).collect();
     }
 
-    /// Update drone physics for one timestep
-    pub fn step(&mut self, dt: f32, max_vel: f32, monitor_center: Option<[f32; 3]>, monitor_orbit_speed: f32) {
-        match self.mode {
-            DroneMode::Idle => {
-                // Slow down to stop
-                self.vel[0] *= 0.95;
-                self.vel[1] *= 0.95;
-                self.vel[2] *= 0.95;
-            }
+    /// Command: Place each role-assigned drone at its offset rotated by `anchor_yaw` about
+    /// the anchor, facing `anchor_yaw`
+    #[pyo3(signature = (anchor, anchor_yaw=0.0))]
+    pub fn apply_roles(&mut self, anchor: [f32; 3], anchor_yaw: f32) -> PyResult<()> {
+        self.log_command("apply_roles", &[anchor[0], anchor[1], anchor[2], anchor_yaw]);
+        self.push_undo();
+        let (sin, cos) = anchor_yaw.sin_cos();
+        let ids: Vec<usize> = self.roles.iter().map(|&(id, _)| id).collect();
+        let slots: Vec<[f32; 3]> = self.roles.iter().map(|&(_, offset)| [
+            anchor[0] + offset[0] * cos - offset[1] * sin,
+            anchor[1] + offset[0] * sin + offset[1] * cos,
+            anchor[2] + offset[2],
+        ]).collect();
 
-            DroneMode::Takeoff | DroneMode::Landing | DroneMode::Goto | DroneMode::Hover => {
-                // Position control mode
-                let vel_cmd = self.compute_position_control(dt, max_vel);
-                self.apply_velocity_control(vel_cmd, dt);
+        self.apply_fixed_slots(&ids, &slots)?;
+        for &id in &ids {
+            self.drones[id].target_yaw = anchor_yaw;
+        }
+        Ok(())
+    }
 
-                // Check for mode transitions
-                let dist = ((self.target_pos[0] - self.pos[0]).powi(2)
-                          + (self.target_pos[1] - self.pos[1]).powi(2)
-                          + (self.target_pos[2] - self.pos[2]).powi(2)).sqrt();
+    /// Command: Regroup into a tight hex-packed cluster around `center`
+    ///
+    /// Neighbours sit one collision diameter (of the largest drone) apart; drones that
+    /// don't fit within `packing_radius` stack in layers above.
+    #[pyo3(signature = (center, packing_radius=1.0))]
+    pub fn cluster(&mut self, center: [f32; 3], packing_radius: f32) -> PyResult<()> {
+        self.log_command("cluster", &[center[0], center[1], center[2], packing_radius]);
+        if !packing_radius.is_finite() || packing_radius < 0.0 {
+            return Err(PyValueError::new_err("cluster packing_radius must be finite and non-negative"));
+        }
+        self.push_undo();
+        let ids: Vec<usize> = (0..self.drones.len()).collect();
+        let max_radius = self.drones.iter().map(|d| d.radius).fold(0.0, f32::max);
+        // Slots are in command units, so undo the command scale on the physical diameter
+        let spacing = (2.0 * max_radius / self.command_scale()).max(1e-3);
+        let slots = hex_slots(ids.len(), center, spacing, packing_radius);
+        self.apply_formation_slots(&ids, &slots)
+    }
 
-                if self.mode == DroneMode::Landing && self.pos[2] < 0.15 {
-                    self.mode = DroneMode::Idle;
-                    self.vel = [0.0, 0.0, 0.0];
-                } else if self.mode == DroneMode::Takeoff && dist < 0.1 {
-                    self.mode = DroneMode::Hover;
+    /// Command: Spread the swarm evenly over the box `min`..`max` (a flat box gives one layer)
+    ///
+    /// Targets come from Lloyd relaxation seeded at the current positions, so each drone takes
+    /// the even-coverage spot nearest where it already is rather than a fixed grid slot.
+    pub fn redistribute(&mut self, min: [f32; 3], max: [f32; 3]) -> PyResult<()> {
+        self.log_command("redistribute", &[min.to_vec(), max.to_vec()].concat());
+        if (0..3).any(|i| max[i] < min[i]) {
+            return Err(PyValueError::new_err("redistribute bounds must have max >= min"));
+        }
+        self.push_undo();
+        let ids: Vec<usize> = (0..self.drones.len()).collect();
+        let scale = self.command_scale();
+        let starts: Vec<[f32; 3]> = self.drones.iter().map(|d| d.pos.map(|v| v / scale)).collect();
+        let slots = lloyd_slots(&starts, min, max, LLOYD_ITERATIONS);
+        self.apply_fixed_slots(&ids, &slots)
+    }
+
+    /// Command: Raise targets so no airborne drone is held inside another's downwash cone
+    ///
+    /// Working up from the lowest target, a drone whose target is within `min_vertical_sep`
+    /// above or below an already-placed one, and horizontally inside that drone's cone at
+    /// `min_vertical_sep`, is lifted to sit `min_vertical_sep` above it (capped at the ceiling).
+    /// Returns the ids whose targets moved.
+    pub fn deconflict_vertical(&mut self, min_vertical_sep: f32) -> Vec<usize> {
+        self.log_command("deconflict_vertical", &[min_vertical_sep]);
+        let separation = self.scale_len(min_vertical_sep.max(0.0));
+        let cone_radius = DOWNWASH_FOOTPRINT + separation * DOWNWASH_HALF_ANGLE.tan();
+
+        let mut order: Vec<usize> = self.drones.iter()
+            .filter(|d| !matches!(d.mode, DroneMode::Idle | DroneMode::Landing))
+            .map(|d| d.id)
+            .collect();
+        order.sort_by(|&a, &b| self.drones[a].target_pos[2].total_cmp(&self.drones[b].target_pos[2]));
+
+        let mut placed: Vec<[f32; 3]> = Vec::with_capacity(order.len());
+        let mut moved = Vec::new();
+        for id in order {
+            let original = self.drones[id].target_pos;
+            let mut target = original;
+            // Lifting can only create conflicts with higher placed targets, so this terminates
+            // Compared against p +/- separation (not |dz|) so a target already lifted to exactly
+            // p + separation doesn't still read as conflicting after rounding
+            while let Some(below) = placed.iter().find(|p| {
+                target[2] > p[2] - separation && target[2] < p[2] + separation
+                    && (target[0] - p[0]).hypot(target[1] - p[1]) < cone_radius
+            }) {
+                let lifted = self.config.clamp_target([target[0], target[1], below[2] + separation]);
+                if lifted[2] <= target[2] {
+                    break;
                 }
+                target = lifted;
             }
+            placed.push(target);
 
-            DroneMode::Velocity => {
-                // Direct velocity control
-                self.apply_velocity_control(self.target_vel, dt);
+            if target != original {
+                let drone = &mut self.drones[id];
+                drone.target_pos = target;
+                if let Some(offset) = &mut drone.formation_offset {
+                    offset[2] = target[2] - drone.formation_anchor[2];
+                }
+                moved.push(id);
             }
+        }
+        moved.sort_unstable();
+        moved
+    }
 
-            DroneMode::Monitor => {
-                // Orbital surveillance mode
-                if let Some(center) = monitor_center {
-                    // Update angle
-                    self.monitor_angle += monitor_orbit_speed * dt;
-                    if self.monitor_angle > 2.0 * PI {
-                        self.monitor_angle -= 2.0 * PI;
-                    }
+    /// Command: Several formations at once, one per disjoint group of ids
+    ///
+    /// Each assignment is `(ids, kind, center, params)` where kind/params are
+    /// "line" [spacing, axis (0=x, 1=y)], "circle" [radius], "grid" [spacing]
+    /// or "v" [spacing]; omitted params use the single-formation defaults.
+    pub fn multi_formation(&mut self, assignments: Vec<FormationAssignment>) -> PyResult<()> {
+        // Each group logged as: id count, ids, center, param count, params
+        let args: Vec<f32> = assignments.iter().flat_map(|(ids, _, center, params)| {
+            [vec![ids.len() as f32], id_args(ids), center.to_vec(), vec![params.len() as f32], params.clone()].concat()
+        }).collect();
+        self.log_command("multi_formation", &args);
 
-                    // Calculate orbital position
-                    self.target_pos[0] = center[0] + self.monitor_radius * self.monitor_angle.cos();
-                    self.target_pos[1] = center[1] + self.monitor_radius * self.monitor_angle.sin();
-                    self.target_pos[2] = self.monitor_altitude;
+        // Validate everything up front so a bad entry leaves targets untouched
+        let mut seen = HashSet::new();
+        let mut groups = Vec::with_capacity(assignments.len());
+        for (ids, kind, center, params) in &assignments {
+            let ids: Vec<usize> = ids.iter().copied().filter(|&id| id < self.drones.len()).collect();
+            if let Some(&dup) = ids.iter().find(|&&id| !seen.insert(id)) {
+                return Err(PyValueError::new_err(format!("drone {} appears in more than one formation", dup)));
+            }
+            let slots = formation_slots(kind, ids.len(), *center, params)
+                .ok_or_else(|| PyValueError::new_err(format!("unknown formation type '{}'", kind)))?;
+            groups.push((ids, slots));
+        }
 
-                    // Face towards center
-                    let dx = center[0] - self.target_pos[0];
-                    let dy = center[1] - self.target_pos[1];
-                    self.target_yaw = dy.atan2(dx);
+        self.push_undo();
+        for (ids, slots) in &groups {
+            self.apply_formation_slots(ids, slots)?;
+        }
+        Ok(())
+    }
 
-                    // Use position control to reach orbital position
-                    let vel_cmd = self.compute_position_control(dt, max_vel);
-                    self.apply_velocity_control(vel_cmd, dt);
-                }
-            }
+    /// Command: Waypoint - all drones go to formation around point
+    #[pyo3(signature = (x, y, z))]
+    pub fn waypoint(&mut self, x: f32, y: f32, z: f32) -> PyResult<()> {
+        let center = [x, y, z];
+        let radius = 0.8;
+
+        if self.drones.len() == 1 {
+            self.goto(0, x, y, z, 0.0)
+        } else {
+            self.formation_circle(center, radius, None)
         }
+    }
 
-        // Update yaw
-        let yaw_error = self.target_yaw - self.yaw;
-        // Normalize to [-PI, PI]
-        let yaw_error = yaw_error.sin().atan2(yaw_error.cos());
-        self.yaw_rate = (2.0 * yaw_error).clamp(-PI, PI);
-        self.yaw += self.yaw_rate * dt;
+    /// Command: Monitor mode - orbital surveillance
+    #[pyo3(signature = (x, y, z))]
+    pub fn monitor(&mut self, x: f32, y: f32, z: f32) {
+        self.log_command("monitor", &[x, y, z]);
+        self.monitor_center = Some(self.scale_point([x, y, z]));
+        self.monitor_rotation = None;
+        let ids: Vec<usize> = (0..self.drones.len()).collect();
+        self.assign_monitor_slots(&ids, z);
+    }
 
-        // Clamp position to world bounds
-        self.pos[0] = self.pos[0].clamp(-10.0, 10.0);
-        self.pos[1] = self.pos[1].clamp(-10.0, 10.0);
-        self.pos[2] = self.pos[2].clamp(0.0, 5.0);
+    /// Command: Monitor with battery rotation - keep `reserves` drones back and swap them
+    /// into the orbit as monitoring drones drop below `low_battery_threshold`
+    ///
+    /// Low drones land (over the nearest charge zone, if any) and rejoin the reserve pool
+    /// once recharged.
+    #[pyo3(signature = (x, y, z, low_battery_threshold=20.0, reserves=1))]
+    pub fn monitor_with_rotation(&mut self, x: f32, y: f32, z: f32, low_battery_threshold: f32, reserves: usize) {
+        self.log_command("monitor_with_rotation", &[x, y, z, low_battery_threshold, reserves as f32]);
+        let n = self.drones.len();
+        let orbiting = n - reserves.min(n.saturating_sub(1));
+        let orbit_ids: Vec<usize> = (0..orbiting).collect();
+        let reserve_ids: Vec<usize> = (orbiting..n).filter(|&id| self.drones[id].mode != DroneMode::Idle).collect();
 
-        // Update health based on bounds and battery
-        self.healthy = self.pos[0].abs() < 15.0
-                    && self.pos[1].abs() < 15.0
-                    && self.pos[2] >= 0.0
-                    && self.pos[2] <= 10.0
-                    && self.battery > 0.0;
+        self.monitor_center = Some(self.scale_point([x, y, z]));
+        self.assign_monitor_slots(&orbit_ids, z);
+        self.hold_position(&reserve_ids);
+        self.monitor_rotation = Some(MonitorRotation {
+            low_battery_threshold,
+            pool: (0..n).collect(),
+            vacancies: Vec::new(),
+        });
     }
 
-    /// Apply velocity control with simple dynamics
-    fn apply_velocity_control(&mut self, target_vel: [f32; 3], dt: f32) {
-        // Velocity response (like a first-order system)
-        const RESPONSE_RATE: f32 = 5.0;  // How fast velocity responds
-        const DRAG: f32 = 0.1;
+    /// Maneuver: breathe - radial formation oscillating between two radii
+    #[pyo3(signature = (center, min_radius, max_radius, period))]
+    pub fn maneuver_breathe(&mut self, center: [f32; 3], min_radius: f32, max_radius: f32, period: f32) {
+        self.log_command("maneuver_breathe", &[center[0], center[1], center[2], min_radius, max_radius, period]);
+        self.maneuver = Some(Maneuver::Breathe {
+            center: self.scale_point(center),
+            min_radius: self.scale_len(min_radius),
+            max_radius: self.scale_len(max_radius),
+            period: period.max(1e-3),
+            start_time: self.sim_time,
+        });
+    }
 
-        for i in 0..3 {
-            let accel = RESPONSE_RATE * (target_vel[i] - self.vel[i]) - DRAG * self.vel[i];
-            self.vel[i] += accel * dt;
+    /// Maneuver: patrol - drones evenly spaced around a polygon, moving along its edges
+    #[pyo3(signature = (vertices, altitude, speed))]
+    pub fn patrol_perimeter(&mut self, vertices: Vec<[f32; 2]>, altitude: f32, speed: f32) -> PyResult<()> {
+        self.log_command("patrol_perimeter", &[vertices.concat(), vec![altitude, speed]].concat());
+        if vertices.len() < 2 {
+            return Err(PyValueError::new_err("patrol perimeter needs at least 2 vertices"));
+        }
+        if perimeter_length(&vertices) <= 0.0 {
+            return Err(PyValueError::new_err("patrol perimeter must have a non-zero length"));
         }
 
-        // Integrate position
-        self.pos[0] += self.vel[0] * dt;
-        self.pos[1] += self.vel[1] * dt;
-        self.pos[2] += self.vel[2] * dt;
+        let scale = self.command_scale();
+        self.maneuver = Some(Maneuver::Patrol {
+            vertices: vertices.iter().map(|v| [v[0] * scale, v[1] * scale]).collect(),
+            altitude: altitude * scale,
+            speed: speed * scale,
+            start_time: self.sim_time,
+        });
+        Ok(())
     }
-}
 
-/// Python-exposed drone state (for returning to Python)
-#[pyclass]
-#[derive(Clone)]
-pub struct PyDroneState {
-    #[pyo3(get)]
-    pub id: usize,
-    #[pyo3(get)]
-    pub pos: [f32; 3],
-    #[pyo3(get)]
-    pub vel: [f32; 3],
-    #[pyo3(get)]
-    pub yaw: f32,
-    #[pyo3(get)]
-    pub battery: f32,
-    #[pyo3(get)]
-    pub healthy: bool,
-}
+    /// Restore the targets from before the last goto/formation command; false if nothing to undo
+    pub fn undo_command(&mut self) -> bool {
+        self.log_command("undo_command", &[]);
+        let Some(snapshot) = self.undo_history.pop_back() else {
+            return false;
+        };
+        for (drone, saved) in self.drones.iter_mut().zip(&snapshot) {
+            saved.restore(drone);
+        }
+        true
+    }
 
-/// The main swarm physics engine
-#[pyclass]
-pub struct RustSwarm {
-    drones: Vec<Drone>,
-    sim_time: f32,
-    physics_dt: f32,
-    max_velocity: f32,
-    speed_multiplier: f32,
-    monitor_center: Option<[f32; 3]>,
-    monitor_orbit_speed: f32,
-}
+    /// Set how many commands can be undone (0 disables undo history)
+    pub fn set_undo_depth(&mut self, depth: usize) {
+        self.undo_depth = depth;
+        while self.undo_history.len() > depth {
+            self.undo_history.pop_front();
+        }
+    }
 
-#[pymethods]
-impl RustSwarm {
-    #[new]
-    #[pyo3(signature = (num_drones, physics_hz=240))]
-    pub fn new(num_drones: usize, physics_hz: u32) -> Self {
+    /// Maneuver: wave - vertical ripple traveling along `direction` while drones hold x/y
+    #[pyo3(signature = (direction, wavelength, amplitude, speed, base_altitude))]
+    pub fn maneuver_wave(&mut self, direction: [f32; 2], wavelength: f32, amplitude: f32, speed: f32, base_altitude: f32) {
+        self.log_command("maneuver_wave", &[direction[0], direction[1], wavelength, amplitude, speed, base_altitude]);
+        let norm = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+        let direction = if norm > 0.0 { [direction[0] / norm, direction[1] / norm] } else { [1.0, 0.0] };
+
+        self.maneuver = Some(Maneuver::Wave {
+            direction,
+            wavelength: self.scale_len(wavelength).max(1e-3),
+            amplitude: self.scale_len(amplitude),
+            speed: self.scale_len(speed),
+            base_altitude: self.scale_len(base_altitude),
+            start_time: self.sim_time,
+            anchors: self.drones.iter().map(|d| [d.pos[0], d.pos[1]]).collect(),
+        });
+    }
+
+    /// Replay recorded positions (`frames[k][id]`, sampled at `fps`) as live targets
+    ///
+    /// Drones chase the interpolated path with their normal dynamics, so tracking is close
+    /// but not exact. Drones missing from a frame are left alone.
+    pub fn play_recording(&mut self, frames: Vec<Vec<[f32; 3]>>, fps: f32) -> PyResult<()> {
+        self.log_command("play_recording", &[frames.len() as f32, fps]);
+        if fps <= 0.0 {
+            return Err(PyValueError::new_err("fps must be greater than 0"));
+        }
+        if frames.is_empty() {
+            return Ok(());
+        }
+
+        self.maneuver = Some(Maneuver::Playback {
+            frames: frames.iter().map(|frame| frame.iter().map(|&p| self.scale_point(p)).collect()).collect(),
+            fps,
+            start_time: self.sim_time,
+        });
+        Ok(())
+    }
+
+    /// Maneuver: morph - blend every drone from its current target to its slot in a new
+    /// formation over `duration` seconds, shaped by `easing`
+    ///
+    /// `kind`/`params` are as in multi_formation; easings are "linear", "ease_in", "ease_out",
+    /// "ease_in_out" and "bounce". Drones end exactly on their slots and hold the formation.
+    #[pyo3(signature = (kind, center, duration, params=Vec::new(), easing="linear"))]
+    pub fn morph_to(&mut self, kind: &str, center: [f32; 3], duration: f32, params: Vec<f32>, easing: &str) -> PyResult<()> {
+        self.log_command("morph_to", &[center.to_vec(), vec![duration], params.clone()].concat());
+        let easing = Easing::from_name(easing)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown easing '{}'", easing)))?;
+        let ids: Vec<usize> = (0..self.drones.len()).collect();
+        let slots = formation_slots(kind, ids.len(), center, &params)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown formation type '{}'", kind)))?;
+        let slots = self.spread_slots(&ids, &slots);
+        let slots = self.assign_slots(&ids, &slots);
+
+        self.push_undo();
+        self.maneuver = Some(Maneuver::Morph {
+            start: self.drones.iter().map(|d| d.target_pos).collect(),
+            end: slots.iter().map(|&slot| self.config.clamp_target(self.scale_point(slot))).collect(),
+            duration: duration.max(0.0),
+            easing,
+            start_time: self.sim_time,
+        });
+        Ok(())
+    }
+
+    /// Add (or replace) a named objective evaluated every step
+    ///
+    /// Kinds: "all_in_zone" [x, y, radius], "formation_achieved" [tolerance=0.2], "all_landed".
+    #[pyo3(signature = (name, kind, params=Vec::new()))]
+    pub fn add_objective(&mut self, name: &str, kind: &str, params: Vec<f32>) -> PyResult<()> {
+        let param = |i: usize, default: f32| params.get(i).copied().unwrap_or(default);
+        let kind = match kind {
+            "all_in_zone" => {
+                if params.len() < 3 {
+                    return Err(PyValueError::new_err("all_in_zone needs [x, y, radius]"));
+                }
+                ObjectiveKind::AllInZone {
+                    center: [self.scale_len(params[0]), self.scale_len(params[1])],
+                    radius: self.scale_len(params[2]),
+                }
+            }
+            "formation_achieved" => ObjectiveKind::FormationAchieved { tolerance: self.scale_len(param(0, 0.2)) },
+            "all_landed" => ObjectiveKind::AllLanded,
+            _ => return Err(PyValueError::new_err(format!("unknown objective kind '{}'", kind))),
+        };
+
+        self.objectives.retain(|o| o.name != name);
+        self.objectives.push(Objective { name: name.to_string(), kind, complete: false });
+        Ok(())
+    }
+
+    /// Objectives in the order added, with whether each has been completed
+    pub fn get_objective_status(&self) -> Vec<(String, bool)> {
+        self.objectives.iter().map(|o| (o.name.clone(), o.complete)).collect()
+    }
+
+    /// Remove all objectives
+    pub fn clear_objectives(&mut self) {
+        self.objectives.clear();
+    }
+
+    /// Command: Translate the whole formation at `velocity` (m/s), holding shape
+    ///
+    /// Moves position targets rather than commanding velocity, so drones keep correcting
+    /// toward their slots while the formation travels. Runs until `stop_cruise`.
+    pub fn cruise_formation(&mut self, velocity: [f32; 3]) {
+        self.log_command("cruise_formation", &velocity);
+        self.maneuver = Some(Maneuver::Cruise { velocity: self.scale_point(velocity) });
+    }
+
+    /// Stop cruising; drones hold where their targets are now
+    pub fn stop_cruise(&mut self) {
+        self.log_command("stop_cruise", &[]);
+        if matches!(self.maneuver, Some(Maneuver::Cruise { .. })) {
+            self.maneuver = None;
+        }
+    }
+
+    /// Stop the active continuous maneuver; drones hold their last targets
+    pub fn stop_maneuver(&mut self) {
+        self.log_command("stop_maneuver", &[]);
+        self.maneuver = None;
+    }
+
+    /// Command: Reset simulation
+    pub fn reset(&mut self) {
+        self.log_command("reset", &[]);
+        let num_drones = self.drones.len();
         let grid_size = (num_drones as f32).sqrt().ceil() as usize;
         let spacing = 0.5;
 
-        let mut drones = Vec::with_capacity(num_drones);
         for i in 0..num_drones {
             let row = i / grid_size;
             let col = i % grid_size;
             let x = (col as f32 - grid_size as f32 / 2.0) * spacing;
             let y = (row as f32 - grid_size as f32 / 2.0) * spacing;
-            let z = 0.1;
-            drones.push(Drone::new(i, x, y, z));
+
+            let drone = &mut self.drones[i];
+            drone.pos = [x, y, 0.1];
+            drone.vel = [0.0, 0.0, 0.0];
+            drone.yaw = 0.0;
+            drone.yaw_rate = 0.0;
+            drone.mode = DroneMode::Idle;
+            drone.battery = 100.0;
+            drone.healthy = true;
+            drone.formation_offset = None;
+            drone.control_effort = 0.0;
+            drone.stuck_time = 0.0;
+            drone.prev_accel = [0.0, 0.0, 0.0];
+            drone.distance_traveled = 0.0;
+            drone.energy_used = 0.0;
+            drone.flight_time = 0.0;
+            drone.frozen = false;
+            drone.paused = false;
+            drone.crashed = false;
+            drone.ground_impact = false;
+            drone.trajectory = None;
+            drone.path = None;
+            drone.track_point = None;
+            drone.landing_pace = None;
+            drone.commanded_target = None;
+            drone.trail.clear();
+            drone.gps_drift_rate = None;
+            drone.baro_drift = None;
+            drone.position_error = [0.0, 0.0, 0.0];
+            drone.fault_phase = None;
+            drone.home = [x, y, 0.1];
+            drone.failsafe_stage = None;
+            drone.mode_entered_time = 0.0;
+            drone.mode_changed = false;
+            drone.reset_pid();
         }
 
-        Self {
-            drones,
-            sim_time: 0.0,
-            physics_dt: 1.0 / physics_hz as f32,
-            max_velocity: 2.0,
-            speed_multiplier: 1.0,
-            monitor_center: None,
-            monitor_orbit_speed: 0.3,
+        self.sim_time = 0.0;
+        self.step_count = 0;
+        self.monitor_center = None;
+        self.monitor_rotation = None;
+        self.panic_landing = false;
+        self.maneuver = None;
+        self.undo_history.clear();
+        self.events.clear();
+        self.dropped_events = 0;
+        for objective in &mut self.objectives {
+            objective.complete = false;
         }
+        self.rebuild_spatial_grid();
     }
 
-    /// Step physics for all drones (parallelized with rayon)
-    pub fn step(&mut self) -> f32 {
-        let dt = self.physics_dt;
-        let max_vel = self.max_velocity * self.speed_multiplier;
-        let monitor_center = self.monitor_center;
-        let monitor_orbit_speed = self.monitor_orbit_speed;
+    /// Time `steps` physics steps of a fresh `num_drones` swarm cycling through takeoff and formations
+    ///
+    /// Runs on a separate swarm with this swarm's config and rate; this swarm is left untouched.
+    pub fn benchmark(&self, num_drones: usize, steps: usize) -> PyResult<PyBenchmarkResult> {
+        let mut swarm = RustSwarm::new(num_drones, 1);
+        swarm.physics_dt = self.physics_dt;
+        swarm.config = self.config.clone();
 
-        // Parallel update of all drones
-        self.drones.par_iter_mut().for_each(|drone| {
-            drone.step(dt, max_vel, monitor_center, monitor_orbit_speed);
-        });
+        let phase = (steps / 5).max(1);
+        let start = std::time::Instant::now();
+        for step in 0..steps {
+            if step % phase == 0 {
+                let center = [0.0, 0.0, 1.5];
+                match step / phase % 5 {
+                    0 => swarm.takeoff_all(1.5),
+                    1 => swarm.formation_circle(center, 3.0, None)?,
+                    2 => swarm.formation_grid(center, 1.0, None)?,
+                    3 => swarm.formation_line(center, 1.0, "y", None)?,
+                    _ => swarm.formation_v(center, 1.0, None)?,
+                }
+            }
+            swarm.step();
+        }
+        let total_time = start.elapsed().as_secs_f64();
 
-        self.sim_time += dt;
-        self.sim_time
+        Ok(PyBenchmarkResult {
+            num_drones,
+            steps,
+            total_time,
+            steps_per_sec: steps as f64 / total_time,
+            us_per_drone_step: total_time * 1e6 / (steps * num_drones).max(1) as f64,
+            threads: rayon::current_num_threads(),
+        })
     }
 
-    /// Step physics multiple times (for speed multiplier)
-    pub fn step_multiple(&mut self, steps: u32) -> f32 {
-        for _ in 0..steps {
-            self.step();
+    /// Respawn with new drone count
+    pub fn respawn(&mut self, num_drones: usize) {
+        self.log_command("respawn", &[num_drones as f32]);
+        let grid_size = (num_drones as f32).sqrt().ceil() as usize;
+        let spacing = 0.5;
+
+        self.drones.clear();
+        for i in 0..num_drones {
+            let row = i / grid_size;
+            let col = i % grid_size;
+            let x = (col as f32 - grid_size as f32 / 2.0) * spacing;
+            let y = (row as f32 - grid_size as f32 / 2.0) * spacing;
+            let z = 0.1;
+            self.drones.push(Drone::new(i, x, y, z));
         }
-        self.sim_time
+
+        self.sim_time = 0.0;
+        self.step_count = 0;
+        self.monitor_center = None;
+        self.monitor_rotation = None;
+        self.panic_landing = false;
+        self.maneuver = None;
+        self.undo_history.clear();
+        self.events.clear();
+        self.dropped_events = 0;
+        self.roles.retain(|&(id, _)| id < num_drones);
+        self.rebuild_spatial_grid();
     }
 
-    /// Get all drone states
-    pub fn get_states(&self) -> Vec<PyDroneState> {
-        self.drones.iter().map(|d| PyDroneState {
-            id: d.id,
-            pos: d.pos,
-            vel: d.vel,
-            yaw: d.yaw,
-            battery: d.battery,
-            healthy: d.healthy,
-        }).collect()
+    /// Register a charge zone; returns its index
+    pub fn add_charge_zone(&mut self, center: [f32; 2], radius: f32, charge_rate: f32) -> usize {
+        let center = [self.scale_len(center[0]), self.scale_len(center[1])];
+        let radius = self.scale_len(radius);
+        self.charge_zones.push(ChargeZone { center, radius, charge_rate });
+        self.charge_zones.len() - 1
     }
 
-    /// Get simulation time
-    pub fn get_time(&self) -> f32 {
-        self.sim_time
+    /// Remove all charge zones
+    pub fn clear_charge_zones(&mut self) {
+        self.charge_zones.clear();
+    }
+
+    /// Register a no-fly cylinder standing on the ground at (x, y); returns its index
+    ///
+    /// Drones that penetrate it are pushed back onto its surface, and Goto targets inside it
+    /// stop the drone at the wall facing it.
+    pub fn add_obstacle(&mut self, x: f32, y: f32, radius: f32, height: f32) -> usize {
+        self.config.obstacles.add(Obstacle {
+            center: [self.scale_len(x), self.scale_len(y)],
+            velocity: [0.0, 0.0],
+            radius: self.scale_len(radius.max(0.0)),
+            height: self.scale_len(height.max(0.0)),
+        })
+    }
+
+    /// Register an obstacle at `pos` (x, y) moving at `vel` (m/s, horizontal); returns its index
+    ///
+    /// It keeps moving at `vel` between update_obstacle calls; collision avoidance steers
+    /// around where it will be over the avoidance look-ahead.
+    pub fn add_moving_obstacle(&mut self, pos: [f32; 2], vel: [f32; 2], radius: f32, height: f32) -> usize {
+        self.config.obstacles.add(Obstacle {
+            center: pos.map(|v| self.scale_len(v)),
+            velocity: vel.map(|v| self.scale_len(v)),
+            radius: self.scale_len(radius.max(0.0)),
+            height: self.scale_len(height.max(0.0)),
+        })
+    }
+
+    /// Move obstacle `id` to `pos` (x, y), and change its velocity if `vel` is given
+    #[pyo3(signature = (id, pos, vel=None))]
+    pub fn update_obstacle(&mut self, id: usize, pos: [f32; 2], vel: Option<[f32; 2]>) {
+        let pos = pos.map(|v| self.scale_len(v));
+        let vel = vel.map(|vel| vel.map(|v| self.scale_len(v)));
+        self.config.obstacles.update(id, pos, vel);
+    }
+
+    /// Remove all obstacles
+    pub fn clear_obstacles(&mut self) {
+        self.config.obstacles.clear();
+    }
+
+    /// Treat every obstacle as `margin` (m) wider and taller than its physical size when
+    /// pushing drones out, stopping targets at the wall and steering around it
+    pub fn set_obstacle_margin(&mut self, margin: f32) {
+        self.config.obstacles.set_safety_margin(self.scale_len(margin.max(0.0)));
+    }
+
+    /// Update battery levels (call once per second)
+    ///
+    /// Flying drones drain at `drain_rate` (%/min), or their mode's rate if one is set;
+    /// landed, idle drones inside a charge zone recharge at the zone's `charge_rate` (%/min).
+    pub fn update_batteries(&mut self, drain_rate: f32) {
+        self.drain_rate = drain_rate.max(0.0);
+        for i in 0..self.drones.len() {
+            let drain_rate = self.drain_rate_of(&self.drones[i]);
+            let drone = &mut self.drones[i];
+            if drone.mode != DroneMode::Idle {
+                drone.battery = (drone.battery - drain_rate / 60.0).max(0.0);
+            } else if drone.pos[2] < LANDED_ALTITUDE {
+                if let Some(zone) = self.charge_zones.iter().find(|z| z.contains(drone.pos)) {
+                    drone.battery = (drone.battery + zone.charge_rate / 60.0).min(100.0);
+                }
+            }
+        }
     }
+}
 
-    /// Get number of drones
-    pub fn num_drones(&self) -> usize {
-        self.drones.len()
+impl RustSwarm {
+    /// Send `ids[k]` to `slots[k]` and remember the slots as a formation
+    fn apply_formation_slots(&mut self, ids: &[usize], slots: &[[f32; 3]]) -> PyResult<()> {
+        let slots = self.spread_slots(ids, slots);
+        let slots = self.assign_slots(ids, &slots);
+        self.apply_fixed_slots(ids, &slots)
     }
 
-    /// Set speed multiplier
-    pub fn set_speed(&mut self, multiplier: f32) {
-        self.speed_multiplier = multiplier;
-        self.max_velocity = 2.0 * multiplier;
+    /// Valid, distinct drones of a formation command in the order given; None means the whole swarm
+    fn formation_ids(&self, ids: Option<Vec<usize>>) -> Vec<usize> {
+        let Some(ids) = ids else {
+            return (0..self.drones.len()).collect();
+        };
+        let mut seen = HashSet::new();
+        ids.into_iter().filter(|&id| id < self.drones.len() && seen.insert(id)).collect()
     }
 
-    /// Command: Takeoff
-    #[pyo3(signature = (ids, altitude=1.0))]
-    pub fn takeoff(&mut self, ids: Vec<usize>, altitude: f32) {
-        for &id in &ids {
-            if id < self.drones.len() {
-                let drone = &mut self.drones[id];
-                drone.target_pos = [drone.pos[0], drone.pos[1], altitude];
-                drone.target_yaw = 0.0;
-                drone.mode = DroneMode::Takeoff;
-                drone.reset_pid();
+    /// Send each drone to the slot at its own position in `slots`, ignoring the assignment mode
+    fn apply_fixed_slots(&mut self, ids: &[usize], slots: &[[f32; 3]]) -> PyResult<()> {
+        // Under the "error" policy one bad slot rejects the whole formation
+        if self.out_of_bounds_policy == OutOfBoundsPolicy::Error {
+            for slot in slots {
+                self.check_in_bounds(*slot)?;
             }
         }
-    }
 
-    /// Command: Takeoff all
-    #[pyo3(signature = (altitude=1.0))]
-    pub fn takeoff_all(&mut self, altitude: f32) {
-        let ids: Vec<usize> = (0..self.drones.len()).collect();
-        self.takeoff(ids, altitude);
+        for (&id, slot) in ids.iter().zip(slots) {
+            self.goto_target(id, slot[0], slot[1], slot[2], 0.0)?;
+        }
+        self.assign_formation_slots(ids.iter().copied());
+        Ok(())
     }
 
-    /// Command: Land
-    pub fn land(&mut self, ids: Vec<usize>) {
-        for &id in &ids {
-            if id < self.drones.len() {
-                let drone = &mut self.drones[id];
-                drone.target_pos = [drone.pos[0], drone.pos[1], 0.05];
-                drone.target_yaw = 0.0;
-                drone.mode = DroneMode::Landing;
-                drone.reset_pid();
+    /// `slots` scaled about their centroid so no two are closer than the drone diameter, when
+    /// spacing enforcement is on
+    fn spread_slots(&self, ids: &[usize], slots: &[[f32; 3]]) -> Vec<[f32; 3]> {
+        let diameter = 2.0 * ids.iter().filter_map(|&id| self.drones.get(id)).map(|d| d.radius).fold(0.0, f32::max);
+        let closest = slots.iter().enumerate()
+            .flat_map(|(i, &a)| slots[i + 1..].iter().map(move |&b| distance(a, b)))
+            .fold(f32::INFINITY, f32::min) * self.command_scale();
+        if !self.enforce_slot_spacing || closest >= diameter || closest <= 0.0 {
+            return slots.to_vec();
+        }
+
+        // A hair over, so rounding never leaves a pair just inside the diameter
+        let stretch = diameter / closest * (1.0 + 1e-4);
+        let mut centroid = [0.0f32; 3];
+        for slot in slots {
+            for (c, v) in centroid.iter_mut().zip(slot) {
+                *c += v / slots.len() as f32;
             }
         }
+        slots.iter().map(|slot| [0, 1, 2].map(|i| centroid[i] + (slot[i] - centroid[i]) * stretch)).collect()
     }
 
-    /// Command: Land all
-    pub fn land_all(&mut self) {
-        let ids: Vec<usize> = (0..self.drones.len()).collect();
-        self.land(ids);
-    }
+    /// Reorder `slots` so the i-th is the one the slot assignment mode gives `ids[i]`
+    fn assign_slots(&self, ids: &[usize], slots: &[[f32; 3]]) -> Vec<[f32; 3]> {
+        let n = ids.len().min(slots.len());
+        if self.slot_assignment == SlotAssignment::Index || n == 0 {
+            return slots.to_vec();
+        }
 
-    /// Command: Hover
-    pub fn hover(&mut self, ids: Vec<usize>) {
-        for &id in &ids {
-            if id < self.drones.len() {
-                let drone = &mut self.drones[id];
-                drone.target_pos = drone.pos;
-                drone.target_yaw = drone.yaw;
-                drone.mode = DroneMode::Hover;
+        // Travel time is distance over the drone's speed multiplier; swarm-wide speed factors
+        // are common to every drone and don't change the matching
+        let cost: Vec<Vec<f32>> = ids[..n].iter().map(|&id| {
+            let drone = &self.drones[id];
+            let speed = match self.slot_assignment {
+                SlotAssignment::Fastest => drone.speed_multiplier.max(1e-3),
+                _ => 1.0,
+            };
+            slots[..n].iter().map(|&slot| distance(drone.pos, self.scale_point(slot)) / speed).collect()
+        }).collect();
+
+        let order = if self.slot_assignment == SlotAssignment::Fastest {
+            // Smallest latest arrival: bisect over the candidate bottlenecks, forbidding slower pairs
+            let mut candidates: Vec<f32> = cost.iter().flatten().copied().collect();
+            candidates.sort_by(f32::total_cmp);
+            let forbidden = candidates[candidates.len() - 1] * n as f32 * 2.0 + 1.0;
+            let capped = |limit: f32| -> Vec<Vec<f32>> {
+                cost.iter().map(|row| row.iter().map(|&c| if c > limit { forbidden } else { c }).collect()).collect()
+            };
+            let (mut lo, mut hi) = (0, candidates.len() - 1);
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                let order = min_cost_assignment(&capped(candidates[mid]));
+                if order.iter().enumerate().all(|(row, &col)| cost[row][col] <= candidates[mid]) {
+                    hi = mid;
+                } else {
+                    lo = mid + 1;
+                }
             }
-        }
+            min_cost_assignment(&capped(candidates[lo]))
+        } else {
+            min_cost_assignment(&cost)
+        };
+
+        let mut assigned: Vec<[f32; 3]> = order.iter().map(|&col| slots[col]).collect();
+        assigned.extend_from_slice(&slots[n..]);
+        assigned
     }
 
-    /// Command: Hover all
-    pub fn hover_all(&mut self) {
-        let ids: Vec<usize> = (0..self.drones.len()).collect();
-        self.hover(ids);
+    /// Error if a command position lies outside the world bounds
+    fn check_in_bounds(&self, pos: [f32; 3]) -> PyResult<()> {
+        let target = self.scale_point(pos);
+        if self.config.clamp_target(target) != target {
+            return Err(PyValueError::new_err(format!(
+                "target ({}, {}, {}) is outside the world bounds, geofence or safe mode volume", pos[0], pos[1], pos[2]
+            )));
+        }
+        Ok(())
     }
 
-    /// Command: Goto position
-    #[pyo3(signature = (id, x, y, z, yaw=0.0))]
-    pub fn goto(&mut self, id: usize, x: f32, y: f32, z: f32, yaw: f32) {
+    /// Set a drone's Goto target (the body of `goto`, without recording undo history)
+    fn goto_target(&mut self, id: usize, x: f32, y: f32, z: f32, yaw: f32) -> PyResult<()> {
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        let requested = self.scale_point([x, y, z]);
+        let target = self.config.clamp_target(requested);
+        if target != requested {
+            match self.out_of_bounds_policy {
+                OutOfBoundsPolicy::Clamp => {}
+                OutOfBoundsPolicy::Reject => return Ok(()),
+                OutOfBoundsPolicy::Error => self.check_in_bounds([x, y, z])?,
+            }
+        }
         if id < self.drones.len() {
             let drone = &mut self.drones[id];
-            drone.target_pos = [
-                x.clamp(-10.0, 10.0),
-                y.clamp(-10.0, 10.0),
-                z.clamp(0.1, 5.0),
-            ];
+            drone.target_pos = target;
             drone.target_yaw = yaw;
-            drone.mode = DroneMode::Goto;
+            drone.set_mode(DroneMode::Goto, reset_on_change);
+            drone.formation_offset = None;
+            drone.trajectory = None;
+            drone.path = None;
+            drone.commanded_target = None;
             drone.reset_pid();
         }
+        Ok(())
     }
 
-    /// Command: Set velocity
-    #[pyo3(signature = (id, vx, vy, vz, yaw_rate=0.0))]
-    pub fn velocity(&mut self, id: usize, vx: f32, vy: f32, vz: f32, yaw_rate: f32) {
-        if id < self.drones.len() {
-            let drone = &mut self.drones[id];
-            let max_v = 2.0;
-            drone.target_vel = [
-                vx.clamp(-max_v, max_v),
-                vy.clamp(-max_v, max_v),
-                vz.clamp(-max_v, max_v),
-            ];
-            drone.yaw_rate = yaw_rate.clamp(-PI, PI);
-            drone.mode = DroneMode::Velocity;
+    /// Save the current target set so the next command can be undone
+    fn push_undo(&mut self) {
+        if self.undo_depth == 0 {
+            return;
+        }
+        if self.undo_history.len() == self.undo_depth {
+            self.undo_history.pop_front();
         }
+        self.undo_history.push_back(self.drones.iter().map(TargetSnapshot::from_drone).collect());
     }
 
-    /// Command: Formation - Line
-    #[pyo3(signature = (center, spacing=1.0, axis="x"))]
-    pub fn formation_line(&mut self, center: [f32; 3], spacing: f32, axis: &str) {
-        let n = self.drones.len();
-        let start_offset = -((n - 1) as f32) * spacing / 2.0;
-
-        for i in 0..n {
-            let offset = start_offset + i as f32 * spacing;
-            let (x, y) = match axis {
-                "x" => (center[0] + offset, center[1]),
-                "y" => (center[0], center[1] + offset),
-                _ => (center[0] + offset, center[1]),
-            };
-            self.goto(i, x, y, center[2], 0.0);
+    /// Position and velocity as reported by a drone's (possibly noisy) sensors
+    fn sensed_pos_vel(&self, drone: &Drone) -> ([f32; 3], [f32; 3]) {
+        let [pos_std, vel_std] = drone.sensor_noise.unwrap_or(self.sensor_noise);
+        let mut pos = drone.pos;
+        let mut vel = drone.vel;
+        if pos_std <= 0.0 && vel_std <= 0.0 {
+            return (pos, vel);
         }
-    }
 
-    /// Command: Formation - Circle
-    #[pyo3(signature = (center, radius=1.5))]
-    pub fn formation_circle(&mut self, center: [f32; 3], radius: f32) {
-        let n = self.drones.len();
-        for i in 0..n {
-            let angle = 2.0 * PI * i as f32 / n as f32;
-            let x = center[0] + radius * angle.cos();
-            let y = center[1] + radius * angle.sin();
-            self.goto(i, x, y, center[2], 0.0);
+        // One independent sample per (seed, drone, step, channel)
+        let base = splitmix64(self.noise_seed ^ splitmix64(drone.id as u64 ^ splitmix64(self.step_count)));
+        for i in 0..3 {
+            pos[i] += pos_std * hash_gaussian(base.wrapping_add(i as u64));
+            vel[i] += vel_std * hash_gaussian(base.wrapping_add(3 + i as u64));
         }
+        (pos, vel)
     }
 
-    /// Command: Formation - Grid
-    #[pyo3(signature = (center, spacing=1.0))]
-    pub fn formation_grid(&mut self, center: [f32; 3], spacing: f32) {
-        let n = self.drones.len();
-        let cols = (n as f32).sqrt().ceil() as usize;
-        let rows = (n + cols - 1) / cols;
+    /// Map a command position from scenario coordinates into the scaled scene
+    fn scale_point(&self, p: [f32; 3]) -> [f32; 3] {
+        p.map(|v| v * self.command_scale())
+    }
 
-        let start_x = -((cols - 1) as f32) * spacing / 2.0;
-        let start_y = -((rows - 1) as f32) * spacing / 2.0;
+    /// Map a command length (spacing, radius, altitude) into the scaled scene
+    fn scale_len(&self, v: f32) -> f32 {
+        v * self.command_scale()
+    }
 
-        for i in 0..n {
-            let row = i / cols;
-            let col = i % cols;
-            let x = center[0] + start_x + col as f32 * spacing;
-            let y = center[1] + start_y + row as f32 * spacing;
-            self.goto(i, x, y, center[2], 0.0);
-        }
+    /// Meters per command unit: the scene scale times the API length unit
+    fn command_scale(&self) -> f32 {
+        self.scene_scale * self.length_unit
     }
 
-    /// Command: Formation - V shape
-    #[pyo3(signature = (center, spacing=1.0))]
-    pub fn formation_v(&mut self, center: [f32; 3], spacing: f32) {
-        let n = self.drones.len();
-        let angle: f32 = PI / 6.0;  // 30 degrees
+    /// Convert an internal length (m) into the API length unit
+    fn report_len(&self, v: f32) -> f32 {
+        v / self.length_unit
+    }
 
-        // Leader at front
-        if n > 0 {
-            self.goto(0, center[0], center[1], center[2], 0.0);
-        }
+    /// Convert an internal position or velocity into the API length unit
+    fn report_point(&self, p: [f32; 3]) -> [f32; 3] {
+        p.map(|v| self.report_len(v))
+    }
 
-        // Followers in V behind
-        for i in 1..n {
-            let side = if i % 2 == 0 { 1.0 } else { -1.0 };
-            let offset_back = ((i + 1) / 2) as f32;
+    /// Scale the world bounds into the scene and re-bin the spatial grid over them
+    fn apply_world_bounds(&mut self) {
+        let [min, max] = self.world_bounds;
+        self.config.world_min = min.map(|v| v * self.scene_scale);
+        self.config.world_max = max.map(|v| v * self.scene_scale);
+        self.spatial_grid = SpatialGrid::new(self.spatial_grid.cell_size(), self.config.world_min, self.config.world_max);
+        self.rebuild_spatial_grid();
+    }
 
-            let x = center[0] - offset_back * spacing * angle.cos();
-            let y = center[1] + side * offset_back * spacing * angle.sin();
-            self.goto(i, x, y, center[2], 0.0);
+    /// Switch `ids` to Hover at their current pose (the body of `hover`, without logging)
+    fn hold_position(&mut self, ids: &[usize]) {
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        for &id in ids {
+            if id < self.drones.len() {
+                let drone = &mut self.drones[id];
+                drone.target_pos = drone.pos;
+                drone.target_yaw = drone.yaw;
+                drone.set_mode(DroneMode::Hover, reset_on_change);
+            }
         }
     }
 
-    /// Command: Waypoint - all drones go to formation around point
-    #[pyo3(signature = (x, y, z))]
-    pub fn waypoint(&mut self, x: f32, y: f32, z: f32) {
-        let center = [x, y, z];
-        let radius = 0.8;
-
-        if self.drones.len() == 1 {
-            self.goto(0, x, y, z, 0.0);
-        } else {
-            self.formation_circle(center, radius);
+    /// Record a received command when command logging is on
+    fn log_command(&mut self, name: &str, args: &[f32]) {
+        if let Some(log) = &mut self.command_log {
+            log.push((self.sim_time, name.to_string(), args.to_vec()));
         }
     }
 
-    /// Command: Monitor mode - orbital surveillance
-    #[pyo3(signature = (x, y, z))]
-    pub fn monitor(&mut self, x: f32, y: f32, z: f32) {
-        self.monitor_center = Some([x, y, z]);
+    /// Spread `ids` over orbits around the monitor center with varied radii and altitudes
+    fn assign_monitor_slots(&mut self, ids: &[usize], z: f32) {
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        let scale = self.command_scale();
+        let n = ids.len();
 
-        let n = self.drones.len();
-        for i in 0..n {
-            let drone = &mut self.drones[i];
+        for (i, &id) in ids.iter().enumerate() {
+            let Some(drone) = self.drones.get_mut(id) else {
+                continue;
+            };
 
             // Vary radius: 1.0 to 3.0
             let radius_factor = (i % 3) as f32 / 2.0;
-            drone.monitor_radius = 1.0 + radius_factor * 2.0;
+            drone.monitor_radius = (1.0 + radius_factor * 2.0) * scale;
 
             // Vary altitude
             let altitude_layers = n.min(5);
             let layer = i % altitude_layers;
             let altitude_offset = (layer as f32 - altitude_layers as f32 / 2.0) * 0.6;
-            drone.monitor_altitude = (z + altitude_offset).max(0.5);
+            drone.monitor_altitude = (z + altitude_offset).max(0.5) * scale;
 
             // Starting angle
             drone.monitor_angle = 2.0 * PI * i as f32 / n as f32;
 
-            drone.mode = DroneMode::Monitor;
+            drone.set_mode(DroneMode::Monitor, reset_on_change);
+            drone.formation_offset = None;
             drone.reset_pid();
         }
     }
 
-    /// Command: Reset simulation
-    pub fn reset(&mut self) {
-        let num_drones = self.drones.len();
-        let grid_size = (num_drones as f32).sqrt().ceil() as usize;
-        let spacing = 0.5;
+    /// Record the current sim time as the mode entry time of drones whose mode changed
+    fn stamp_mode_changes(&mut self) {
+        for drone in self.drones.iter_mut().filter(|d| d.mode_changed) {
+            drone.mode_entered_time = self.sim_time;
+            drone.mode_changed = false;
+        }
+    }
 
-        for i in 0..num_drones {
-            let row = i / grid_size;
-            let col = i % grid_size;
-            let x = (col as f32 - grid_size as f32 / 2.0) * spacing;
-            let y = (row as f32 - grid_size as f32 / 2.0) * spacing;
+    /// Re-index drone positions in the spatial hash
+    fn rebuild_spatial_grid(&mut self) {
+        self.spatial_grid.rebuild(self.drones.iter().map(|d| (d.id, d.pos)));
+    }
 
-            let drone = &mut self.drones[i];
-            drone.pos = [x, y, 0.1];
-            drone.vel = [0.0, 0.0, 0.0];
-            drone.yaw = 0.0;
-            drone.yaw_rate = 0.0;
-            drone.mode = DroneMode::Idle;
-            drone.battery = 100.0;
-            drone.healthy = true;
-            drone.reset_pid();
+    /// Mark drones that collided or hit the ground this step as crashed and report them
+    fn detect_crashes(&mut self) {
+        let Some([crash_distance, _]) = self.config.crash_detection else {
+            return;
+        };
+
+        // Each pair once (lower id first), skipping pairs already crashed or resting on the ground
+        let max_radius = self.drones.iter().map(|d| d.radius).fold(0.0, f32::max);
+        let grid = &self.spatial_grid;
+        let drones = &self.drones;
+        let mut collisions: Vec<(usize, usize)> = drones.par_iter()
+            .flat_map_iter(|a| {
+                grid.query_radius(a.pos, crash_distance.max(a.radius + max_radius)).into_iter()
+                    .filter(move |&j| j > a.id)
+                    .map(move |j| (a, &drones[j]))
+            })
+            .filter(|(a, b)| !(a.crashed && b.crashed))
+            .filter(|(a, b)| a.pos[2] >= LANDED_ALTITUDE || b.pos[2] >= LANDED_ALTITUDE)
+            .filter(|(a, b)| distance(a.pos, b.pos) < crash_distance.max(a.radius + b.radius))
+            .map(|(a, b)| (a.id, b.id))
+            .collect();
+        collisions.sort_unstable();
+
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        let crash = |drone: &mut Drone| {
+            drone.crashed = true;
+            drone.healthy = false;
+            drone.set_mode(DroneMode::Idle, reset_on_change);
+            drone.formation_offset = None;
+            drone.trajectory = None;
+        };
+        for (a, b) in collisions {
+            crash(&mut self.drones[a]);
+            crash(&mut self.drones[b]);
+            self.push_event("collision", vec![a, b]);
+        }
+        for id in 0..self.drones.len() {
+            if std::mem::take(&mut self.drones[id].ground_impact) {
+                crash(&mut self.drones[id]);
+                self.push_event("ground_impact", vec![id]);
+            }
         }
+    }
 
-        self.sim_time = 0.0;
-        self.monitor_center = None;
+    /// Record an event at the current sim time under the event buffer policy
+    fn push_event(&mut self, kind: &str, ids: Vec<usize>) {
+        if let Some((capacity, policy)) = self.event_buffer {
+            if self.events.len() >= capacity {
+                match policy {
+                    EventBufferPolicy::DropOldest => {
+                        self.events.pop_front();
+                    }
+                    EventBufferPolicy::DropNewest => return,
+                    EventBufferPolicy::Error => {
+                        self.dropped_events += 1;
+                        return;
+                    }
+                }
+            }
+        }
+        self.events.push_back((self.sim_time, kind.to_string(), ids));
     }
 
-    /// Respawn with new drone count
-    pub fn respawn(&mut self, num_drones: usize) {
-        let grid_size = (num_drones as f32).sqrt().ceil() as usize;
-        let spacing = 0.5;
+    /// Append the current state to an active recording on its interval
+    fn record_frame(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            if recording.active && self.step_count.is_multiple_of(recording.interval) {
+                recording.frames.push(Frame::capture(&self.drones, self.sim_time));
+            }
+        }
+    }
 
-        self.drones.clear();
-        for i in 0..num_drones {
-            let row = i / grid_size;
-            let col = i % grid_size;
-            let x = (col as f32 - grid_size as f32 / 2.0) * spacing;
-            let y = (row as f32 - grid_size as f32 / 2.0) * spacing;
-            let z = 0.1;
-            self.drones.push(Drone::new(i, x, y, z));
+    /// Battery drain (%/min) of `drone` in its current mode; 0 while idle
+    fn drain_rate_of(&self, drone: &Drone) -> f32 {
+        if drone.mode == DroneMode::Idle {
+            return 0.0;
         }
+        self.mode_drain_rates.get(&drone.mode).copied().unwrap_or(self.drain_rate)
+    }
 
-        self.sim_time = 0.0;
-        self.monitor_center = None;
+    /// Trigger, advance and enforce the low-battery failsafe (set_failsafe)
+    fn apply_failsafe(&mut self) {
+        let Some([return_home_pct, land_pct]) = self.failsafe else {
+            return;
+        };
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+
+        for drone in &mut self.drones {
+            let battery = self.discharge_curve.reported(drone.battery);
+            if battery >= return_home_pct {
+                drone.failsafe_stage = None;
+                continue;
+            }
+            let airborne = drone.mode != DroneMode::Idle;
+
+            // Escalate: untriggered airborne drones start returning; anything below land_pct lands
+            if battery < land_pct && (airborne || drone.failsafe_stage.is_some()) {
+                drone.failsafe_stage = Some(FailsafeStage::Land);
+            } else if drone.failsafe_stage.is_none() && airborne {
+                let altitude = drone.pos[2].max(MIN_TARGET_ALTITUDE);
+                drone.failsafe_stage = Some(FailsafeStage::ReturnHome {
+                    target: self.config.clamp_target([drone.home[0], drone.home[1], altitude]),
+                });
+            }
+
+            // Re-impose the stage over whatever was commanded since; arrival lands on the home pad
+            let order = match drone.failsafe_stage {
+                Some(FailsafeStage::ReturnHome { target })
+                    if (drone.pos[0] - target[0]).hypot(drone.pos[1] - target[1]) < FAILSAFE_HOME_RADIUS =>
+                {
+                    drone.failsafe_stage = Some(FailsafeStage::Land);
+                    Some(([target[0], target[1], 0.05], DroneMode::Landing))
+                }
+                Some(FailsafeStage::ReturnHome { target }) => {
+                    (drone.mode != DroneMode::Goto || drone.target_pos != target).then_some((target, DroneMode::Goto))
+                }
+                Some(FailsafeStage::Land) => {
+                    (!matches!(drone.mode, DroneMode::Landing | DroneMode::Idle))
+                        .then_some(([drone.pos[0], drone.pos[1], 0.05], DroneMode::Landing))
+                }
+                None => None,
+            };
+            if let Some((target, mode)) = order {
+                drone.target_pos = target;
+                if mode == DroneMode::Landing {
+                    drone.target_yaw = drone.yaw;
+                }
+                drone.set_mode(mode, reset_on_change);
+                drone.formation_offset = None;
+                drone.trajectory = None;
+                drone.path = None;
+                drone.commanded_target = None;
+                drone.reset_pid();
+            }
+        }
     }
 
-    /// Update battery levels (call once per second)
-    pub fn update_batteries(&mut self, drain_rate: f32) {
+    /// Start and advance the climb-then-land sequence of faulted drones (set_fault_response)
+    fn respond_to_faults(&mut self) {
+        let Some(response) = self.fault_response else {
+            return;
+        };
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+
         for drone in &mut self.drones {
-            if drone.mode != DroneMode::Idle {
-                drone.battery = (drone.battery - drain_rate / 60.0).max(0.0);
+            if drone.mode == DroneMode::Idle {
+                continue;
+            }
+            let land = match drone.fault_phase {
+                None if drone.battery <= 0.0 || drone.gps_drift_rate.is_some() => {
+                    if response.climb_first && drone.pos[2] < response.safe_altitude - FAULT_CLIMB_TOLERANCE {
+                        drone.target_pos = [drone.pos[0], drone.pos[1], response.safe_altitude];
+                        drone.set_mode(DroneMode::Goto, reset_on_change);
+                        drone.formation_offset = None;
+                        drone.trajectory = None;
+                        drone.path = None;
+                        drone.commanded_target = None;
+                        drone.reset_pid();
+                        drone.fault_phase = Some(FaultPhase::Climb);
+                        false
+                    } else {
+                        true
+                    }
+                }
+                Some(FaultPhase::Climb) => {
+                    if drone.mode != DroneMode::Goto || drone.target_pos[2] != response.safe_altitude {
+                        drone.fault_phase = Some(FaultPhase::Descend);
+                    }
+                    drone.fault_phase == Some(FaultPhase::Climb)
+                        && drone.pos[2] >= response.safe_altitude - FAULT_CLIMB_TOLERANCE
+                }
+                _ => false,
+            };
+
+            if land {
+                drone.target_pos = [drone.pos[0], drone.pos[1], 0.05];
+                drone.target_yaw = drone.yaw;
+                drone.set_mode(DroneMode::Landing, reset_on_change);
+                drone.formation_offset = None;
+                drone.reset_pid();
+                drone.fault_phase = Some(FaultPhase::Descend);
+            }
+        }
+    }
+
+    /// Hold panic-landing drones that have an unlanded drone beneath them (panic_land)
+    fn sequence_panic_landing(&mut self) {
+        if !self.panic_landing {
+            return;
+        }
+        let descending: Vec<[f32; 3]> = self.drones.iter()
+            .filter(|d| d.mode == DroneMode::Landing)
+            .map(|d| d.pos)
+            .collect();
+        if descending.is_empty() {
+            self.panic_landing = false;
+            return;
+        }
+
+        for drone in self.drones.iter_mut().filter(|d| d.mode == DroneMode::Landing) {
+            let blocked = descending.iter().any(|other| {
+                other[2] < drone.pos[2]
+                    && self.config.hypot(other[0] - drone.pos[0], other[1] - drone.pos[1]) < PANIC_LAND_CLEARANCE
+            });
+            if !blocked {
+                drone.target_pos[2] = 0.05;
+            } else if drone.target_pos[2] < drone.pos[2] - 0.1 {
+                // Freeze at the altitude where the conflict was first seen
+                drone.target_pos[2] = drone.pos[2];
+            }
+        }
+    }
+
+    /// Swap low-battery orbiters out for charged reserves (monitor_with_rotation)
+    fn rotate_monitor_drones(&mut self) {
+        let Some(mut rotation) = self.monitor_rotation.take() else {
+            return;
+        };
+        let reset_on_change = self.config.reset_pid_on_mode_change;
+        let threshold = rotation.low_battery_threshold;
+        let curve = self.discharge_curve;
+
+        // Send low orbiters down to charge, leaving their slot vacant
+        for &id in &rotation.pool {
+            let drone = &mut self.drones[id];
+            if drone.mode != DroneMode::Monitor || curve.reported(drone.battery) >= threshold {
+                continue;
+            }
+            rotation.vacancies.push(OrbitVacancy {
+                radius: drone.monitor_radius,
+                altitude: drone.monitor_altitude,
+                angle: drone.monitor_angle,
+                vacated_at: self.sim_time,
+            });
+
+            let nearest_zone = self.charge_zones.iter().min_by(|a, b| {
+                let da = self.config.hypot(a.center[0] - drone.pos[0], a.center[1] - drone.pos[1]);
+                let db = self.config.hypot(b.center[0] - drone.pos[0], b.center[1] - drone.pos[1]);
+                da.total_cmp(&db)
+            });
+            let pad = nearest_zone.map_or([drone.pos[0], drone.pos[1]], |zone| zone.center);
+            drone.target_pos = [pad[0], pad[1], 0.05];
+            drone.set_mode(DroneMode::Landing, reset_on_change);
+            drone.reset_pid();
+        }
+
+        // Fill vacancies with the best-charged ready reserves, phased to where the slot is now
+        while let Some(&vacancy) = rotation.vacancies.first() {
+            let reserve = rotation.pool.iter().copied()
+                .filter(|&id| {
+                    let drone = &self.drones[id];
+                    matches!(drone.mode, DroneMode::Idle | DroneMode::Hover)
+                        && curve.reported(drone.battery) >= threshold + RESERVE_READY_MARGIN
+                })
+                .max_by(|&a, &b| self.drones[a].battery.total_cmp(&self.drones[b].battery));
+            let Some(id) = reserve else {
+                break;
+            };
+
+            let drone = &mut self.drones[id];
+            let elapsed = self.sim_time - vacancy.vacated_at;
+            drone.monitor_radius = vacancy.radius;
+            drone.monitor_altitude = vacancy.altitude;
+            drone.monitor_angle = (vacancy.angle + self.monitor_orbit_speed * elapsed).rem_euclid(2.0 * PI);
+            drone.set_mode(DroneMode::Monitor, reset_on_change);
+            drone.formation_offset = None;
+            drone.reset_pid();
+            rotation.vacancies.remove(0);
+        }
+
+        self.monitor_rotation = Some(rotation);
+    }
+
+    /// Re-target drones for the active continuous maneuver
+    fn apply_maneuver(&mut self) {
+        let Some(maneuver) = &self.maneuver else {
+            return;
+        };
+
+        match maneuver {
+            &Maneuver::Breathe { center, min_radius, max_radius, period, start_time } => {
+                // Starts fully expanded, contracts to min_radius at half period
+                let phase = 2.0 * PI * (self.sim_time - start_time) / period;
+                let radius = min_radius + (max_radius - min_radius) * 0.5 * (1.0 + self.config.cos(phase));
+
+                let n = self.drones.len();
+                for (i, drone) in self.drones.iter_mut().enumerate() {
+                    let angle = 2.0 * PI * i as f32 / n as f32;
+                    drone.follow_target([
+                        center[0] + radius * self.config.cos(angle),
+                        center[1] + radius * self.config.sin(angle),
+                        center[2],
+                    ], &self.config);
+                }
+            }
+
+            Maneuver::Patrol { vertices, altitude, speed, start_time } => {
+                let perimeter = perimeter_length(vertices);
+                let travelled = speed * (self.sim_time - start_time);
+
+                let n = self.drones.len();
+                for (i, drone) in self.drones.iter_mut().enumerate() {
+                    let s = (perimeter * i as f32 / n as f32 + travelled).rem_euclid(perimeter);
+                    let p = point_on_perimeter(vertices, s);
+                    drone.follow_target([p[0], p[1], *altitude], &self.config);
+                }
+            }
+
+            Maneuver::Wave { direction, wavelength, amplitude, speed, base_altitude, start_time, anchors } => {
+                let k = 2.0 * PI / wavelength;
+                let t = self.sim_time - start_time;
+
+                for (drone, anchor) in self.drones.iter_mut().zip(anchors) {
+                    let along = direction[0] * anchor[0] + direction[1] * anchor[1];
+                    let z = base_altitude + amplitude * self.config.sin(k * (along - speed * t));
+                    drone.follow_target([anchor[0], anchor[1], z], &self.config);
+                }
+            }
+
+            Maneuver::Playback { frames, fps, start_time } => {
+                // Holds the final frame once the recording runs out
+                let position = ((self.sim_time - start_time) * fps).max(0.0);
+                let last = frames.len() - 1;
+                let index = (position.floor() as usize).min(last);
+                let next = (index + 1).min(last);
+                let t = if index == last { 0.0 } else { position - index as f32 };
+
+                for (id, drone) in self.drones.iter_mut().enumerate() {
+                    let (Some(a), Some(b)) = (frames[index].get(id), frames[next].get(id)) else {
+                        continue;
+                    };
+                    drone.follow_target([
+                        a[0] + t * (b[0] - a[0]),
+                        a[1] + t * (b[1] - a[1]),
+                        a[2] + t * (b[2] - a[2]),
+                    ], &self.config);
+                }
+            }
+
+            &Maneuver::Cruise { velocity } => {
+                let step = velocity.map(|v| v * self.physics_dt);
+                for drone in self.drones.iter_mut().filter(|d| matches!(d.mode, DroneMode::Goto | DroneMode::Hover)) {
+                    let target = [0, 1, 2].map(|i| drone.target_pos[i] + step[i]);
+                    drone.target_pos = self.config.clamp_target(target);
+                    for (a, s) in drone.formation_anchor.iter_mut().zip(step) {
+                        *a += s;
+                    }
+                }
+            }
+
+            Maneuver::Morph { start, end, duration, easing, start_time } => {
+                let t = if *duration > 0.0 { (self.sim_time - start_time) / duration } else { 1.0 };
+                let progress = easing.apply(t);
+                for (drone, (a, b)) in self.drones.iter_mut().zip(start.iter().zip(end)) {
+                    // The end slot itself once done, so no rounding is left in the final target
+                    let target = if t >= 1.0 { *b } else { [0, 1, 2].map(|i| a[i] + progress * (b[i] - a[i])) };
+                    drone.follow_target(target, &self.config);
+                }
+                if t >= 1.0 {
+                    // Done: hold the new formation like a formation command would
+                    self.maneuver = None;
+                    self.assign_formation_slots(0..self.drones.len());
+                }
+            }
+        }
+    }
+
+    /// Record each drone's current target as a slot relative to the group's target centroid
+    fn assign_formation_slots(&mut self, ids: impl IntoIterator<Item = usize>) {
+        let ids: Vec<usize> = ids.into_iter().filter(|&id| id < self.drones.len()).collect();
+        if ids.is_empty() {
+            return;
+        }
+
+        let mut anchor = [0.0f32; 3];
+        for &id in &ids {
+            for (a, t) in anchor.iter_mut().zip(self.drones[id].target_pos) {
+                *a += t / ids.len() as f32;
             }
         }
+
+        for &id in &ids {
+            let drone = &mut self.drones[id];
+            drone.formation_anchor = anchor;
+            drone.formation_offset = Some([
+                drone.target_pos[0] - anchor[0],
+                drone.target_pos[1] - anchor[1],
+                drone.target_pos[2] - anchor[2],
+            ]);
+        }
     }
 }
 
@@ -570,5 +5215,8 @@ impl RustSwarm {
 fn drone_physics(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<RustSwarm>()?;
     m.add_class::<PyDroneState>()?;
+    m.add_class::<StateDeltaDecoder>()?;
+    m.add_class::<PyBenchmarkResult>()?;
+    m.add_class::<PyDroneTelemetry>()?;
     Ok(())
 }
@@ -0,0 +1,174 @@
+//! Vertical-cylinder obstacles, moved between steps and shared read-only by the parallel step
+
+/// Cylinder standing on the ground at `center` (x, y), sliding at `velocity` (m/s)
+#[derive(Clone, Copy, Debug)]
+pub struct Obstacle {
+    pub center: [f32; 2],
+    pub velocity: [f32; 2],
+    pub radius: f32,
+    pub height: f32,
+}
+
+impl Obstacle {
+    /// Horizontal offset of `pos` from the axis and its length
+    fn offset(&self, pos: [f32; 3]) -> ([f32; 2], f32) {
+        let offset = [pos[0] - self.center[0], pos[1] - self.center[1]];
+        (offset, (offset[0] * offset[0] + offset[1] * offset[1]).sqrt())
+    }
+
+    /// Whether `pos` lies inside the cylinder grown by `margin` on the side and top
+    pub fn contains(&self, pos: [f32; 3], margin: f32) -> bool {
+        pos[2] < self.height + margin && self.offset(pos).1 < self.radius + margin
+    }
+
+    /// Point on the grown cylinder's side at `pos`'s altitude, in the direction of `toward`
+    fn side_point(&self, pos: [f32; 3], toward: [f32; 3], margin: f32) -> [f32; 3] {
+        let (offset, dist) = self.offset(toward);
+        let dir = if dist > 1e-6 { [offset[0] / dist, offset[1] / dist] } else { [1.0, 0.0] };
+        let r = self.radius + margin;
+        [self.center[0] + dir[0] * r, self.center[1] + dir[1] * r, pos[2]]
+    }
+}
+
+/// Registered obstacles
+///
+/// Every query grows the obstacles by the safety margin on top of the caller's own margin, so
+/// drones keep that much extra clearance from the physical cylinders.
+#[derive(Clone, Default)]
+pub struct ObstacleSet {
+    obstacles: Vec<Obstacle>,
+    safety_margin: f32,
+}
+
+impl ObstacleSet {
+    /// Register an obstacle; returns its index
+    pub fn add(&mut self, obstacle: Obstacle) -> usize {
+        self.obstacles.push(obstacle);
+        self.obstacles.len() - 1
+    }
+
+    pub fn clear(&mut self) {
+        self.obstacles.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Obstacle> {
+        self.obstacles.iter()
+    }
+
+    pub fn set_safety_margin(&mut self, margin: f32) {
+        self.safety_margin = margin;
+    }
+
+    /// Move obstacle `index` to `center`, and set its velocity if given; false for a bad index
+    pub fn update(&mut self, index: usize, center: [f32; 2], velocity: Option<[f32; 2]>) -> bool {
+        let Some(obstacle) = self.obstacles.get_mut(index) else {
+            return false;
+        };
+        obstacle.center = center;
+        if let Some(velocity) = velocity {
+            obstacle.velocity = velocity;
+        }
+        true
+    }
+
+    /// Carry every obstacle along its velocity for `dt` seconds
+    pub fn advance(&mut self, dt: f32) {
+        for obstacle in &mut self.obstacles {
+            obstacle.center[0] += obstacle.velocity[0] * dt;
+            obstacle.center[1] += obstacle.velocity[1] * dt;
+        }
+    }
+
+    /// Whether `pos` is inside any obstacle grown by `margin`
+    pub fn contains(&self, pos: [f32; 3], margin: f32) -> bool {
+        let margin = margin + self.safety_margin;
+        self.obstacles.iter().any(|o| o.contains(pos, margin))
+    }
+
+    /// Move `pos` out of every obstacle it penetrates onto the nearest surface (side or top)
+    /// and remove the velocity component pointing back in
+    pub fn push_out(&self, pos: &mut [f32; 3], vel: &mut [f32; 3], margin: f32) {
+        let margin = margin + self.safety_margin;
+        for obstacle in &self.obstacles {
+            if !obstacle.contains(*pos, margin) {
+                continue;
+            }
+            let (offset, dist) = obstacle.offset(*pos);
+            let side_depth = obstacle.radius + margin - dist;
+            let top_depth = obstacle.height + margin - pos[2];
+            if top_depth < side_depth {
+                pos[2] = obstacle.height + margin;
+                vel[2] = vel[2].max(0.0);
+            } else {
+                *pos = obstacle.side_point(*pos, *pos, margin);
+                let normal = if dist > 1e-6 { [offset[0] / dist, offset[1] / dist] } else { [1.0, 0.0] };
+                let inward = vel[0] * normal[0] + vel[1] * normal[1];
+                if inward < 0.0 {
+                    vel[0] -= inward * normal[0];
+                    vel[1] -= inward * normal[1];
+                }
+            }
+        }
+    }
+
+    /// Horizontal push away from obstacles a drone at `pos` moving at `vel` will pass within
+    /// `radius` (m) of the surface (grown by `margin`) in the next `lookahead` seconds
+    ///
+    /// Each obstacle is judged at the point of closest approach of the relative motion, so the
+    /// drone sidesteps to the side the obstacle will miss it on; the push is `strength` times
+    /// (1 - clearance / radius), like drone-to-drone avoidance. Drones above an obstacle ignore it.
+    pub fn avoidance_velocity(&self, pos: [f32; 3], vel: [f32; 3], margin: f32, radius: f32, strength: f32, lookahead: f32) -> [f32; 3] {
+        let margin = margin + self.safety_margin;
+        let mut push = [0.0f32; 3];
+        for obstacle in &self.obstacles {
+            if pos[2] >= obstacle.height + margin {
+                continue;
+            }
+            let (offset, _) = obstacle.offset(pos);
+            let rel_vel = [vel[0] - obstacle.velocity[0], vel[1] - obstacle.velocity[1]];
+            let speed_sq = rel_vel[0] * rel_vel[0] + rel_vel[1] * rel_vel[1];
+            let t = if speed_sq > 1e-9 {
+                (-(offset[0] * rel_vel[0] + offset[1] * rel_vel[1]) / speed_sq).clamp(0.0, lookahead)
+            } else {
+                0.0
+            };
+            let closest = [offset[0] + rel_vel[0] * t, offset[1] + rel_vel[1] * t];
+            let dist = closest[0].hypot(closest[1]);
+            let clearance = dist - obstacle.radius - margin;
+            if clearance >= radius {
+                continue;
+            }
+            // Dead-center approach: sidestep to the right of the relative motion
+            let away = if dist > 1e-6 {
+                [closest[0] / dist, closest[1] / dist]
+            } else if speed_sq > 1e-9 {
+                let speed = speed_sq.sqrt();
+                [rel_vel[1] / speed, -rel_vel[0] / speed]
+            } else {
+                [1.0, 0.0]
+            };
+            let magnitude = strength * (1.0 - clearance.max(0.0) / radius);
+            push[0] += magnitude * away[0];
+            push[1] += magnitude * away[1];
+        }
+        push
+    }
+
+    /// A `target` inside an obstacle moved to its boundary on the side facing `from` (on top
+    /// if `from` is above it), so a drone flying there stops at the wall
+    pub fn block_target(&self, from: [f32; 3], target: [f32; 3], margin: f32) -> [f32; 3] {
+        let margin = margin + self.safety_margin;
+        let mut target = target;
+        for obstacle in &self.obstacles {
+            if !obstacle.contains(target, margin) {
+                continue;
+            }
+            target = if from[2] >= obstacle.height + margin {
+                [target[0], target[1], obstacle.height + margin]
+            } else {
+                obstacle.side_point(target, from, margin)
+            };
+        }
+        target
+    }
+}
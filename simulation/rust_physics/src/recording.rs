@@ -0,0 +1,63 @@
+//! Swarm recordings for random-access replay (seek_frame)
+
+use crate::{Drone, DroneMode};
+
+/// Recorded state of one drone
+#[derive(Clone, Copy)]
+struct DroneFrame {
+    pos: [f32; 3],
+    vel: [f32; 3],
+    yaw: f32,
+    yaw_rate: f32,
+    battery: f32,
+    healthy: bool,
+    mode: DroneMode,
+}
+
+/// Every drone's recorded state at one sim time
+#[derive(Clone)]
+pub struct Frame {
+    pub sim_time: f32,
+    drones: Vec<DroneFrame>,
+}
+
+impl Frame {
+    pub fn capture(drones: &[Drone], sim_time: f32) -> Self {
+        let drones = drones.iter().map(|d| DroneFrame {
+            pos: d.pos,
+            vel: d.vel,
+            yaw: d.yaw,
+            yaw_rate: d.yaw_rate,
+            battery: d.battery,
+            healthy: d.healthy,
+            mode: d.mode,
+        }).collect();
+        Self { sim_time, drones }
+    }
+
+    pub fn drone_count(&self) -> usize {
+        self.drones.len()
+    }
+
+    /// Overwrite the recorded fields of `drones`; targets and controller state are left as they are
+    pub fn apply(&self, drones: &mut [Drone]) {
+        for (drone, frame) in drones.iter_mut().zip(&self.drones) {
+            drone.pos = frame.pos;
+            drone.vel = frame.vel;
+            drone.yaw = frame.yaw;
+            drone.yaw_rate = frame.yaw_rate;
+            drone.battery = frame.battery;
+            drone.healthy = frame.healthy;
+            drone.mode_changed |= drone.mode != frame.mode;
+            drone.mode = frame.mode;
+        }
+    }
+}
+
+/// Frames captured every `interval` steps while `active`
+#[derive(Clone)]
+pub struct Recording {
+    pub interval: u64,
+    pub active: bool,
+    pub frames: Vec<Frame>,
+}
@@ -0,0 +1,136 @@
+//! Uniform spatial hash over the world bounds for neighbor queries
+
+use std::collections::HashMap;
+
+use crate::distance;
+
+/// Integer cell coordinates
+type Cell = [i32; 3];
+
+/// Uniform grid of `cell_size` cubes covering the world bounds
+///
+/// Points outside the bounds are filed in the nearest edge cell, so queries stay exact;
+/// only occupied cells are stored, so a swarm packed into one cell costs a single entry.
+#[derive(Clone)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    min: [f32; 3],
+    max_cell: Cell,
+    cells: HashMap<Cell, Vec<(usize, [f32; 3])>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32, world_min: [f32; 3], world_max: [f32; 3]) -> Self {
+        let cell_size = cell_size.max(1e-3);
+        let max_cell = [0, 1, 2].map(|i| (((world_max[i] - world_min[i]) / cell_size).ceil() as i32 - 1).max(0));
+        Self { cell_size, min: world_min, max_cell, cells: HashMap::new() }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Replace the contents with `points` as (id, position)
+    pub fn rebuild(&mut self, points: impl IntoIterator<Item = (usize, [f32; 3])>) {
+        self.cells.clear();
+        for (id, pos) in points {
+            self.cells.entry(self.cell_of(pos)).or_default().push((id, pos));
+        }
+    }
+
+    /// Ids within `radius` of `center`, ascending
+    pub fn query_radius(&self, center: [f32; 3], radius: f32) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.within(center, radius).map(|(id, _)| id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Up to `k` ids nearest to `center` (skipping `exclude`), closest first
+    pub fn k_nearest(&self, center: [f32; 3], k: usize, exclude: Option<usize>) -> Vec<usize> {
+        if k == 0 {
+            return Vec::new();
+        }
+        // Grow the search until it holds k candidates: then the k nearest are all inside it
+        let extent = self.max_cell.iter().map(|&c| (c + 1) as f32 * self.cell_size).fold(0.0, f32::max);
+        let mut radius = self.cell_size;
+        let mut found: Vec<(usize, f32)> = loop {
+            let found: Vec<(usize, f32)> = self.within(center, radius)
+                .filter(|&(id, _)| Some(id) != exclude)
+                .collect();
+            if found.len() >= k || radius > 2.0 * extent {
+                break found;
+            }
+            radius *= 2.0;
+        };
+        if found.len() < k {
+            // Fewer points than asked for (some may lie far outside the bounds)
+            found = self.cells.values().flatten()
+                .filter(|&&(id, _)| Some(id) != exclude)
+                .map(|&(id, pos)| (id, distance(center, pos)))
+                .collect();
+        }
+        found.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        found.into_iter().take(k).map(|(id, _)| id).collect()
+    }
+
+    /// (id, distance) of every point within `radius` of `center`
+    fn within(&self, center: [f32; 3], radius: f32) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let lo = self.cell_of(center.map(|v| v - radius));
+        let hi = self.cell_of(center.map(|v| v + radius));
+        (lo[0]..=hi[0])
+            .flat_map(move |x| (lo[1]..=hi[1]).flat_map(move |y| (lo[2]..=hi[2]).map(move |z| [x, y, z])))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .map(move |&(id, pos)| (id, distance(center, pos)))
+            .filter(move |&(_, dist)| dist <= radius)
+    }
+
+    /// Cell containing `pos`, clamped onto the grid
+    fn cell_of(&self, pos: [f32; 3]) -> Cell {
+        [0, 1, 2].map(|i| (((pos[i] - self.min[i]) / self.cell_size).floor() as i32).clamp(0, self.max_cell[i]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 5x5 horizontal lattice at 1m spacing centered on the origin, plus one point beyond the bounds
+    fn lattice() -> (SpatialGrid, Vec<(usize, [f32; 3])>) {
+        let mut points: Vec<(usize, [f32; 3])> = (0..25)
+            .map(|i| (i, [(i % 5) as f32 - 2.0, (i / 5) as f32 - 2.0, 1.0]))
+            .collect();
+        points.push((25, [30.0, 0.0, 1.0]));
+        let mut grid = SpatialGrid::new(1.0, [-10.0, -10.0, 0.0], [10.0, 10.0, 5.0]);
+        grid.rebuild(points.iter().copied());
+        (grid, points)
+    }
+
+    #[test]
+    fn query_radius_matches_a_brute_force_scan() {
+        let (grid, points) = lattice();
+        assert_eq!(grid.query_radius([0.0, 0.0, 1.0], 1.0), vec![7, 11, 12, 13, 17]);
+        assert_eq!(grid.query_radius([0.0, 0.0, 1.0], 0.5), vec![12]);
+        assert_eq!(grid.query_radius([2.0, 2.0, 1.0], 1.5), vec![18, 19, 23, 24]);
+
+        for center in [[0.3, -0.7, 1.2], [-2.5, 2.5, 0.0], [9.5, 0.0, 1.0], [25.0, 0.0, 1.0]] {
+            for radius in [0.5, 1.3, 2.9, 8.0] {
+                let expected: Vec<usize> = points.iter()
+                    .filter(|(_, pos)| distance(center, *pos) <= radius)
+                    .map(|&(id, _)| id)
+                    .collect();
+                assert_eq!(grid.query_radius(center, radius), expected, "center {:?} radius {}", center, radius);
+            }
+        }
+    }
+
+    #[test]
+    fn k_nearest_orders_by_distance_and_reaches_outside_points() {
+        let (grid, _) = lattice();
+        assert_eq!(grid.k_nearest([0.0, 0.0, 1.0], 1, Some(12)), vec![7]);
+        assert_eq!(grid.k_nearest([2.1, 2.0, 1.0], 3, None), vec![24, 19, 23]);
+        assert_eq!(grid.k_nearest([29.0, 0.0, 1.0], 1, None), vec![25]);
+        assert_eq!(grid.k_nearest([0.0, 0.0, 1.0], 40, None).len(), 26);
+        assert!(grid.k_nearest([0.0, 0.0, 1.0], 0, None).is_empty());
+    }
+}
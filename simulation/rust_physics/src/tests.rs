@@ -0,0 +1,2113 @@
+//! Swarm behavior tests, driving RustSwarm through its public methods
+
+use super::*;
+
+/// Physics steps for `seconds` of sim time at 240 Hz
+fn steps(swarm: &mut RustSwarm, seconds: f32) {
+    for _ in 0..(seconds * 240.0) as usize {
+        swarm.step();
+    }
+}
+
+/// `n` drones taken off to 1m and settled in hover
+fn hovering_swarm(n: usize) -> RustSwarm {
+    let mut swarm = RustSwarm::new(n, 240);
+    swarm.takeoff_all(1.0);
+    steps(&mut swarm, 3.0);
+    swarm.hover_all();
+    swarm
+}
+
+#[test]
+fn snapshot_restore_replays_identically() {
+    let mut swarm = hovering_swarm(4);
+    swarm.goto(0, 2.0, 1.0, 1.5, 0.5).unwrap();
+    swarm.velocity(1, 0.5, -0.3, 0.1, 0.2);
+    let saved = swarm.snapshot().unwrap();
+
+    steps(&mut swarm, 1.0);
+    let first = swarm.snapshot().unwrap();
+
+    swarm.restore(saved).unwrap();
+    steps(&mut swarm, 1.0);
+    assert_eq!(swarm.snapshot().unwrap(), first);
+}
+
+#[test]
+fn restore_rejects_other_versions_and_swarm_sizes() {
+    let mut swarm = hovering_swarm(3);
+    let mut data = swarm.snapshot().unwrap();
+    data[..4].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+    assert!(swarm.restore(data).is_err());
+
+    let other = RustSwarm::new(5, 240).snapshot().unwrap();
+    assert!(swarm.restore(other).is_err());
+}
+
+/// Assert the decoder matches the swarm to within the delta quantization (1mm, 1e-4 rad, 0.01%)
+fn assert_decoded(decoder: &StateDeltaDecoder, swarm: &RustSwarm) {
+    let decoded = decoder.get_states();
+    let actual = swarm.get_states();
+    assert_eq!(decoded.len(), actual.len());
+    for (d, a) in decoded.iter().zip(&actual) {
+        for i in 0..3 {
+            assert!((d.pos[i] - a.pos[i]).abs() <= 5e-4 + 1e-6, "drone {} pos {:?} vs {:?}", a.id, d.pos, a.pos);
+            assert!((d.vel[i] - a.vel[i]).abs() <= 5e-4 + 1e-6, "drone {} vel {:?} vs {:?}", a.id, d.vel, a.vel);
+        }
+        assert!((d.yaw - a.yaw).abs() <= 5e-5 + 1e-6);
+        assert!((d.battery - a.battery).abs() <= 5e-3 + 1e-4);
+        assert_eq!(d.healthy, a.healthy);
+    }
+    assert_eq!(decoder.get_time(), swarm.get_time());
+}
+
+#[test]
+fn state_delta_round_trips_within_quantization() {
+    let mut swarm = hovering_swarm(5);
+    swarm.velocity(0, 0.4, 0.1, 0.0, 0.3);
+    swarm.goto(2, -1.5, 2.0, 2.0, 1.0).unwrap();
+    let mut decoder = StateDeltaDecoder::new();
+
+    let keyframe = swarm.get_state_delta();
+    assert_eq!(keyframe[0], DELTA_KEYFRAME);
+    decoder.apply(keyframe).unwrap();
+    assert_decoded(&decoder, &swarm);
+
+    for _ in 0..20 {
+        steps(&mut swarm, 0.05);
+        let delta = swarm.get_state_delta();
+        assert_eq!(delta[0] & DELTA_KEYFRAME, 0);
+        decoder.apply(delta).unwrap();
+        assert_decoded(&decoder, &swarm);
+    }
+}
+
+#[test]
+fn state_delta_sends_keyframe_after_respawn() {
+    let mut swarm = hovering_swarm(3);
+    let mut decoder = StateDeltaDecoder::new();
+    decoder.apply(swarm.get_state_delta()).unwrap();
+
+    swarm.respawn(6);
+    swarm.step();
+    let delta = swarm.get_state_delta();
+    assert_eq!(delta[0], DELTA_KEYFRAME);
+    decoder.apply(delta).unwrap();
+    assert_decoded(&decoder, &swarm);
+
+    // A decoder that missed the keyframe can't apply deltas for the new drone count
+    let mut stale = StateDeltaDecoder::new();
+    stale.states = vec![QuantizedState::default(); 3];
+    swarm.step();
+    assert!(stale.apply(swarm.get_state_delta()).is_err());
+}
+
+/// Swarm with seeded noise and personality variation running a mixed command sequence
+fn lockstep_run(seed: u64) -> Vec<u64> {
+    let mut swarm = RustSwarm::new(6, 240);
+    swarm.set_lockstep(true);
+    swarm.set_sensor_noise(0.02, 0.01, seed);
+    swarm.set_personality_variation(true, 0.2, seed);
+    swarm.set_wind(0.3, -0.2, 0.0);
+    swarm.set_wind_gust(0.5, 0.7);
+    swarm.takeoff_all(1.0);
+    let mut hashes = Vec::new();
+    for k in 0..960 {
+        match k {
+            240 => swarm.formation_circle([0.0, 0.0, 1.5], 1.5, None).unwrap(),
+            480 => swarm.velocity(3, 0.5, 0.2, 0.0, 0.4),
+            720 => swarm.maneuver_wave([1.0, 0.0], 3.0, 0.3, 1.0, 1.5),
+            _ => {}
+        }
+        swarm.step();
+        hashes.push(swarm.get_state_hash());
+    }
+    hashes
+}
+
+#[test]
+fn lockstep_runs_with_the_same_seed_hash_identically() {
+    assert_eq!(lockstep_run(7), lockstep_run(7));
+    assert_ne!(lockstep_run(7).last(), lockstep_run(8).last());
+}
+
+#[test]
+fn lockstep_trig_tracks_libm() {
+    for i in -4000..=4000 {
+        let x = i as f32 * 0.005;
+        assert!((lockstep_sin(x) - x.sin()).abs() < 1e-5, "sin({}) = {} vs {}", x, lockstep_sin(x), x.sin());
+    }
+    for i in -40..=40 {
+        for j in -40..=40 {
+            let (y, x) = (i as f32 * 0.25, j as f32 * 0.25);
+            let error = (lockstep_atan2(y, x) - y.atan2(x)).abs();
+            // The branch cut at -x may land on either side of +-PI
+            let error = error.min((error - 2.0 * PI).abs());
+            assert!(error < 1e-5, "atan2({}, {}) = {} vs {}", y, x, lockstep_atan2(y, x), y.atan2(x));
+        }
+    }
+}
+
+/// Closest distance between drones 0 and 1 flying head-on between (-2, 0) and (2, 0)
+fn head_on_min_distance(swarm: &mut RustSwarm) -> f32 {
+    swarm.drones[0].pos = [-2.0, 0.0, 1.0];
+    swarm.drones[1].pos = [2.0, 0.0, 1.0];
+    swarm.goto(0, 2.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, -2.0, 0.0, 1.0, 0.0).unwrap();
+    let mut closest = f32::INFINITY;
+    for _ in 0..240 * 8 {
+        swarm.step();
+        closest = closest.min(distance(swarm.drones[0].pos, swarm.drones[1].pos));
+    }
+    closest
+}
+
+#[test]
+fn avoidance_keeps_head_on_drones_apart() {
+    let mut swarm = hovering_swarm(2);
+    let contact = 2.0 * DEFAULT_RADIUS;
+    assert!(head_on_min_distance(&mut swarm) < contact);
+
+    // The soft falloff lets the pair dip a little inside the radius as they pass
+    let mut swarm = hovering_swarm(2);
+    swarm.set_collision_avoidance(true, 0.4, 1.0, 1.0);
+    let closest = head_on_min_distance(&mut swarm);
+    assert!(closest > 0.8 * 0.4, "closest approach {}", closest);
+    assert!(distance(swarm.drones[0].pos, [2.0, 0.0, 1.0]) < 0.05);
+    assert!(distance(swarm.drones[1].pos, [-2.0, 0.0, 1.0]) < 0.05);
+}
+
+#[test]
+fn avoidance_lets_a_crowd_settle_at_a_shared_waypoint() {
+    let mut swarm = hovering_swarm(16);
+    swarm.formation_grid([0.0, 0.0, 1.0], 1.0, None).unwrap();
+    steps(&mut swarm, 5.0);
+    swarm.set_collision_avoidance(true, 0.4, 1.0, 0.0);
+    for id in 0..16 {
+        swarm.goto(id, 3.0, 3.0, 1.5, 0.0).unwrap();
+    }
+    let mut closest = f32::INFINITY;
+    for _ in 0..240 * 20 {
+        swarm.step();
+        closest = closest.min(swarm.min_pairwise_distance((0..16).collect()).unwrap().2);
+    }
+    assert!(closest > 2.0 * DEFAULT_RADIUS, "closest approach {}", closest);
+    for drone in &swarm.drones {
+        assert!(distance(drone.pos, [3.0, 3.0, 1.5]) < 0.6);
+        assert!(drone.vel.iter().all(|v| v.abs() < 0.05), "drone {} still moving {:?}", drone.id, drone.vel);
+    }
+}
+
+#[test]
+fn failsafe_returns_home_then_lands_and_overrides_commands() {
+    let mut swarm = hovering_swarm(2);
+    let home = swarm.drones[0].home;
+    swarm.goto(0, 3.0, 2.0, 1.5, 0.0).unwrap();
+    steps(&mut swarm, 5.0);
+    swarm.set_failsafe(30.0, 10.0);
+
+    // Above both thresholds nothing happens
+    swarm.update_batteries(60.0 * 60.0);
+    swarm.step();
+    assert_eq!(swarm.drones[0].failsafe_stage, None);
+    assert_eq!(swarm.drones[0].mode, DroneMode::Goto);
+
+    // Below return-home: fly back over home at the current altitude
+    swarm.update_batteries(15.0 * 60.0);
+    swarm.step();
+    let target = [home[0], home[1], swarm.drones[0].target_pos[2]];
+    assert_eq!(swarm.drones[0].failsafe_stage, Some(FailsafeStage::ReturnHome { target }));
+    assert_eq!(swarm.drones[0].mode, DroneMode::Goto);
+    assert!((target[2] - 1.5).abs() < 0.05);
+
+    // Later commands are overridden on the next step
+    swarm.goto(0, -3.0, -3.0, 2.0, 0.0).unwrap();
+    swarm.step();
+    assert_eq!(swarm.drones[0].mode, DroneMode::Goto);
+    assert_eq!(swarm.drones[0].target_pos, target);
+
+    // Arrival over the pad lands there
+    for _ in 0..240 * 10 {
+        swarm.step();
+        if swarm.drones[0].mode == DroneMode::Landing {
+            break;
+        }
+    }
+    assert_eq!(swarm.drones[0].failsafe_stage, Some(FailsafeStage::Land));
+    assert_eq!(swarm.drones[0].mode, DroneMode::Landing);
+    assert_eq!(&swarm.drones[0].target_pos[..2], &home[..2]);
+    swarm.hover(vec![0]);
+    swarm.step();
+    assert_eq!(swarm.drones[0].mode, DroneMode::Landing);
+    steps(&mut swarm, 6.0);
+    assert_eq!(swarm.drones[0].mode, DroneMode::Idle);
+    assert!((swarm.drones[0].pos[0] - home[0]).hypot(swarm.drones[0].pos[1] - home[1]) < FAILSAFE_HOME_RADIUS);
+}
+
+#[test]
+fn failsafe_lands_in_place_below_the_land_threshold() {
+    let mut swarm = hovering_swarm(1);
+    swarm.goto(0, 4.0, 0.0, 1.5, 0.0).unwrap();
+    steps(&mut swarm, 5.0);
+    swarm.set_failsafe(30.0, 10.0);
+
+    swarm.update_batteries(75.0 * 60.0);
+    swarm.step();
+    assert!(matches!(swarm.drones[0].failsafe_stage, Some(FailsafeStage::ReturnHome { .. })));
+    steps(&mut swarm, 0.5);
+
+    // Dropping through the land threshold on the way home lands where the drone is
+    swarm.update_batteries(20.0 * 60.0);
+    swarm.step();
+    let pos = swarm.drones[0].pos;
+    assert_eq!(swarm.drones[0].failsafe_stage, Some(FailsafeStage::Land));
+    assert_eq!(swarm.drones[0].mode, DroneMode::Landing);
+    assert!((swarm.drones[0].target_pos[0] - pos[0]).abs() < 0.05);
+    assert!(pos[0] > 1.0);
+}
+
+#[test]
+fn collisions_use_each_pairs_radius_sum() {
+    let mut swarm = hovering_swarm(4);
+    swarm.set_physical_properties(0, 0.05, 0.3);
+    swarm.set_physical_properties(1, 0.05, 0.1);
+    // 0-1: 0.35 apart, inside 0.3 + 0.1; 2-3: same gap, outside the default 0.075 + 0.075
+    swarm.drones[0].pos = [0.0, 0.0, 1.0];
+    swarm.drones[1].pos = [0.35, 0.0, 1.0];
+    swarm.drones[2].pos = [3.0, 0.0, 1.0];
+    swarm.drones[3].pos = [3.35, 0.0, 1.0];
+    assert_eq!(swarm.get_collisions(), vec![(0, 1)]);
+
+    for id in 0..4 {
+        let [x, y, z] = swarm.drones[id].pos;
+        swarm.goto(id, x, y, z, 0.0).unwrap();
+    }
+    swarm.set_crash_detection(true, 0.05, 2.0);
+    swarm.step();
+    assert!(swarm.drones[0].crashed && swarm.drones[1].crashed);
+    assert!(!swarm.drones[2].crashed && !swarm.drones[3].crashed);
+}
+
+#[test]
+fn crashes_emit_one_event_each() {
+    let mut swarm = hovering_swarm(3);
+    swarm.set_crash_detection(true, 0.15, 1.5);
+    swarm.drones[2].pos = [0.0, 3.0, 1.0];
+    swarm.goto(2, 0.0, 3.0, 1.0, 0.0).unwrap();
+    swarm.drones[0].pos = [-2.0, 0.0, 1.0];
+    swarm.drones[1].pos = [2.0, 0.0, 1.0];
+    swarm.goto(0, 2.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, -2.0, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 5.0);
+
+    let events = swarm.drain_events().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!((events[0].1.as_str(), events[0].2.clone()), ("collision", vec![0, 1]));
+    assert!(!swarm.drones[0].healthy && !swarm.drones[1].healthy);
+    assert!(swarm.drain_events().unwrap().is_empty());
+
+    swarm.velocity(2, 0.0, 0.0, -5.0, 0.0);
+    steps(&mut swarm, 3.0);
+    let events = swarm.drain_events().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!((events[0].1.as_str(), events[0].2.clone()), ("ground_impact", vec![2]));
+    assert!(swarm.drones[2].crashed);
+}
+
+#[test]
+fn gentle_landings_are_not_ground_impacts() {
+    let mut swarm = hovering_swarm(3);
+    swarm.set_crash_detection(true, 0.15, 1.5);
+    swarm.land_all();
+    steps(&mut swarm, 6.0);
+    assert!(swarm.drain_events().unwrap().is_empty());
+    assert!(swarm.drones.iter().all(|d| d.healthy && d.mode == DroneMode::Idle));
+}
+
+#[test]
+fn subset_formations_leave_other_drones_alone() {
+    let mut swarm = hovering_swarm(8);
+    swarm.formation_circle([-3.0, 0.0, 1.0], 1.0, Some(vec![0, 1, 2, 3])).unwrap();
+    steps(&mut swarm, 6.0);
+    let first: Vec<[f32; 3]> = swarm.drones[..4].iter().map(|d| d.target_pos).collect();
+
+    swarm.formation_circle([3.0, 0.0, 2.0], 1.0, Some(vec![4, 5, 6, 7])).unwrap();
+    steps(&mut swarm, 6.0);
+
+    for (drone, target) in swarm.drones[..4].iter().zip(&first) {
+        assert_eq!(drone.target_pos, *target);
+        assert!((distance(drone.pos, [-3.0, 0.0, 1.0]) - 1.0).abs() < 0.05);
+    }
+    for drone in &swarm.drones[4..] {
+        assert!((distance(drone.pos, [3.0, 0.0, 2.0]) - 1.0).abs() < 0.05);
+    }
+}
+
+#[test]
+fn formation_ids_drop_unknown_and_repeated_ids() {
+    let swarm = RustSwarm::new(4, 240);
+    assert_eq!(swarm.formation_ids(Some(vec![2, 9, 0, 2])), vec![2, 0]);
+    assert_eq!(swarm.formation_ids(None), vec![0, 1, 2, 3]);
+}
+
+/// Furthest drone 0 strays from its hover spot at the origin and drone 1 from its y = 0.1
+/// line while drone 1 flies past along it
+fn pass_by_deviation(goal_priority: bool) -> (f32, f32) {
+    let mut swarm = hovering_swarm(2);
+    swarm.set_collision_avoidance(true, 0.4, 1.0, 1.0);
+    swarm.set_goal_priority(goal_priority, 0.1);
+    swarm.drones[0].pos = [0.0, 0.0, 1.0];
+    swarm.goto(0, 0.0, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 2.0);
+    swarm.drones[1].pos = [-2.0, 0.1, 1.0];
+    swarm.goto(1, 2.0, 0.1, 1.0, 0.0).unwrap();
+    let (mut holder, mut passer) = (0.0f32, 0.0f32);
+    for _ in 0..240 * 8 {
+        swarm.step();
+        holder = holder.max(distance(swarm.drones[0].pos, [0.0, 0.0, 1.0]));
+        passer = passer.max((swarm.drones[1].pos[1] - 0.1).abs());
+    }
+    assert!(distance(swarm.drones[1].pos, [2.0, 0.1, 1.0]) < 0.05);
+    (holder, passer)
+}
+
+#[test]
+fn goal_priority_leaves_the_avoiding_to_the_passing_drone() {
+    let (holder, passer) = pass_by_deviation(false);
+    assert!(holder > 0.1, "holder strayed {}", holder);
+
+    let (held, passed) = pass_by_deviation(true);
+    assert!(held < 0.05, "holder strayed {}", held);
+    assert!(passed > passer && passed > 0.3, "passer deviated {}", passed);
+}
+
+/// Ids in the events drained after five hovering drones dive into the ground together, with
+/// the event buffer capped at three
+fn impacts_kept(policy: &str) -> (bool, Vec<usize>) {
+    let mut swarm = hovering_swarm(5);
+    swarm.set_crash_detection(true, 0.15, 1.5);
+    swarm.set_event_buffer_policy(3, policy).unwrap();
+    for id in 0..5 {
+        swarm.velocity(id, 0.0, 0.0, -5.0, 0.0);
+    }
+    steps(&mut swarm, 3.0);
+    assert!(swarm.drones.iter().all(|d| d.crashed));
+    let (raised, events) = match swarm.drain_events() {
+        Ok(events) => (false, events),
+        Err(_) => (true, swarm.drain_events().unwrap()),
+    };
+    (raised, events.into_iter().flat_map(|(_, _, ids)| ids).collect())
+}
+
+#[test]
+fn event_buffer_policy_bounds_the_buffer() {
+    assert_eq!(impacts_kept("drop_oldest"), (false, vec![2, 3, 4]));
+    assert_eq!(impacts_kept("drop_newest"), (false, vec![0, 1, 2]));
+    assert_eq!(impacts_kept("error"), (true, vec![0, 1, 2]));
+    assert!(RustSwarm::new(1, 240).set_event_buffer_policy(3, "drop_random").is_err());
+}
+
+/// Closest drone 0 comes to the axis of a 0.3m obstacle at the origin while flying past it
+/// along y = 0.5, and how far from the axis it stops short of a target just inside the obstacle
+fn obstacle_clearance(margin: f32) -> (f32, f32) {
+    let mut swarm = hovering_swarm(1);
+    swarm.add_obstacle(0.0, 0.0, 0.3, 2.0);
+    swarm.set_obstacle_margin(margin);
+    swarm.set_collision_avoidance(true, 0.4, 1.0, 1.0);
+    swarm.drones[0].pos = [-2.0, 0.5, 1.0];
+    swarm.goto(0, 2.0, 0.5, 1.0, 0.0).unwrap();
+    let mut closest = f32::INFINITY;
+    for _ in 0..240 * 8 {
+        swarm.step();
+        closest = closest.min(swarm.drones[0].pos[0].hypot(swarm.drones[0].pos[1]));
+    }
+    swarm.set_collision_avoidance(false, 0.4, 1.0, 1.0);
+    swarm.goto(0, 0.2, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 8.0);
+    (closest, swarm.drones[0].pos[0].hypot(swarm.drones[0].pos[1]))
+}
+
+#[test]
+fn obstacle_margin_widens_the_berth() {
+    let (closest, stop) = obstacle_clearance(0.0);
+    let (wide, wide_stop) = obstacle_clearance(0.3);
+    assert!(closest > 0.3 + DEFAULT_RADIUS);
+    assert!(wide > closest + 0.1, "closest approach {} vs {} without margin", wide, closest);
+    assert!((stop - (0.3 + DEFAULT_RADIUS)).abs() < 0.02, "stopped {} from the axis", stop);
+    assert!((wide_stop - (0.6 + DEFAULT_RADIUS)).abs() < 0.02, "stopped {} from the axis", wide_stop);
+}
+
+/// Separation of drones 0 and 1 after holding targets 0.2m apart with collision avoidance on
+fn held_spacing(groups: [Option<u32>; 2]) -> f32 {
+    let mut swarm = hovering_swarm(2);
+    swarm.set_collision_avoidance(true, 0.4, 1.0, 0.0);
+    for (id, group) in groups.into_iter().enumerate() {
+        if let Some(group) = group {
+            swarm.set_avoidance_group(id, group);
+        }
+    }
+    swarm.goto(0, -0.1, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, 0.1, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 5.0);
+    distance(swarm.drones[0].pos, swarm.drones[1].pos)
+}
+
+#[test]
+fn avoidance_groups_only_separate_different_groups() {
+    assert!((held_spacing([Some(1), Some(1)]) - 0.2).abs() < 0.01);
+    assert!(held_spacing([Some(1), Some(2)]) > 0.25);
+    assert!(held_spacing([Some(1), None]) > 0.25);
+    assert!(held_spacing([None, None]) > 0.25);
+}
+
+/// Stationary avoidance neighbor `id` at `pos`
+fn still_neighbor(id: usize, pos: [f32; 3]) -> AvoidanceNeighbor {
+    AvoidanceNeighbor { id, pos, vel: [0.0; 3], radius: DEFAULT_RADIUS, holding: false, group: None }
+}
+
+#[test]
+fn avoidance_falloff_curves_shape_the_push() {
+    let drone = still_neighbor(0, [0.0, 0.0, 1.0]);
+    let push_at = |dist: f32, falloff: AvoidanceFalloff| {
+        let neighbors = [drone, still_neighbor(1, [dist, 0.0, 1.0])];
+        -avoidance_velocity(&drone, &neighbors, 0.4, 1.0, 0.0, falloff, false)[0]
+    };
+    let curves = [AvoidanceFalloff::Linear, AvoidanceFalloff::Inverse, AvoidanceFalloff::InverseSquare, AvoidanceFalloff::Exponential];
+    for falloff in curves {
+        assert!(push_at(0.2, falloff) > 0.0);
+        assert!(push_at(0.39, falloff) < push_at(0.2, falloff));
+        assert!(push_at(0.4, falloff).abs() < 1e-6);
+    }
+    assert!((push_at(0.1, AvoidanceFalloff::Linear) - 0.75).abs() < 1e-5);
+    assert!(push_at(0.1, AvoidanceFalloff::InverseSquare) > 10.0 * push_at(0.1, AvoidanceFalloff::Linear));
+    assert!(push_at(0.1, AvoidanceFalloff::Inverse) > push_at(0.1, AvoidanceFalloff::Linear));
+    assert!(push_at(0.1, AvoidanceFalloff::Exponential) < push_at(0.1, AvoidanceFalloff::Linear));
+    assert!(RustSwarm::new(1, 240).set_avoidance_falloff("cubic").is_err());
+}
+
+#[test]
+fn adaptive_avoidance_pushes_harder_in_a_crowd() {
+    let drone = still_neighbor(0, [0.0, 0.0, 1.0]);
+    let pair = [drone, still_neighbor(1, [0.2, 0.0, 1.0])];
+    // Two more neighbors equally close on either side, whose pushes cancel
+    let crowd = [pair[0], pair[1], still_neighbor(2, [0.0, 0.2, 1.0]), still_neighbor(3, [0.0, -0.2, 1.0])];
+    let push_x = |neighbors: &[AvoidanceNeighbor], adaptive| {
+        -avoidance_velocity(&drone, neighbors, 0.4, 1.0, 0.0, AvoidanceFalloff::Linear, adaptive)[0]
+    };
+    assert!((push_x(&pair, false) - push_x(&crowd, false)).abs() < 1e-6);
+    assert!((push_x(&pair, true) - push_x(&pair, false)).abs() < 1e-6);
+    assert!((push_x(&crowd, true) - 3.0 * push_x(&pair, true)).abs() < 1e-5);
+}
+
+#[test]
+fn scene_description_lists_obstacles_and_the_geofence() {
+    let mut swarm = RustSwarm::new(1, 240);
+    assert!(swarm.get_scene_description().contains("\"geofence\":null"));
+
+    swarm.add_obstacle(1.0, 2.0, 0.5, 3.0);
+    swarm.set_geofence_polygon(vec![[-4.0, -4.0], [4.0, -4.0], [0.0, 4.0]], 0.5, 4.0).unwrap();
+    swarm.add_charge_zone([0.0, 0.0], 1.0, f32::INFINITY);
+    let scene = swarm.get_scene_description();
+    assert!(scene.contains("\"obstacles\":[{\"center\":[1,2],\"velocity\":[0,0],\"radius\":0.5,\"height\":3}]"), "{}", scene);
+    assert!(scene.contains("\"geofence\":{\"vertices\":[[-4,-4],[4,-4],[0,4]],\"min_z\":0.5,\"max_z\":4}"), "{}", scene);
+    assert!(scene.contains("\"charge_rate\":null"), "{}", scene);
+    assert!(!scene.contains("inf") && !scene.contains("NaN"));
+}
+
+/// Furthest drone 0 drifts from where it was told to hover after 0.5s of a goto toward
+/// x = 3 with a strong integral gain
+fn hover_overshoot(reset_pid: bool) -> f32 {
+    let mut swarm = hovering_swarm(1);
+    swarm.set_reset_pid_on_mode_change(reset_pid);
+    swarm.set_drone_control_params(0, 2.0, 2.0, 0.5, 5.0, 0.1);
+    swarm.goto(0, 3.0, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 0.5);
+    swarm.hover(vec![0]);
+    let spot = swarm.drones[0].target_pos;
+    let mut drift = 0.0f32;
+    for _ in 0..240 * 3 {
+        swarm.step();
+        drift = drift.max(distance(swarm.drones[0].pos, spot));
+    }
+    drift
+}
+
+#[test]
+fn mode_change_pid_reset_stops_the_wound_up_kick() {
+    let wound_up = hover_overshoot(false);
+    let reset = hover_overshoot(true);
+    assert!(reset < 0.5 * wound_up, "overshoot {} with reset vs {} without", reset, wound_up);
+
+    // Maneuvers take drones into goto under the same setting
+    for reset_pid in [false, true] {
+        let mut swarm = hovering_swarm(1);
+        swarm.set_reset_pid_on_mode_change(reset_pid);
+        swarm.drones[0].pid_integral = [0.5, 0.0, 0.0];
+        swarm.drones[0].follow_target([1.0, 0.0, 1.0], &swarm.config);
+        assert_eq!(swarm.drones[0].pid_integral[0] == 0.0, reset_pid);
+    }
+}
+
+#[test]
+fn min_pairwise_distance_finds_the_closest_pair_in_a_large_swarm() {
+    // 10x10 lattice at 0.5m spacing with drone 37 nudged to 0.2m from drone 36
+    let mut swarm = RustSwarm::new(100, 240);
+    for drone in &mut swarm.drones {
+        drone.pos = [(drone.id % 10) as f32 * 0.5 - 2.0, (drone.id / 10) as f32 * 0.5 - 2.0, 1.0];
+    }
+    swarm.drones[37].pos[0] = swarm.drones[36].pos[0] + 0.2;
+
+    let (a, b, dist) = swarm.min_pairwise_distance((0..100).collect()).unwrap();
+    assert_eq!((a, b), (36, 37));
+    assert!((dist - 0.2).abs() < 1e-5);
+
+    let without_37: Vec<usize> = (0..100).filter(|&id| id != 37).collect();
+    let (_, _, dist) = swarm.min_pairwise_distance(without_37.clone()).unwrap();
+    assert!((dist - 0.5).abs() < 1e-5);
+    let scanned = swarm.pairwise_distances(without_37).into_iter().fold(f32::INFINITY, f32::min);
+    assert_eq!(dist, scanned);
+
+    assert_eq!(swarm.min_pairwise_distance(vec![36, 37, 37]).map(|(a, b, _)| (a, b)), Some((36, 37)));
+    assert_eq!(swarm.min_pairwise_distance(vec![36, 36]), None);
+}
+
+#[test]
+fn nearest_drone_uses_the_spatial_hash_and_skips_failed_drones() {
+    // 10x10 lattice at 0.5m spacing; drone 55 sits at (0.5, 0.5)
+    let mut swarm = RustSwarm::new(100, 240);
+    for drone in &mut swarm.drones {
+        drone.pos = [(drone.id % 10) as f32 * 0.5 - 2.0, (drone.id / 10) as f32 * 0.5 - 2.0, 1.0];
+    }
+    swarm.rebuild_spatial_grid();
+
+    let (id, dist) = swarm.nearest_drone([0.6, 0.5, 1.0], false).unwrap();
+    assert_eq!(id, 55);
+    assert!((dist - 0.1).abs() < 1e-5);
+
+    swarm.drones[55].healthy = false;
+    assert_eq!(swarm.nearest_drone([0.6, 0.5, 1.0], false).unwrap().0, 56);
+    assert_eq!(swarm.nearest_drone([0.6, 0.5, 1.0], true).unwrap().0, 55);
+
+    // Only a far corner drone is healthy: none of the hashed candidates qualify
+    for drone in &mut swarm.drones {
+        drone.healthy = drone.id == 0;
+    }
+    assert_eq!(swarm.nearest_drone([0.6, 0.5, 1.0], false).unwrap().0, 0);
+    swarm.drones[0].healthy = false;
+    assert_eq!(swarm.nearest_drone([0.6, 0.5, 1.0], false), None);
+}
+
+#[test]
+fn scene_scale_applies_to_tolerances_and_mode_limits() {
+    let mut swarm = hovering_swarm(1);
+    swarm.set_scene_scale(2.0);
+    swarm.set_mode_velocity_limit("goto", 0.5).unwrap();
+    swarm.goto(0, 3.0, 0.0, 1.0, 0.0).unwrap();
+    let mut top_speed = 0.0f32;
+    for _ in 0..240 * 2 {
+        swarm.step();
+        top_speed = top_speed.max(swarm.drones[0].vel[0].hypot(swarm.drones[0].vel[1]));
+    }
+    assert!(top_speed > 0.9 && top_speed < 1.0 + 1e-3, "top speed {}", top_speed);
+
+    // 0.3m off target reads as 0.15 scene units
+    let drone = &mut swarm.drones[0];
+    drone.target_pos = [drone.pos[0] + 0.3, drone.pos[1], drone.pos[2]];
+    drone.vel = [0.0; 3];
+    drone.stuck_time = 10.0;
+    assert!(swarm.formation_complete(0.2));
+    assert!(!swarm.formation_complete(0.1));
+    assert!(swarm.get_stuck_drones(0.2, 1.0).is_empty());
+    assert_eq!(swarm.get_stuck_drones(0.1, 1.0), vec![0]);
+}
+
+/// Circle formation of 4 drones around (0, 0, 1) held with hold_formation, and its slots
+fn held_circle() -> (RustSwarm, Vec<[f32; 3]>) {
+    let mut swarm = hovering_swarm(4);
+    swarm.hold_formation(true);
+    swarm.formation_circle([0.0, 0.0, 1.0], 1.0, None).unwrap();
+    steps(&mut swarm, 5.0);
+    let slots = swarm.drones.iter().map(|d| d.target_pos).collect();
+    (swarm, slots)
+}
+
+#[test]
+fn held_formations_return_to_their_commanded_anchor() {
+    // The whole group shoved 1m and one target knocked off: slots stay where commanded
+    let (mut swarm, slots) = held_circle();
+    for drone in &mut swarm.drones {
+        drone.pos[0] += 1.0;
+    }
+    swarm.drones[2].target_pos = [3.0, 3.0, 2.0];
+    steps(&mut swarm, 6.0);
+    for (drone, slot) in swarm.drones.iter().zip(&slots) {
+        assert_eq!(drone.target_pos, *slot);
+        assert!(distance(drone.pos, *slot) < 0.05, "drone {} at {:?}", drone.id, drone.pos);
+    }
+
+    // Cruising carries the anchor, and the slots with it
+    let (mut swarm, slots) = held_circle();
+    swarm.cruise_formation([0.5, 0.0, 0.0]);
+    steps(&mut swarm, 2.0);
+    swarm.stop_cruise();
+    steps(&mut swarm, 4.0);
+    for (drone, slot) in swarm.drones.iter().zip(&slots) {
+        assert!(distance(drone.target_pos, [slot[0] + 1.0, slot[1], slot[2]]) < 0.01);
+        assert!(distance(drone.pos, drone.target_pos) < 0.05);
+    }
+}
+
+/// Slots sorted by coordinates, for comparing slot sets regardless of tie order
+fn sorted_slots(mut slots: Vec<[f32; 3]>) -> Vec<[f32; 3]> {
+    slots.sort_by(|a, b| a.iter().zip(b).map(|(x, y)| x.total_cmp(y)).find(|o| o.is_ne()).unwrap_or(std::cmp::Ordering::Equal));
+    slots
+}
+
+#[test]
+fn hex_slots_only_build_the_lattice_the_drones_need() {
+    // 19 slots fill the center, ring at 1 and the two rings within 2 spacings
+    let tight = sorted_slots(hex_slots(19, [0.0, 0.0, 1.0], 1.0, 2.0));
+    assert_eq!(sorted_slots(hex_slots(19, [0.0, 0.0, 1.0], 1.0, 1e9)), tight);
+    assert!(tight.iter().all(|p| p[2] == 1.0 && p[0].hypot(p[1]) <= 2.0 + 1e-4));
+    // A disc that can't hold them all stacks layers instead
+    let stacked = hex_slots(14, [0.0, 0.0, 1.0], 1.0, 1.0);
+    assert_eq!(stacked.iter().filter(|p| p[2] == 2.0).count(), 7);
+
+    let mut swarm = RustSwarm::new(4, 240);
+    assert!(swarm.cluster([0.0, 0.0, 1.0], f32::NAN).is_err());
+    assert!(swarm.cluster([0.0, 0.0, 1.0], f32::INFINITY).is_err());
+    assert!(swarm.cluster([0.0, 0.0, 1.0], -1.0).is_err());
+    assert!(swarm.cluster([0.0, 0.0, 1.0], 1e30).is_ok());
+}
+
+#[test]
+fn control_effort_measures_position_corrections() {
+    // Hovering drones settle to almost no correction, then hold against a crosswind; drone 2
+    // cruises on a velocity command with nothing to correct
+    let mut swarm = hovering_swarm(3);
+    swarm.velocity(2, 1.0, 0.0, 0.0, 0.0);
+    steps(&mut swarm, 6.0);
+    let calm = swarm.get_control_effort(1);
+    assert!(calm < 0.01, "calm hover effort {}", calm);
+    let cruising = swarm.get_control_effort(2);
+
+    swarm.set_wind(3.0, 0.0, 0.0);
+    steps(&mut swarm, 3.0);
+    let windy = swarm.get_control_effort(0);
+    assert!(windy > 0.05 && windy > 10.0 * calm, "effort {} in wind vs {} calm", windy, calm);
+    // Only the effort left over from its hover, fading out
+    assert!(swarm.get_control_effort(2) < 0.1 * cruising);
+}
+
+#[test]
+fn time_in_mode_restarts_at_each_change() {
+    let mut swarm = RustSwarm::new(1, 240);
+    steps(&mut swarm, 1.0);
+    assert!((swarm.time_in_mode(0) - 1.0).abs() < 1e-3);
+
+    // A command reads as just entered before the next step, not as the old mode's age
+    swarm.takeoff_all(1.0);
+    assert_eq!(swarm.time_in_mode(0), 0.0);
+    steps(&mut swarm, 0.5);
+    assert!((swarm.time_in_mode(0) - 0.5).abs() < 1e-3);
+
+    // The automatic hand-over to hover restarts the clock
+    while swarm.drones[0].mode == DroneMode::Takeoff {
+        swarm.step();
+    }
+    assert!(swarm.time_in_mode(0) < 1e-3);
+    steps(&mut swarm, 1.0);
+    assert!((swarm.time_in_mode(0) - 1.0).abs() < 1e-3);
+
+    swarm.goto(0, 1.0, 0.0, 1.0, 0.0).unwrap();
+    assert_eq!(swarm.time_in_mode(0), 0.0);
+    swarm.step();
+    assert!((swarm.time_in_mode(0) - 1.0 / 240.0).abs() < 1e-4);
+}
+
+#[test]
+fn safe_mode_flies_drones_back_instead_of_moving_them() {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, 6.0, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 8.0);
+    swarm.hover_all();
+    assert!((swarm.drones[0].pos[0] - 6.0).abs() < 0.1);
+
+    swarm.enable_safe_mode(true);
+    swarm.step();
+    assert!(swarm.drones[0].pos[0] > 5.8, "drone moved to {:?}", swarm.drones[0].pos);
+    assert!(swarm.drones[0].target_pos[0] <= 2.5);
+
+    // Targets outside the volume are clamped into it, and the drone flies back under the speed cap
+    swarm.goto(1, -6.0, 0.0, 4.0, 0.0).unwrap();
+    let mut top_speed: f32 = 0.0;
+    for _ in 0..(12.0 * 240.0) as usize {
+        swarm.step();
+        top_speed = top_speed.max(distance(swarm.drones[0].vel, [0.0; 3]));
+    }
+    assert!(top_speed < 0.55, "top speed {}", top_speed);
+    assert!(swarm.drones[0].pos[0] < 2.6, "drone still at {:?}", swarm.drones[0].pos);
+    assert!(swarm.drones[1].pos[0] > -2.6 && swarm.drones[1].pos[2] < 2.6);
+
+    swarm.enable_safe_mode(false);
+    assert!(swarm.config.safe_volume.is_none());
+    swarm.goto(0, 6.0, 0.0, 1.0, 0.0).unwrap();
+    assert_eq!(swarm.drones[0].target_pos, [6.0, 0.0, 1.0]);
+}
+
+/// Arc-length position of the point on the square of half-width `h` nearest to `p`
+/// (counter-clockwise from (-h, -h)), and its distance from the square's edges
+fn square_arc(p: [f32; 3], h: f32) -> (f32, f32) {
+    let edges = [([-h, -h], [h, -h]), ([h, -h], [h, h]), ([h, h], [-h, h]), ([-h, h], [-h, -h])];
+    edges.iter().enumerate()
+        .map(|(i, (a, b))| {
+            let t = (((p[0] - a[0]) * (b[0] - a[0]) + (p[1] - a[1]) * (b[1] - a[1])) / (4.0 * h * h)).clamp(0.0, 1.0);
+            let q = [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+            ((i as f32 + t) * 2.0 * h, ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt())
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+}
+
+#[test]
+fn patrol_perimeter_keeps_drones_on_the_edges_and_evenly_spaced() {
+    let mut swarm = hovering_swarm(4);
+    let square = vec![[-2.0, -2.0], [2.0, -2.0], [2.0, 2.0], [-2.0, 2.0]];
+    assert!(swarm.patrol_perimeter(vec![[1.0, 1.0]], 1.5, 0.3).is_err());
+    assert!(swarm.patrol_perimeter(vec![[1.0, 1.0], [1.0, 1.0]], 1.5, 0.3).is_err());
+    swarm.patrol_perimeter(square, 1.5, 0.3).unwrap();
+    steps(&mut swarm, 10.0);
+
+    for _ in 0..8 {
+        steps(&mut swarm, 2.0);
+        let mut arcs: Vec<f32> = swarm.drones.iter()
+            .map(|d| {
+                let (arc, off_edge) = square_arc(d.pos, 2.0);
+                assert!(off_edge < 0.3, "drone {} is {} off the perimeter", d.id, off_edge);
+                assert!((d.pos[2] - 1.5).abs() < 0.1);
+                arc
+            })
+            .collect();
+        arcs.sort_by(f32::total_cmp);
+        for i in 0..4 {
+            let gap = (arcs[(i + 1) % 4] - arcs[i]).rem_euclid(16.0);
+            assert!((gap - 4.0).abs() < 0.5, "arc gaps {:?}", arcs);
+        }
+    }
+}
+
+#[test]
+fn flight_time_remaining_follows_the_mode_drain_rate() {
+    let mut swarm = hovering_swarm(3);
+    // Before any battery update a flying drone drains at the default rate, not "charging"
+    assert!((swarm.get_flight_time_remaining(0) - 100.0 / 0.5 * 60.0).abs() < 1.0);
+
+    swarm.set_mode_drain_rate("goto", 2.0).unwrap();
+    assert!(swarm.set_mode_drain_rate("warp", 2.0).is_err());
+    swarm.goto(1, 1.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.land(vec![2]);
+    steps(&mut swarm, 4.0);
+    for drone in &mut swarm.drones {
+        drone.battery = 50.0;
+    }
+    swarm.update_batteries(1.0);
+
+    let hovering = swarm.get_flight_time_remaining(0);
+    let cruising = swarm.get_flight_time_remaining(1);
+    assert!(cruising < hovering, "goto {} s vs hover {} s", cruising, hovering);
+    assert!((hovering - (50.0 - 1.0 / 60.0) * 60.0).abs() < 0.1);
+    assert!((cruising - (50.0 - 2.0 / 60.0) / 2.0 * 60.0).abs() < 0.1);
+
+    assert_eq!(swarm.drones[2].mode, DroneMode::Idle);
+    assert_eq!(swarm.get_flight_time_remaining(2), f32::MAX);
+    swarm.drones[0].battery = 0.0;
+    assert_eq!(swarm.get_flight_time_remaining(0), 0.0);
+}
+
+/// Descent speed of drone 0 on its last step before touching down, and its fastest descent
+fn landing_speeds(swarm: &mut RustSwarm) -> (f32, f32) {
+    swarm.land(vec![0]);
+    let (mut last, mut fastest) = (0.0, 0.0_f32);
+    while swarm.drones[0].mode == DroneMode::Landing {
+        last = -swarm.drones[0].vel[2];
+        fastest = fastest.max(last);
+        swarm.step();
+    }
+    (last, fastest)
+}
+
+#[test]
+fn landing_flare_softens_the_touchdown() {
+    let mut swarm = RustSwarm::new(1, 240);
+    swarm.takeoff_all(2.0);
+    steps(&mut swarm, 5.0);
+    let (hard, _) = landing_speeds(&mut swarm);
+
+    swarm.set_landing_flare(0.5, 0.1);
+    swarm.takeoff_all(2.0);
+    steps(&mut swarm, 5.0);
+    let (soft, approach) = landing_speeds(&mut swarm);
+    assert!(soft <= 0.1 + 1e-3, "touchdown at {} m/s", soft);
+    assert!(approach > 2.0 * soft && hard > soft, "approach {} hard {} soft {}", approach, hard, soft);
+
+    // A non-positive altitude turns the flare back off
+    swarm.set_landing_flare(0.0, 0.1);
+    assert!(swarm.config.landing_flare.is_none());
+    swarm.takeoff_all(2.0);
+    steps(&mut swarm, 5.0);
+    let (again, _) = landing_speeds(&mut swarm);
+    assert!((again - hard).abs() < 1e-3);
+}
+
+/// Largest overshoot past a 1.5 rad heading command on drone 0, and how often the heading
+/// error changes sign, over `seconds`
+fn yaw_step_response(swarm: &mut RustSwarm, seconds: f32) -> (f32, usize) {
+    swarm.drones[0].yaw = 0.0;
+    swarm.drones[0].yaw_rate = 0.0;
+    let pos = swarm.drones[0].pos;
+    swarm.goto(0, pos[0], pos[1], pos[2], 1.5).unwrap();
+    let (mut overshoot, mut crossings) = (0.0_f32, 0);
+    let mut last_error = -1.5_f32;
+    for _ in 0..(seconds * 240.0) as usize {
+        swarm.step();
+        let error = swarm.drones[0].yaw - 1.5;
+        overshoot = overshoot.max(error);
+        if error.signum() != last_error.signum() {
+            crossings += 1;
+        }
+        last_error = error;
+    }
+    (overshoot, crossings)
+}
+
+#[test]
+fn yaw_inertia_overshoots_and_oscillates() {
+    let mut swarm = hovering_swarm(1);
+    let (overshoot, crossings) = yaw_step_response(&mut swarm, 10.0);
+    assert!(overshoot < 0.01 && crossings <= 1, "instant response overshot {} ({} crossings)", overshoot, crossings);
+
+    swarm.set_yaw_dynamics(1.0, 0.6);
+    let (overshoot, crossings) = yaw_step_response(&mut swarm, 30.0);
+    assert!(overshoot > 0.2 && crossings >= 3, "heavy yaw overshot {} ({} crossings)", overshoot, crossings);
+    assert!((swarm.drones[0].yaw - 1.5).abs() < 0.05, "settled at {}", swarm.drones[0].yaw);
+
+    // A non-positive inertia goes back to the instant response
+    swarm.set_yaw_dynamics(0.0, 0.6);
+    assert!(swarm.config.yaw_dynamics.is_none());
+    let (overshoot, _) = yaw_step_response(&mut swarm, 10.0);
+    assert!(overshoot < 0.01);
+}
+
+#[test]
+fn charge_zones_recharge_only_landed_drones_inside_them() {
+    let mut swarm = RustSwarm::new(3, 240);
+    for drone in &mut swarm.drones {
+        drone.battery = 50.0;
+    }
+    let [x, y, _] = swarm.drones[0].pos;
+    swarm.add_charge_zone([x, y], 0.2, 6.0);
+    swarm.update_batteries(0.5);
+    assert!((swarm.drones[0].battery - 50.1).abs() < 1e-4);
+    assert_eq!(swarm.drones[1].battery, 50.0);
+
+    // Flying over the pad drains instead
+    swarm.takeoff(vec![0], 1.0);
+    steps(&mut swarm, 2.0);
+    swarm.update_batteries(0.5);
+    assert!(swarm.drones[0].battery < 50.1);
+    assert_eq!(swarm.drones[1].battery, 50.0);
+}
+
+/// Peak horizontal acceleration of drone 0 over a 3 s goto 8 m along x
+fn peak_horizontal_accel(swarm: &mut RustSwarm) -> f32 {
+    let pos = swarm.drones[0].pos;
+    swarm.goto(0, pos[0] + 8.0, pos[1], pos[2], 0.0).unwrap();
+    let mut peak = 0.0_f32;
+    for _ in 0..(3.0 * 240.0) as usize {
+        let vel = swarm.drones[0].vel;
+        swarm.step();
+        let dv = [swarm.drones[0].vel[0] - vel[0], swarm.drones[0].vel[1] - vel[1]];
+        peak = peak.max((dv[0] * dv[0] + dv[1] * dv[1]).sqrt() * 240.0);
+    }
+    peak
+}
+
+#[test]
+fn max_tilt_caps_horizontal_acceleration() {
+    let mut swarm = hovering_swarm(1);
+    let free = peak_horizontal_accel(&mut swarm);
+
+    let mut swarm = hovering_swarm(1);
+    swarm.set_max_tilt(0.1);
+    let cap = 9.81 * 0.1_f32.tan();
+    let tilted = peak_horizontal_accel(&mut swarm);
+    assert!(tilted <= cap * 1.01, "peak {} over the tilt cap {}", tilted, cap);
+    assert!(tilted > 0.9 * cap && free > 3.0 * cap, "tilted {} free {}", tilted, free);
+}
+
+/// Mean horizontal distance of the drones from `center`
+fn mean_radius(swarm: &RustSwarm, center: [f32; 2]) -> f32 {
+    swarm.drones.iter()
+        .map(|d| ((d.pos[0] - center[0]).powi(2) + (d.pos[1] - center[1]).powi(2)).sqrt())
+        .sum::<f32>() / swarm.drones.len() as f32
+}
+
+#[test]
+fn breathe_oscillates_the_radius_between_its_bounds() {
+    let mut swarm = hovering_swarm(6);
+    swarm.maneuver_breathe([0.0, 0.0, 1.5], 1.0, 2.0, 8.0);
+    steps(&mut swarm, 8.0);
+
+    // Sample every 0.1 s over two periods and find the peaks
+    let samples: Vec<f32> = (0..160)
+        .map(|_| {
+            steps(&mut swarm, 0.1);
+            mean_radius(&swarm, [0.0, 0.0])
+        })
+        .collect();
+    let (low, high) = samples.iter().fold((f32::INFINITY, 0.0_f32), |(lo, hi), &r| (lo.min(r), hi.max(r)));
+    assert!((low - 1.0).abs() < 0.15 && (high - 2.0).abs() < 0.15, "radius ranged {}..{}", low, high);
+
+    let peaks: Vec<usize> = (1..samples.len() - 1)
+        .filter(|&i| samples[i] >= samples[i - 1] && samples[i] > samples[i + 1] && samples[i] > 1.5)
+        .collect();
+    assert_eq!(peaks.len(), 2, "peaks at {:?}", peaks);
+    assert!(((peaks[1] - peaks[0]) as f32 * 0.1 - 8.0).abs() < 0.3);
+}
+
+#[test]
+fn yaw_derivative_reduces_heading_overshoot() {
+    // Yaw inertia makes the P-only controller swing past the heading
+    let mut swarm = hovering_swarm(1);
+    swarm.set_yaw_dynamics(1.0, 0.2);
+    let (p_only, _) = yaw_step_response(&mut swarm, 10.0);
+
+    swarm.set_yaw_pid(2.0, 0.0, 1.5);
+    let (with_d, _) = yaw_step_response(&mut swarm, 10.0);
+    assert!(p_only > 0.3 && with_d < 0.5 * p_only, "overshoot P {} vs PD {}", p_only, with_d);
+    assert!((swarm.drones[0].yaw - 1.5).abs() < 0.05);
+}
+
+#[test]
+fn per_drone_noise_overrides_the_swarm_setting_reproducibly() {
+    let noisy_swarm = || {
+        let mut swarm = hovering_swarm(3);
+        swarm.set_sensor_noise(0.1, 0.1, 7);
+        swarm.set_drone_noise(0, 0.0, 0.0);
+        swarm.set_drone_noise(2, 0.5, 0.5);
+        steps(&mut swarm, 0.5);
+        swarm
+    };
+    let swarm = noisy_swarm();
+    let states = swarm.get_states();
+    assert_eq!(states[0].pos, swarm.drones[0].pos);
+    assert_eq!(states[0].vel, swarm.drones[0].vel);
+    for id in [1, 2] {
+        assert!(distance(states[id].pos, swarm.drones[id].pos) > 1e-4);
+        assert!(distance(states[id].vel, swarm.drones[id].vel) > 1e-4);
+    }
+
+    let again = noisy_swarm().get_states();
+    for (a, b) in states.iter().zip(&again) {
+        assert_eq!((a.pos, a.vel), (b.pos, b.vel));
+    }
+}
+
+#[test]
+fn undo_restores_the_targets_from_before_a_formation() {
+    let mut swarm = hovering_swarm(4);
+    swarm.goto(1, 1.0, 2.0, 1.5, 0.0).unwrap();
+    let before: Vec<([f32; 3], DroneMode)> = swarm.drones.iter().map(|d| (d.target_pos, d.mode)).collect();
+
+    swarm.formation_circle([0.0, 0.0, 2.0], 2.0, None).unwrap();
+    assert!(swarm.drones.iter().all(|d| (d.target_pos[2] - 2.0).abs() < 1e-5));
+    assert!(swarm.undo_command());
+    let after: Vec<([f32; 3], DroneMode)> = swarm.drones.iter().map(|d| (d.target_pos, d.mode)).collect();
+    assert_eq!(after, before);
+
+    // Bounded history: with depth 1 only the last command can be undone
+    swarm.set_undo_depth(1);
+    swarm.formation_line([0.0, 0.0, 1.0], 1.0, "x", None).unwrap();
+    swarm.formation_grid([0.0, 0.0, 1.0], 1.0, None).unwrap();
+    assert!(swarm.undo_command());
+    assert!(!swarm.undo_command());
+}
+
+#[test]
+fn downwash_pushes_down_only_the_drone_underneath() {
+    let mut swarm = hovering_swarm(3);
+    swarm.goto(0, 0.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, 0.0, 0.0, 2.0, 0.0).unwrap();
+    swarm.goto(2, 3.0, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 6.0);
+    let calm: Vec<(f32, f32)> = swarm.drones.iter().map(|d| (d.pos[2], swarm.get_control_effort(d.id))).collect();
+
+    swarm.set_downwash(true, 1.0);
+    steps(&mut swarm, 3.0);
+    let below = &swarm.drones[0];
+    assert!(below.external_accel[2] < 0.0);
+    assert!(below.pos[2] < calm[0].0 - 0.005, "drone below held {} (was {})", below.pos[2], calm[0].0);
+    assert!(swarm.get_control_effort(0) > calm[0].1 + 0.01, "effort {} (was {})", swarm.get_control_effort(0), calm[0].1);
+
+    for id in [1, 2] {
+        assert_eq!(swarm.drones[id].external_accel, [0.0; 3]);
+        assert!((swarm.drones[id].pos[2] - calm[id].0).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn lifting_a_drone_adds_m_g_dh_of_potential_energy() {
+    let mut swarm = hovering_swarm(2);
+    swarm.set_physical_properties(0, 0.5, 0.1);
+    swarm.set_gravity(9.81);
+    steps(&mut swarm, 1.0);
+    let (_, low) = swarm.get_total_energy();
+    let z0 = swarm.drones[0].pos[2];
+
+    let [x, y, _] = swarm.drones[0].pos;
+    swarm.goto(0, x, y, z0 + 1.5, 0.0).unwrap();
+    steps(&mut swarm, 6.0);
+    let (kinetic, high) = swarm.get_total_energy();
+    let dh = swarm.drones[0].pos[2] - z0;
+    assert!((dh - 1.5).abs() < 0.02);
+    assert!((high - low - 0.5 * 9.81 * dh).abs() < 0.01, "potential rose {} for {} m", high - low, dh);
+    assert!(kinetic < 1e-3);
+}
+
+#[test]
+fn multi_formation_places_each_group_in_its_own_shape() {
+    let mut swarm = hovering_swarm(7);
+    swarm.multi_formation(vec![
+        (vec![0, 1, 2, 3], "circle".to_string(), [3.0, 0.0, 1.5], vec![1.5]),
+        (vec![4, 5, 6], "line".to_string(), [-3.0, 0.0, 1.0], vec![1.0]),
+    ]).unwrap();
+    steps(&mut swarm, 8.0);
+
+    for drone in &swarm.drones[..4] {
+        let radius = ((drone.pos[0] - 3.0).powi(2) + drone.pos[1].powi(2)).sqrt();
+        assert!((radius - 1.5).abs() < 0.05 && (drone.pos[2] - 1.5).abs() < 0.05, "circle drone at {:?}", drone.pos);
+    }
+    let mut xs: Vec<f32> = swarm.drones[4..].iter()
+        .map(|d| {
+            assert!(d.pos[1].abs() < 0.05 && (d.pos[2] - 1.0).abs() < 0.05, "line drone at {:?}", d.pos);
+            d.pos[0]
+        })
+        .collect();
+    xs.sort_by(f32::total_cmp);
+    for (x, expected) in xs.iter().zip([-4.0, -3.0, -2.0]) {
+        assert!((x - expected).abs() < 0.05, "line at {:?}", xs);
+    }
+
+    // A drone can't be in two formations at once
+    assert!(swarm.multi_formation(vec![
+        (vec![0, 1], "circle".to_string(), [0.0, 0.0, 1.0], vec![]),
+        (vec![1, 2], "line".to_string(), [0.0, 0.0, 1.0], vec![]),
+    ]).is_err());
+}
+
+#[test]
+fn out_of_bounds_policy_clamps_rejects_or_errors() {
+    let mut swarm = hovering_swarm(1);
+    swarm.goto(0, 2.0, 1.0, 1.5, 0.0).unwrap();
+    assert!(swarm.set_out_of_bounds_policy("ignore").is_err());
+
+    swarm.set_out_of_bounds_policy("reject").unwrap();
+    swarm.goto(0, 20.0, 1.0, 1.5, 0.0).unwrap();
+    assert_eq!(swarm.drones[0].target_pos, [2.0, 1.0, 1.5]);
+
+    swarm.set_out_of_bounds_policy("error").unwrap();
+    assert!(swarm.goto(0, 20.0, 1.0, 1.5, 0.0).is_err());
+    assert_eq!(swarm.drones[0].target_pos, [2.0, 1.0, 1.5]);
+
+    swarm.set_out_of_bounds_policy("clamp").unwrap();
+    swarm.goto(0, 20.0, 1.0, 1.5, 0.0).unwrap();
+    assert_eq!(swarm.drones[0].target_pos, [10.0, 1.0, 1.5]);
+}
+
+#[test]
+fn trails_hold_the_latest_positions_oldest_first() {
+    let mut swarm = hovering_swarm(2);
+    swarm.set_trail_length(5);
+    swarm.goto(0, 3.0, 0.0, 1.0, 0.0).unwrap();
+    let mut visited = Vec::new();
+    for _ in 0..12 {
+        swarm.step();
+        visited.push(swarm.drones[0].pos);
+    }
+    assert_eq!(swarm.get_trail(0), visited[7..].to_vec());
+
+    swarm.set_trail_length(2);
+    assert_eq!(swarm.get_trail(0), visited[10..].to_vec());
+    swarm.set_trail_length(0);
+    swarm.step();
+    assert!(swarm.get_trail(0).is_empty());
+}
+
+#[test]
+fn mode_velocity_limits_slow_landing_below_goto() {
+    let mut swarm = RustSwarm::new(2, 240);
+    swarm.takeoff_all(3.0);
+    steps(&mut swarm, 5.0);
+    swarm.set_mode_velocity_limit("landing", 0.3).unwrap();
+    swarm.set_mode_velocity_limit("goto", 1.5).unwrap();
+    assert!(swarm.set_mode_velocity_limit("sprint", 1.5).is_err());
+
+    // Both head for the floor from the same height
+    swarm.land(vec![0]);
+    let [x, y, _] = swarm.drones[1].pos;
+    swarm.goto(1, x, y, 0.05, 0.0).unwrap();
+    steps(&mut swarm, 1.0);
+    let landing = -swarm.drones[0].vel[2];
+    let diving = -swarm.drones[1].vel[2];
+    assert!((landing - 0.3).abs() < 0.02, "landing at {} m/s", landing);
+    assert!((diving - 1.5).abs() < 0.1, "goto at {} m/s", diving);
+
+    swarm.clear_mode_velocity_limits();
+    steps(&mut swarm, 0.5);
+    assert!(-swarm.drones[0].vel[2] > 0.5);
+}
+
+#[test]
+fn wave_puts_drones_half_a_wavelength_apart_in_antiphase() {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, 0.0, 0.0, 2.0, 0.0).unwrap();
+    swarm.goto(1, 2.0, 0.0, 2.0, 0.0).unwrap();
+    steps(&mut swarm, 5.0);
+    swarm.maneuver_wave([1.0, 0.0], 4.0, 0.5, 1.0, 2.0);
+    steps(&mut swarm, 4.0);
+
+    let mut swing = 0.0_f32;
+    for _ in 0..40 {
+        steps(&mut swarm, 0.1);
+        let [a, b] = [0, 1].map(|id| swarm.drones[id].pos[2] - 2.0);
+        assert!((a + b).abs() < 0.05, "heights {} and {} aren't opposite", a, b);
+        assert!(swarm.drones[1].pos[0] - swarm.drones[0].pos[0] > 1.95);
+        swing = swing.max(a.abs());
+    }
+    assert!(swing > 0.3, "wave only swung {}", swing);
+}
+
+#[test]
+fn formation_orientation_follows_a_line() {
+    let mut swarm = hovering_swarm(5);
+    swarm.formation_line([0.0, 0.0, 1.0], 1.0, "x", None).unwrap();
+    steps(&mut swarm, 6.0);
+    let (yaw, major, minor) = swarm.get_formation_orientation();
+    assert!(yaw.abs() < 0.05, "line along x reports yaw {}", yaw);
+    assert!((major - 4.0).abs() < 0.1 && minor < 0.1, "extents {} x {}", major, minor);
+
+    swarm.formation_line([0.0, 0.0, 1.0], 1.0, "y", None).unwrap();
+    steps(&mut swarm, 6.0);
+    let (yaw, _, _) = swarm.get_formation_orientation();
+    assert!((yaw.abs() - PI / 2.0).abs() < 0.05, "line along y reports yaw {}", yaw);
+}
+
+#[test]
+fn gps_dropout_lets_the_true_position_drift_off_target() {
+    let dropped_swarm = || {
+        let mut swarm = hovering_swarm(2);
+        swarm.set_gps_dropout(vec![0], true, 0.05);
+        swarm
+    };
+    let mut swarm = dropped_swarm();
+    let off_target = |swarm: &RustSwarm, id: usize| distance(swarm.drones[id].pos, swarm.drones[id].target_pos);
+    steps(&mut swarm, 5.0);
+    let early = off_target(&swarm, 0);
+    steps(&mut swarm, 5.0);
+    let late = off_target(&swarm, 0);
+    assert!(early > 0.15 && late > early + 0.15, "drift {} then {}", early, late);
+    assert!(off_target(&swarm, 1) < 0.02);
+
+    let mut again = dropped_swarm();
+    steps(&mut again, 10.0);
+    assert_eq!(again.drones[0].pos, swarm.drones[0].pos);
+
+    // Restoring GPS brings the drone back
+    swarm.set_gps_dropout(vec![0], false, 0.05);
+    steps(&mut swarm, 5.0);
+    assert!(off_target(&swarm, 0) < 0.02);
+}
+
+#[test]
+fn changing_the_physics_rate_mid_flight_keeps_the_trajectory() {
+    let cruising_swarm = || {
+        let mut swarm = hovering_swarm(1);
+        swarm.goto(0, 4.0, 2.0, 2.0, 1.0).unwrap();
+        steps(&mut swarm, 1.0);
+        swarm
+    };
+    let mut steady = cruising_swarm();
+    let mut switched = cruising_swarm();
+    assert!(switched.set_physics_hz(0).is_err());
+    switched.set_physics_hz(960).unwrap();
+
+    // No jump in velocity on the first step at the new rate
+    let vel = switched.drones[0].vel;
+    switched.step();
+    assert!(distance(vel, switched.drones[0].vel) < 0.05);
+
+    for _ in 0..(2.0 * 960.0) as usize - 1 {
+        switched.step();
+    }
+    steps(&mut steady, 2.0);
+    assert!((switched.sim_time - steady.sim_time).abs() < 1e-3);
+    let gap = distance(switched.drones[0].pos, steady.drones[0].pos);
+    assert!(gap < 0.02, "trajectories parted by {} m", gap);
+    assert!((switched.drones[0].yaw - steady.drones[0].yaw).abs() < 0.01);
+}
+
+#[test]
+fn monitor_rotation_swaps_a_low_drone_for_a_reserve() {
+    let mut swarm = hovering_swarm(4);
+    swarm.monitor_with_rotation(0.0, 0.0, 2.0, 20.0, 1);
+    steps(&mut swarm, 3.0);
+    assert_eq!(swarm.drones[3].mode, DroneMode::Hover);
+    assert!(swarm.drones[..3].iter().all(|d| d.mode == DroneMode::Monitor));
+    let slot_offset = (swarm.drones[1].monitor_angle - swarm.drones[0].monitor_angle).rem_euclid(2.0 * PI);
+    let slot_radius = swarm.drones[1].monitor_radius;
+
+    swarm.drones[1].battery = 10.0;
+    steps(&mut swarm, 1.0);
+    assert_eq!(swarm.drones[1].mode, DroneMode::Landing);
+    assert_eq!(swarm.drones[3].mode, DroneMode::Monitor);
+    // The reserve takes over the vacated slot where it has moved on to
+    assert_eq!(swarm.drones[3].monitor_radius, slot_radius);
+    let offset = (swarm.drones[3].monitor_angle - swarm.drones[0].monitor_angle).rem_euclid(2.0 * PI);
+    assert!((offset - slot_offset).abs() < 1e-3, "slot at {} instead of {}", offset, slot_offset);
+    assert_eq!(swarm.drones[0].mode, DroneMode::Monitor);
+    assert_eq!(swarm.drones[2].mode, DroneMode::Monitor);
+}
+
+#[test]
+fn playback_tracks_the_recorded_path() {
+    let mut swarm = hovering_swarm(2);
+    let start: Vec<[f32; 3]> = swarm.drones.iter().map(|d| d.pos).collect();
+    // 10 s at 10 fps: drone 0 slides 3 m along x and climbs 1 m, drone 1 holds still
+    let path = |t: f32| [start[0][0] + 0.3 * t, start[0][1], start[0][2] + 0.1 * t];
+    let frames: Vec<Vec<[f32; 3]>> = (0..=100).map(|k| vec![path(k as f32 / 10.0), start[1]]).collect();
+    assert!(swarm.play_recording(frames.clone(), 0.0).is_err());
+    swarm.play_recording(frames, 10.0).unwrap();
+    let start_time = swarm.sim_time;
+
+    let mut worst = 0.0_f32;
+    for _ in 0..(10.0 * 240.0) as usize {
+        swarm.step();
+        let t = swarm.sim_time - start_time;
+        if t > 1.0 {
+            worst = worst.max(distance(swarm.drones[0].pos, path(t)));
+        }
+    }
+    assert!(worst < 0.3, "fell {} m behind the recording", worst);
+    assert!(distance(swarm.drones[1].pos, start[1]) < 0.02);
+
+    // Holds the final frame once the recording runs out
+    steps(&mut swarm, 3.0);
+    assert!(distance(swarm.drones[0].pos, path(10.0)) < 0.02);
+}
+
+#[test]
+fn formations_handle_one_and_two_drones() {
+    let center = [1.0, -1.0, 2.0];
+    for kind in ["circle", "v", "grid", "line"] {
+        for n in [1, 2] {
+            let mut swarm = RustSwarm::new(n, 240);
+            match kind {
+                "circle" => swarm.formation_circle(center, 1.5, None),
+                "v" => swarm.formation_v(center, 1.0, None),
+                "grid" => swarm.formation_grid(center, 1.0, None),
+                _ => swarm.formation_line(center, 1.0, "x", None),
+            }.unwrap();
+            let targets: Vec<[f32; 3]> = swarm.drones.iter().map(|d| d.target_pos).collect();
+            assert!(targets.iter().all(|&t| swarm.config.clamp_target(t) == t), "{} {:?} out of bounds", kind, targets);
+
+            if n == 1 {
+                assert_eq!(targets[0], center, "lone drone in a {} goes to the center", kind);
+            } else {
+                let gap = distance(targets[0], targets[1]);
+                assert!((1.0 - 1e-4..=3.0 + 1e-4).contains(&gap), "{} pair {} apart", kind, gap);
+                assert!(targets.iter().all(|t| t[2] == center[2]));
+                // The V keeps its leader on the center; the others straddle it
+                let mid = [0, 1, 2].map(|i| (targets[0][i] + targets[1][i]) / 2.0);
+                let anchor = if kind == "v" { targets[0] } else { mid };
+                assert!(distance(anchor, center) < 1e-4, "{} pair {:?} off center", kind, targets);
+            }
+        }
+    }
+}
+
+#[test]
+fn panic_land_holds_the_upper_drone_until_the_lower_is_down() {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, 0.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, 0.2, 0.0, 2.5, 0.0).unwrap();
+    steps(&mut swarm, 6.0);
+
+    swarm.panic_land();
+    let mut lower_down = None;
+    for step in 0..(20.0 * 240.0) as usize {
+        swarm.step();
+        if lower_down.is_none() {
+            if swarm.drones[0].mode == DroneMode::Idle {
+                lower_down = Some(step);
+            } else {
+                assert!(swarm.drones[1].pos[2] > 2.3, "upper drone descended to {} onto the lower", swarm.drones[1].pos[2]);
+            }
+        }
+        if swarm.drones[1].mode == DroneMode::Idle {
+            assert!(lower_down.is_some_and(|down| down < step), "upper drone landed first");
+            return;
+        }
+    }
+    panic!("upper drone never landed (lower landed at step {:?})", lower_down);
+}
+
+#[test]
+fn health_margins_decide_when_an_edge_drone_is_unhealthy() {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, 9.5, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 10.0);
+    // The default envelope extends past the world bounds, so the edge of the world is fine
+    assert!(swarm.drones[0].healthy);
+
+    swarm.set_health_margins(9.4, 0.0, 5.0);
+    swarm.step();
+    assert!(!swarm.drones[0].healthy);
+    assert!(swarm.drones[1].healthy);
+
+    swarm.set_health_margins(9.6, 0.0, 5.0);
+    swarm.step();
+    assert!(swarm.drones[0].healthy);
+    swarm.set_health_margins(9.6, 1.2, 5.0);
+    swarm.step();
+    assert!(!swarm.drones[0].healthy);
+}
+
+#[test]
+fn drone_speed_multipliers_change_how_far_drones_get() {
+    let mut swarm = hovering_swarm(4);
+    swarm.set_drone_speed(0, 0.25);
+    swarm.set_group_speed(vec![2, 3], 0.25);
+    let start: Vec<[f32; 3]> = swarm.drones.iter().map(|d| d.pos).collect();
+    for (id, p) in start.iter().enumerate() {
+        swarm.goto(id, p[0] + 6.0, p[1], p[2], 0.0).unwrap();
+    }
+    steps(&mut swarm, 1.5);
+
+    let covered: Vec<f32> = swarm.drones.iter().map(|d| d.pos[0] - start[d.id][0]).collect();
+    assert!(covered[1] > 3.0 * covered[0], "full speed {} vs quarter speed {}", covered[1], covered[0]);
+    assert!((covered[2] - covered[0]).abs() < 1e-3 && (covered[3] - covered[0]).abs() < 1e-3);
+}
+
+#[test]
+fn stuck_drones_are_the_ones_pinned_away_from_their_target() {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, -3.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, -3.0, 3.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 6.0);
+
+    // A wall straight across drone 0's path; drone 1 flies clear of it
+    swarm.add_obstacle(0.0, 0.0, 1.0, 5.0);
+    swarm.goto(0, 3.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, 3.0, 3.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 4.0);
+    assert!(swarm.drones[0].pos[0] < -0.9, "drone 0 got through to {:?}", swarm.drones[0].pos);
+    assert!(swarm.get_stuck_drones(1.0, 3.0).is_empty());
+
+    steps(&mut swarm, 4.0);
+    assert_eq!(swarm.get_stuck_drones(1.0, 3.0), vec![0]);
+    assert!(distance(swarm.drones[1].pos, swarm.drones[1].target_pos) < 0.05);
+}
+
+#[test]
+fn freeze_except_only_steps_the_listed_drones() {
+    let mut swarm = hovering_swarm(3);
+    swarm.freeze_except(vec![0]);
+    let frozen: Vec<([f32; 3], [f32; 3])> = swarm.drones.iter().map(|d| (d.pos, d.vel)).collect();
+    for id in 0..3 {
+        swarm.goto(id, 2.0, id as f32, 1.5, 0.0).unwrap();
+    }
+    steps(&mut swarm, 1.0);
+    assert!(distance(swarm.drones[0].pos, frozen[0].0) > 0.5);
+    for id in [1, 2] {
+        assert_eq!((swarm.drones[id].pos, swarm.drones[id].vel), frozen[id]);
+    }
+
+    swarm.unfreeze_all();
+    steps(&mut swarm, 1.0);
+    assert!(distance(swarm.drones[1].pos, frozen[1].0) > 0.5);
+}
+
+#[test]
+fn all_landed_objective_completes_once_every_drone_is_down() {
+    let mut swarm = hovering_swarm(3);
+    swarm.add_objective("down", "all_landed", vec![]).unwrap();
+    assert!(swarm.add_objective("up", "all_flying", vec![]).is_err());
+    swarm.step();
+    assert_eq!(swarm.get_objective_status(), vec![("down".to_string(), false)]);
+
+    swarm.land(vec![0, 1]);
+    steps(&mut swarm, 5.0);
+    assert!(swarm.drones[..2].iter().all(|d| d.mode == DroneMode::Idle));
+    assert_eq!(swarm.get_objective_status(), vec![("down".to_string(), false)]);
+
+    swarm.land(vec![2]);
+    while swarm.drones[2].mode != DroneMode::Idle {
+        assert!(!swarm.get_objective_status()[0].1);
+        swarm.step();
+    }
+    swarm.step();
+    assert_eq!(swarm.get_objective_status(), vec![("down".to_string(), true)]);
+}
+
+#[test]
+fn field_following_drones_climb_to_the_peak() {
+    let mut swarm = hovering_swarm(3);
+    let peak = [2.0, 1.0];
+    let resolution = 21;
+    let grid: Vec<f32> = (0..resolution * resolution)
+        .map(|k| {
+            let (x, y) = ((k % resolution) as f32 * 0.5 - 5.0, (k / resolution) as f32 * 0.5 - 5.0);
+            10.0 * (-((x - peak[0]).powi(2) + (y - peak[1]).powi(2)) / 8.0).exp()
+        })
+        .collect();
+    assert!(swarm.set_potential_field(grid.clone(), 20, ([-5.0, -5.0], [5.0, 5.0])).is_err());
+    swarm.set_potential_field(grid, resolution, ([-5.0, -5.0], [5.0, 5.0])).unwrap();
+
+    let to_peak = |d: &Drone| ((d.pos[0] - peak[0]).powi(2) + (d.pos[1] - peak[1]).powi(2)).sqrt();
+    let before: Vec<f32> = swarm.drones.iter().map(to_peak).collect();
+    let altitude: Vec<f32> = swarm.drones.iter().map(|d| d.pos[2]).collect();
+    swarm.field_follow(vec![0, 1, 2], 1.0);
+    steps(&mut swarm, 20.0);
+    for drone in &swarm.drones {
+        assert!(to_peak(drone) < 0.5 && to_peak(drone) < before[drone.id], "drone {} ended {} from the peak", drone.id, to_peak(drone));
+        assert!((drone.pos[2] - altitude[drone.id]).abs() < 0.05);
+    }
+}
+
+#[test]
+fn roles_place_drones_at_their_rotated_offsets() {
+    let mut swarm = hovering_swarm(4);
+    swarm.set_roles(vec![(0, [0.0, 0.0, 0.0]), (2, [1.0, 0.0, 0.5]), (3, [0.0, -2.0, 0.0]), (9, [1.0, 1.0, 1.0])]);
+    swarm.apply_roles([1.0, 1.0, 1.5], PI / 2.0).unwrap();
+    steps(&mut swarm, 8.0);
+
+    // A quarter turn maps body +x to world +y and body -y to world +x
+    let expected = [(0, [1.0, 1.0, 1.5]), (2, [1.0, 2.0, 2.0]), (3, [3.0, 1.0, 1.5])];
+    for (id, slot) in expected {
+        assert!(distance(swarm.drones[id].target_pos, slot) < 1e-5, "drone {} sent to {:?}", id, swarm.drones[id].target_pos);
+        assert!(distance(swarm.drones[id].pos, slot) < 0.02);
+        assert!((swarm.drones[id].yaw - PI / 2.0).abs() < 0.02);
+    }
+    assert_eq!(swarm.drones[1].mode, DroneMode::Hover);
+}
+
+#[test]
+fn optimal_split_travels_no_further_than_a_greedy_split() {
+    let mut swarm = RustSwarm::new(6, 240);
+    for (drone, x) in swarm.drones.iter_mut().zip([3.0, 2.5, 2.4, 4.9, 4.8, -1.0]) {
+        drone.pos = [x, 0.0, 1.0];
+    }
+    let targets = [[5.0, 0.0, 1.0], [-1.0, 0.0, 1.0]];
+
+    // Greedy: each drone in id order takes its nearest target that still has room
+    let mut room = [3, 3];
+    let greedy: f32 = swarm.drones.iter()
+        .map(|d| {
+            let k = (0..2).filter(|&k| room[k] > 0)
+                .min_by(|&a, &b| distance(d.pos, targets[a]).total_cmp(&distance(d.pos, targets[b])))
+                .unwrap();
+            room[k] -= 1;
+            distance(d.pos, targets[k])
+        })
+        .sum();
+
+    let assignment = swarm.optimal_split(targets.to_vec(), 0, 0.0).unwrap();
+    assert_eq!(assignment.iter().filter(|&&k| k == 0).count(), 3);
+    let optimal: f32 = swarm.drones.iter().map(|d| distance(d.pos, targets[assignment[d.id]])).sum();
+    assert!(optimal <= greedy, "optimal {} vs greedy {}", optimal, greedy);
+    assert!((optimal - 9.2).abs() < 1e-3, "optimal split travels {}", optimal);
+    assert!(swarm.optimal_split(Vec::new(), 0, 1.0).is_err());
+}
+
+#[test]
+fn wind_shear_drifts_high_and_low_drones_apart() {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, 0.0, 0.0, 0.5, 0.0).unwrap();
+    swarm.goto(1, 0.0, 2.0, 4.0, 0.0).unwrap();
+    steps(&mut swarm, 6.0);
+    assert!(swarm.set_wind_profile(vec![0.0, 5.0], vec![[1.0, 0.0, 0.0]]).is_err());
+    swarm.set_wind_profile(vec![0.0, 1.0, 3.0, 5.0], vec![[2.0, 0.0, 0.0], [2.0, 0.0, 0.0], [-2.0, 0.0, 0.0], [-2.0, 0.0, 0.0]]).unwrap();
+
+    // Idle in velocity mode so the drones drift with the air they sit in
+    swarm.velocity(0, 0.0, 0.0, 0.0, 0.0);
+    swarm.velocity(1, 0.0, 0.0, 0.0, 0.0);
+    steps(&mut swarm, 6.0);
+    assert!(swarm.drones[0].pos[0] > 0.1, "low drone at {:?}", swarm.drones[0].pos);
+    assert!(swarm.drones[1].pos[0] < -0.1, "high drone at {:?}", swarm.drones[1].pos);
+}
+
+#[test]
+fn smooth_goto_starts_and_ends_at_rest_on_time() {
+    let mut swarm = hovering_swarm(1);
+    let start = swarm.drones[0].pos;
+    let end = [start[0] + 3.0, start[1] - 1.0, start[2] + 1.0];
+    swarm.goto_smooth(0, end, 4.0, 0.0).unwrap();
+
+    // The moving target sampled every step, from the start point
+    let mut path = vec![start];
+    for _ in 0..(4.0 * 240.0) as usize {
+        swarm.step();
+        path.push(swarm.drones[0].target_pos);
+    }
+    assert!(distance(*path.last().unwrap(), end) < 1e-4);
+
+    let dt = 1.0 / 240.0;
+    let vel = |k: usize| distance(path[k + 1], path[k]) / dt;
+    let accel = |k: usize| (0..3).map(|i| (path[k + 2][i] - 2.0 * path[k + 1][i] + path[k][i]) / (dt * dt)).map(|a| a * a).sum::<f32>().sqrt();
+    let last = path.len() - 3;
+    assert!(vel(0) < 0.01 && vel(last + 1) < 0.01, "endpoint speeds {} and {}", vel(0), vel(last + 1));
+    assert!(accel(0) < 0.1 && accel(last) < 0.1, "endpoint accelerations {} and {}", accel(0), accel(last));
+    // Peak speed of a minimum-jerk move: 1.875 * distance / duration, at the midpoint
+    let peak = (0..path.len() - 1).map(vel).fold(0.0, f32::max);
+    assert!((peak - 1.875 * distance(start, end) / 4.0).abs() < 0.01, "peak speed {}", peak);
+
+    steps(&mut swarm, 2.0);
+    assert!(distance(swarm.drones[0].pos, end) < 0.02);
+}
+
+#[test]
+fn feet_are_converted_at_the_api_boundary() {
+    let mut swarm = hovering_swarm(1);
+    assert!(swarm.set_units("furlong").is_err());
+    swarm.set_units("ft").unwrap();
+    swarm.goto(0, 10.0, -5.0, 6.0, 0.0).unwrap();
+    let target = swarm.drones[0].target_pos;
+    assert!(distance(target, [3.048, -1.524, 1.8288]) < 1e-5, "internal target {:?}", target);
+
+    steps(&mut swarm, 1.0);
+    let vel = swarm.get_states()[0].vel;
+    assert!(distance(vel, swarm.drones[0].vel.map(|v| v / 0.3048)) < 1e-5 && vel[0] > 1.0);
+
+    steps(&mut swarm, 7.0);
+    let pos = swarm.get_states()[0].pos;
+    assert!(distance(pos, [10.0, -5.0, 6.0]) < 0.05, "reported {:?} ft", pos);
+}
+
+/// Offsets of each drone from its start after flying 3 m along x for 1 s, with personality
+/// variation seeded by `seed` (None leaves it off)
+fn personality_run(seed: Option<u64>) -> Vec<[f32; 3]> {
+    let mut swarm = hovering_swarm(2);
+    if let Some(seed) = seed {
+        swarm.set_personality_variation(true, 0.3, seed);
+    }
+    let start: Vec<[f32; 3]> = swarm.drones.iter().map(|d| d.pos).collect();
+    for (id, p) in start.iter().enumerate() {
+        swarm.goto(id, p[0] + 3.0, p[1], p[2], 1.0).unwrap();
+    }
+    steps(&mut swarm, 1.0);
+    swarm.drones.iter().map(|d| [0, 1, 2].map(|i| d.pos[i] - start[d.id][i])).collect()
+}
+
+#[test]
+fn personality_variation_makes_drones_differ_reproducibly() {
+    let uniform = personality_run(None);
+    assert!(distance(uniform[0], uniform[1]) < 1e-5);
+
+    let varied = personality_run(Some(42));
+    assert!(distance(varied[0], varied[1]) > 0.01, "drones still fly alike: {:?}", varied);
+    assert_eq!(personality_run(Some(42)), varied);
+    assert_ne!(personality_run(Some(43)), varied);
+}
+
+#[test]
+fn half_time_scale_group_makes_less_progress() {
+    let mut swarm = hovering_swarm(4);
+    swarm.set_time_scale(vec![2, 3], 0.5);
+    let start: Vec<[f32; 3]> = swarm.drones.iter().map(|d| d.pos).collect();
+    for (id, p) in start.iter().enumerate() {
+        swarm.goto(id, p[0] + 4.0, p[1], p[2], 0.0).unwrap();
+    }
+    steps(&mut swarm, 1.5);
+    let progress: Vec<f32> = swarm.drones.iter().map(|d| d.pos[0] - start[d.id][0]).collect();
+    assert!(progress[2] < 0.7 * progress[0], "slow group {} vs full speed {}", progress[2], progress[0]);
+    assert!((progress[0] - progress[1]).abs() < 1e-4 && (progress[2] - progress[3]).abs() < 1e-4);
+
+    // Half as many steps at full scale land where the slowed group is
+    let mut reference = hovering_swarm(1);
+    let p = reference.drones[0].pos;
+    reference.goto(0, p[0] + 4.0, p[1], p[2], 0.0).unwrap();
+    steps(&mut reference, 0.75);
+    assert!((reference.drones[0].pos[0] - p[0] - progress[2]).abs() < 0.02);
+}
+
+#[test]
+fn shape_match_error_separates_a_formed_shape_from_a_scrambled_swarm() {
+    let mut swarm = hovering_swarm(6);
+    let shape: Vec<[f32; 3]> = (0..6)
+        .map(|k| {
+            let angle = 2.0 * PI * k as f32 / 6.0;
+            [2.0 * angle.cos(), 2.0 * angle.sin(), 1.5]
+        })
+        .collect();
+    for (id, p) in shape.iter().enumerate() {
+        swarm.goto(id, p[0], p[1], p[2], 0.0).unwrap();
+    }
+    steps(&mut swarm, 8.0);
+    let formed = swarm.shape_match_error(shape.clone());
+    assert!(formed < 0.02, "formed shape error {}", formed);
+
+    // Scramble: the same drones strung out along a line off to one side
+    swarm.formation_line([-3.0, 4.0, 1.0], 1.2, "x", None).unwrap();
+    steps(&mut swarm, 8.0);
+    let scrambled = swarm.shape_match_error(shape);
+    assert!(scrambled > 0.5 && scrambled > 20.0 * formed, "scrambled error {}", scrambled);
+}
+
+#[test]
+fn command_log_records_commands_with_time_and_args() {
+    let mut swarm = RustSwarm::new(2, 240);
+    swarm.takeoff(vec![0], 1.0);
+    swarm.set_command_logging(true);
+    steps(&mut swarm, 0.5);
+    swarm.takeoff(vec![0, 1], 1.5);
+    steps(&mut swarm, 2.5);
+    swarm.goto(1, 2.0, -1.0, 1.5, 0.5).unwrap();
+    swarm.step();
+
+    let log = swarm.get_command_log();
+    assert_eq!(log.len(), 2, "log {:?}", log);
+    assert_eq!((log[0].1.as_str(), log[0].2.clone()), ("takeoff", vec![0.0, 1.0, 1.5]));
+    assert!((log[0].0 - 0.5).abs() < 1e-4);
+    assert_eq!((log[1].1.as_str(), log[1].2.clone()), ("goto", vec![1.0, 2.0, -1.0, 1.5, 0.5]));
+    assert!((log[1].0 - 3.0).abs() < 1e-4);
+
+    swarm.set_command_logging(false);
+    assert!(swarm.get_command_log().is_empty());
+}
+
+/// Tightest turn radius (speed over heading rate, while above 0.3 m/s) and widest sideways
+/// excursion of drone 0 as it reverses from cruising along +x
+fn reversal_turn(min_turn_radius: f32) -> (f32, f32) {
+    let mut swarm = hovering_swarm(1);
+    swarm.set_min_turn_radius(min_turn_radius);
+    let [_, y, z] = swarm.drones[0].pos;
+    swarm.goto(0, 8.0, y, z, 0.0).unwrap();
+    steps(&mut swarm, 2.0);
+    swarm.goto(0, -8.0, y, z, 0.0).unwrap();
+
+    let (mut tightest, mut excursion) = (f32::INFINITY, 0.0_f32);
+    let mut heading = swarm.drones[0].vel[1].atan2(swarm.drones[0].vel[0]);
+    for _ in 0..(6.0 * 240.0) as usize {
+        swarm.step();
+        let vel = swarm.drones[0].vel;
+        let speed = (vel[0] * vel[0] + vel[1] * vel[1]).sqrt();
+        let next = vel[1].atan2(vel[0]);
+        let turn = (next - heading + PI).rem_euclid(2.0 * PI) - PI;
+        if speed > 0.3 && turn.abs() > 1e-6 {
+            tightest = tightest.min(speed / (turn.abs() * 240.0));
+        }
+        heading = next;
+        excursion = excursion.max((swarm.drones[0].pos[1] - y).abs());
+    }
+    (tightest, excursion)
+}
+
+#[test]
+fn min_turn_radius_makes_reversals_arc() {
+    let (_, pivot) = reversal_turn(0.0);
+    assert!(pivot < 0.01, "holonomic reversal strayed {} m sideways", pivot);
+
+    let (tightest, excursion) = reversal_turn(1.0);
+    assert!(tightest >= 0.99, "turned on a {} m radius", tightest);
+    assert!(excursion > 1.9, "arc only {} m wide", excursion);
+}
+
+#[test]
+fn benchmark_runs_and_reports_plausible_timings() {
+    let swarm = RustSwarm::new(1, 240);
+    let result = swarm.benchmark(50, 100).unwrap();
+    assert_eq!((result.num_drones, result.steps), (50, 100));
+    assert!(result.total_time > 0.0 && result.total_time < 60.0);
+    assert!((result.steps_per_sec * result.total_time - 100.0).abs() < 1e-6);
+    assert!((result.us_per_drone_step - result.total_time * 1e6 / 5000.0).abs() < 1e-6);
+    assert!(result.threads >= 1);
+}
+
+#[test]
+fn faulted_drone_climbs_to_the_safe_altitude_before_landing() {
+    let mut swarm = hovering_swarm(2);
+    swarm.set_fault_response(true, 3.0);
+    swarm.drones[0].battery = 0.0;
+    swarm.step();
+    assert_eq!(swarm.drones[0].fault_phase, Some(FaultPhase::Climb));
+
+    let mut peak = 0.0_f32;
+    while swarm.drones[0].mode != DroneMode::Landing {
+        assert!(swarm.drones[0].pos[2] >= peak - 0.01, "sank to {} before reaching the safe altitude", swarm.drones[0].pos[2]);
+        peak = peak.max(swarm.drones[0].pos[2]);
+        assert!(swarm.sim_time < 15.0, "never finished climbing");
+        swarm.step();
+    }
+    assert!(peak >= 2.9, "started the descent from {}", peak);
+    steps(&mut swarm, 10.0);
+    assert_eq!(swarm.drones[0].mode, DroneMode::Idle);
+    assert_eq!(swarm.drones[1].mode, DroneMode::Hover);
+}
+
+#[test]
+fn triangular_geofence_projects_targets_and_drones_onto_its_edge() {
+    let mut swarm = hovering_swarm(1);
+    assert!(swarm.set_geofence_polygon(vec![[0.0, 0.0], [1.0, 0.0]], 0.0, 3.0).is_err());
+    swarm.set_geofence_polygon(vec![[-2.0, -2.0], [4.0, -2.0], [-2.0, 4.0]], 0.0, 3.0).unwrap();
+    let inside = |p: [f32; 3]| p[0] >= -2.0 - 1e-4 && p[1] >= -2.0 - 1e-4 && p[0] + p[1] <= 2.0 + 1e-4;
+
+    // Beyond the hypotenuse x + y = 2: the nearest edge point is (1, 1)
+    swarm.goto(0, 3.0, 3.0, 1.5, 0.0).unwrap();
+    assert!(distance(swarm.drones[0].target_pos, [1.0, 1.0, 1.5]) < 1e-4, "target {:?}", swarm.drones[0].target_pos);
+    for _ in 0..(6.0 * 240.0) as usize {
+        swarm.step();
+        assert!(inside(swarm.drones[0].pos), "drone left the fence at {:?}", swarm.drones[0].pos);
+    }
+    assert!(distance(swarm.drones[0].pos, [1.0, 1.0, 1.5]) < 0.02);
+
+    // Flying straight at the edge, the drone is held on it
+    swarm.velocity(0, 1.0, 1.0, 0.0, 0.0);
+    for _ in 0..240 {
+        swarm.step();
+        assert!(inside(swarm.drones[0].pos), "drone left the fence at {:?}", swarm.drones[0].pos);
+    }
+}
+
+#[test]
+fn deconflict_vertical_separates_stacked_targets() {
+    let mut swarm = hovering_swarm(4);
+    swarm.goto(0, 0.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, 0.05, 0.0, 1.3, 0.0).unwrap();
+    swarm.goto(2, 0.0, 0.05, 1.1, 0.0).unwrap();
+    swarm.goto(3, 3.0, 0.0, 1.1, 0.0).unwrap();
+
+    let moved = swarm.deconflict_vertical(0.8);
+    assert_eq!(moved, vec![1, 2]);
+    let targets: Vec<[f32; 3]> = swarm.drones.iter().map(|d| d.target_pos).collect();
+    for a in 0..4 {
+        for b in a + 1..4 {
+            let horizontal = (targets[a][0] - targets[b][0]).hypot(targets[a][1] - targets[b][1]);
+            if horizontal < 0.5 {
+                let vertical = (targets[a][2] - targets[b][2]).abs();
+                assert!(vertical >= 0.8 - 1e-4, "targets {} and {} stacked {} apart", a, b, vertical);
+            }
+        }
+    }
+    assert_eq!(targets[0], [0.0, 0.0, 1.0]);
+    assert_eq!(targets[3], [3.0, 0.0, 1.1]);
+}
+
+#[test]
+fn lipo_curve_drops_faster_near_empty() {
+    let mut swarm = hovering_swarm(1);
+    assert!(swarm.set_discharge_curve("nimh").is_err());
+    swarm.set_discharge_curve("lipo").unwrap();
+
+    // 1% of energy per update; report after each
+    let mut reported = vec![swarm.get_states()[0].battery];
+    for _ in 0..100 {
+        swarm.update_batteries(60.0);
+        reported.push(swarm.get_states()[0].battery);
+    }
+    assert_eq!((reported[0], reported[100]), (100.0, 0.0));
+    assert!(reported.windows(2).all(|w| w[1] <= w[0]));
+    let drop = |from: usize| reported[from] - reported[from + 10];
+    // The last 10% of energy takes far more than 10 reported points, the plateau far less
+    assert!(drop(90) > 30.0, "final drop {}", drop(90));
+    assert!(drop(40) < 10.0 && drop(90) > 3.0 * drop(40), "plateau drop {}", drop(40));
+    assert!(reported[50] > 50.0);
+
+    swarm.set_discharge_curve("linear").unwrap();
+    swarm.drones[0].battery = 5.0;
+    assert_eq!(swarm.get_states()[0].battery, 5.0);
+}
+
+/// Largest horizontal distance from its landing spot over two drones landing 0.3 m apart under
+/// collision avoidance, after 10 s
+fn landing_offset(landing_avoidance: &str) -> f32 {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, 0.0, 0.0, 1.5, 0.0).unwrap();
+    swarm.goto(1, 0.3, 0.0, 1.5, 0.0).unwrap();
+    steps(&mut swarm, 5.0);
+    swarm.set_collision_avoidance(true, 1.0, 1.0, 0.0);
+    swarm.set_landing_avoidance(landing_avoidance, 1.0).unwrap();
+    swarm.land_all();
+    steps(&mut swarm, 10.0);
+    assert!(swarm.drones.iter().all(|d| d.mode == DroneMode::Idle));
+    [[0.0, 0.0], [0.3, 0.0]].iter().zip(&swarm.drones)
+        .map(|(spot, d): (&[f32; 2], _)| (d.pos[0] - spot[0]).hypot(d.pos[1] - spot[1]))
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn vertical_only_landing_avoidance_lets_neighbors_touch_down_on_their_spots() {
+    let full = landing_offset("full");
+    let vertical_only = landing_offset("vertical_only");
+    assert!(vertical_only < 0.05, "{vertical_only}");
+    assert!(full > 2.0 * vertical_only + 0.05, "{full} vs {vertical_only}");
+}
+
+#[test]
+fn spread_grows_when_scattered_and_shrinks_when_clustered() {
+    let mut swarm = hovering_swarm(8);
+    let start = swarm.get_spread();
+    swarm.formation_circle([0.0, 0.0, 1.5], 4.0, None).unwrap();
+    steps(&mut swarm, 8.0);
+    let scattered = swarm.get_spread();
+    swarm.cluster([0.0, 0.0, 1.5], 0.3).unwrap();
+    steps(&mut swarm, 8.0);
+    let clustered = swarm.get_spread();
+    assert!(scattered > start + 2.0, "{start} -> {scattered}");
+    assert!(clustered < scattered - 2.0, "{scattered} -> {clustered}");
+}
+
+#[test]
+fn tracked_point_keeps_the_yaw_on_it_while_flying_past() {
+    let mut swarm = hovering_swarm(1);
+    swarm.goto(0, -2.0, 3.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 5.0);
+    swarm.track_point(0, [0.0, 0.0, 1.0]);
+    steps(&mut swarm, 2.0);
+    swarm.goto(0, 2.0, 3.0, 1.0, 0.0).unwrap();
+    let pointing_error = |d: &Drone| wrap_angle(d.yaw - (-d.pos[1]).atan2(-d.pos[0])).abs();
+    let mut worst = 0.0f32;
+    for _ in 0..(6.0 * 240.0) as usize {
+        swarm.step();
+        worst = worst.max(pointing_error(&swarm.drones[0]));
+    }
+    assert!(swarm.drones[0].pos[0] > 1.9);
+    assert!(worst < 0.35, "{worst}");
+    assert!(pointing_error(&swarm.drones[0]) < 0.02);
+}
+
+#[test]
+fn paused_drone_holds_while_others_move_then_resumes_its_goto() {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, 1.0, 1.0, 1.5, 0.0).unwrap();
+    swarm.goto(1, -1.0, -1.0, 1.5, 0.0).unwrap();
+    steps(&mut swarm, 0.5);
+    swarm.pause_drone(0);
+    let held = swarm.drones[0].pos;
+    let other = swarm.drones[1].pos;
+    steps(&mut swarm, 2.0);
+    assert_eq!(swarm.drones[0].pos, held);
+    assert!(distance(swarm.drones[1].pos, other) > 0.5);
+    swarm.resume_drone(0);
+    steps(&mut swarm, 6.0);
+    assert!(distance(swarm.drones[0].pos, [1.0, 1.0, 1.5]) < 0.05);
+}
+
+#[test]
+fn seek_frame_reproduces_recorded_positions_and_yaws() {
+    let mut swarm = hovering_swarm(3);
+    swarm.goto(0, 1.0, 1.0, 1.5, 1.0).unwrap();
+    swarm.velocity(1, 0.4, -0.2, 0.1, 0.5);
+    swarm.start_recording(1);
+    let mut poses = vec![swarm.drones.iter().map(|d| (d.pos, d.yaw)).collect::<Vec<_>>()];
+    for _ in 0..240 {
+        swarm.step();
+        poses.push(swarm.drones.iter().map(|d| (d.pos, d.yaw)).collect());
+    }
+    swarm.stop_recording();
+    assert_eq!(swarm.recording_length(), poses.len());
+    for index in [120, 0, 240, 60] {
+        swarm.seek_frame(index).unwrap();
+        let seeked: Vec<_> = swarm.drones.iter().map(|d| (d.pos, d.yaw)).collect();
+        assert_eq!(seeked, poses[index], "frame {index}");
+    }
+}
+
+#[test]
+fn synchronized_landing_touches_down_together() {
+    let mut swarm = hovering_swarm(3);
+    for (id, z) in [0.8, 1.6, 2.4].into_iter().enumerate() {
+        swarm.goto(id, id as f32, 0.0, z, 0.0).unwrap();
+    }
+    steps(&mut swarm, 5.0);
+    swarm.land_synchronized(4.0, 1.0);
+    let mut touchdowns = [None; 3];
+    for step in 0..(8.0 * 240.0) as usize {
+        swarm.step();
+        for (touchdown, drone) in touchdowns.iter_mut().zip(&swarm.drones) {
+            if touchdown.is_none() && drone.mode == DroneMode::Idle {
+                *touchdown = Some(step as f32 / 240.0);
+            }
+        }
+    }
+    let times: Vec<f32> = touchdowns.iter().map(|t| t.expect("every drone lands")).collect();
+    let first = times.iter().copied().fold(f32::INFINITY, f32::min);
+    let last = times.iter().copied().fold(0.0, f32::max);
+    assert!(last - first < 0.1, "{times:?}");
+    assert!((first - 4.0).abs() < 0.1, "{times:?}");
+}
+
+/// Closest pair distance between the drones' targets
+fn closest_targets(swarm: &RustSwarm) -> f32 {
+    let targets: Vec<[f32; 3]> = swarm.drones.iter().map(|d| d.target_pos).collect();
+    targets.iter().enumerate()
+        .flat_map(|(i, &a)| targets[i + 1..].iter().map(move |&b| distance(a, b)))
+        .fold(f32::INFINITY, f32::min)
+}
+
+#[test]
+fn dense_circle_is_expanded_to_the_drone_diameter() {
+    let mut swarm = hovering_swarm(16);
+    let diameter = 2.0 * swarm.drones[0].radius;
+    swarm.set_formation_spacing_enforcement(false);
+    swarm.formation_circle([0.0, 0.0, 1.5], 0.2, None).unwrap();
+    assert!(closest_targets(&swarm) < diameter);
+    swarm.set_formation_spacing_enforcement(true);
+    swarm.formation_circle([0.0, 0.0, 1.5], 0.2, None).unwrap();
+    assert!(closest_targets(&swarm) >= diameter, "{} < {diameter}", closest_targets(&swarm));
+}
+
+/// Steepest rise (m/s^2) and final value of x velocity over 3 s after a 2 m/s step command
+fn velocity_step_ramp(swarm: &mut RustSwarm) -> (f32, f32) {
+    swarm.velocity(0, 2.0, 0.0, 0.0, 0.0);
+    let dt = 1.0 / 240.0;
+    let mut peak = 0.0f32;
+    let mut previous = swarm.drones[0].vel[0];
+    for _ in 0..(3.0 * 240.0) as usize {
+        swarm.step();
+        let vel = swarm.drones[0].vel[0];
+        peak = peak.max((vel - previous) / dt);
+        previous = vel;
+    }
+    (peak, previous)
+}
+
+#[test]
+fn dynamics_limits_ramp_a_velocity_step_no_faster_than_max_accel() {
+    let (unlimited, _) = velocity_step_ramp(&mut hovering_swarm(1));
+    let mut swarm = hovering_swarm(1);
+    swarm.set_dynamics_limits(1.0, 1000.0);
+    let (peak, settled) = velocity_step_ramp(&mut swarm);
+    assert!(unlimited > 2.0, "{unlimited}");
+    assert!(peak <= 1.0 + 1e-3, "{peak}");
+    assert!(settled > 1.5, "{settled}");
+}
+
+/// Variance of the drones' nearest-neighbor distances over their squared mean, so layouts of
+/// different size compare by evenness alone
+fn nearest_neighbor_dispersion(swarm: &RustSwarm) -> f32 {
+    let gaps: Vec<f32> = swarm.drones.iter()
+        .map(|a| swarm.drones.iter().filter(|b| b.id != a.id).map(|b| distance(a.pos, b.pos)).fold(f32::INFINITY, f32::min))
+        .collect();
+    let mean = gaps.iter().sum::<f32>() / gaps.len() as f32;
+    gaps.iter().map(|g| (g - mean).powi(2)).sum::<f32>() / gaps.len() as f32 / (mean * mean)
+}
+
+#[test]
+fn redistribute_evens_out_a_clustered_swarm() {
+    let mut swarm = hovering_swarm(9);
+    let clumped = [[0.0, 0.0], [0.3, 0.1], [0.1, 0.4], [0.5, 0.5], [-0.3, 0.2], [2.5, 2.5], [2.8, 2.4], [-2.5, 2.0], [1.0, -2.5]];
+    for (id, [x, y]) in clumped.into_iter().enumerate() {
+        swarm.goto(id, x, y, 1.5, 0.0).unwrap();
+    }
+    steps(&mut swarm, 6.0);
+    let clustered = nearest_neighbor_dispersion(&swarm);
+    swarm.redistribute([-3.0, -3.0, 1.5], [3.0, 3.0, 1.5]).unwrap();
+    steps(&mut swarm, 8.0);
+    let spread = nearest_neighbor_dispersion(&swarm);
+    assert!(spread < clustered / 2.0, "{clustered} -> {spread}");
+}
+
+/// Smallest gap between a drone crossing x = -3..3 and an obstacle moving across its path so
+/// both would meet at the origin, under collision avoidance with `lookahead`
+fn moving_obstacle_clearance(lookahead: f32) -> f32 {
+    let mut swarm = hovering_swarm(1);
+    swarm.goto(0, -3.0, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 6.0);
+    swarm.set_collision_avoidance(true, 0.5, 1.0, lookahead);
+    swarm.add_moving_obstacle([0.0, -3.0], [0.0, 1.0], 0.3, 3.0);
+    swarm.velocity(0, 1.0, 0.0, 0.0, 0.0);
+    let mut clearance = f32::INFINITY;
+    for step in 0..(6.0 * 240.0) as usize {
+        swarm.step();
+        let obstacle = [0.0, -3.0 + step as f32 / 240.0];
+        let d = &swarm.drones[0];
+        clearance = clearance.min((d.pos[0] - obstacle[0]).hypot(d.pos[1] - obstacle[1]) - 0.3 - d.radius);
+    }
+    clearance
+}
+
+#[test]
+fn lookahead_steers_around_a_moving_obstacle() {
+    assert!(moving_obstacle_clearance(0.0) < 0.01);
+    assert!(moving_obstacle_clearance(1.0) > 0.2);
+}
+
+#[test]
+fn mode_histogram_follows_takeoff_into_hover() {
+    let mut swarm = RustSwarm::new(5, 240);
+    assert_eq!(swarm.mode_histogram(), HashMap::from([("idle".to_string(), 5)]));
+    swarm.takeoff_all(1.0);
+    assert_eq!(swarm.mode_histogram(), HashMap::from([("takeoff".to_string(), 5)]));
+    steps(&mut swarm, 5.0);
+    assert_eq!(swarm.mode_histogram(), HashMap::from([("hover".to_string(), 5)]));
+}
+
+#[test]
+fn converging_drones_warn_with_a_shrinking_time_to_collision() {
+    let mut swarm = hovering_swarm(2);
+    swarm.goto(0, -2.0, 0.0, 1.0, 0.0).unwrap();
+    swarm.goto(1, 2.0, 0.0, 1.0, 0.0).unwrap();
+    steps(&mut swarm, 6.0);
+    assert!(swarm.get_collision_warnings(10.0).is_empty());
+    swarm.velocity(0, 0.5, 0.0, 0.0, 0.0);
+    swarm.velocity(1, -0.5, 0.0, 0.0, 0.0);
+    steps(&mut swarm, 1.0);
+    let mut previous = f32::INFINITY;
+    for _ in 0..4 {
+        let warnings = swarm.get_collision_warnings(10.0);
+        assert_eq!(warnings.len(), 1);
+        let (a, b, time) = warnings[0];
+        assert_eq!((a, b), (0, 1));
+        assert!(time < previous - 0.4, "{time} after {previous}");
+        previous = time;
+        steps(&mut swarm, 0.5);
+    }
+}
+
+/// Sum of squared step-to-step moves of the effective target while a drone with target filter
+/// `alpha` is sent a goto each step to a point jittering about (1, 0, 1.5)
+fn target_path_roughness(alpha: f32) -> f32 {
+    let mut swarm = hovering_swarm(1);
+    swarm.goto(0, 1.0, 0.0, 1.5, 0.0).unwrap();
+    steps(&mut swarm, 4.0);
+    swarm.set_target_filter(0, alpha);
+    let mut previous = swarm.drones[0].target_pos;
+    let mut roughness = 0.0;
+    for i in 0..480u32 {
+        let jitter = ((i * 7919) % 13) as f32 / 13.0 - 0.5;
+        swarm.goto(0, 1.0 + 0.2 * jitter, 0.2 * jitter, 1.5, 0.0).unwrap();
+        swarm.step();
+        let target = swarm.drones[0].target_pos;
+        roughness += distance(target, previous).powi(2);
+        previous = target;
+    }
+    roughness
+}
+
+#[test]
+fn target_filter_smooths_a_jittery_goto_stream() {
+    let raw = target_path_roughness(1.0);
+    let filtered = target_path_roughness(0.05);
+    assert!(filtered < raw / 10.0, "{raw} -> {filtered}");
+}
+
+#[test]
+fn formation_complete_waits_for_the_drones_to_converge() {
+    let mut swarm = hovering_swarm(6);
+    swarm.formation_circle([0.0, 0.0, 1.5], 2.0, None).unwrap();
+    assert!(!swarm.formation_complete(0.1));
+    steps(&mut swarm, 1.0);
+    assert!(!swarm.formation_complete(0.1));
+    steps(&mut swarm, 8.0);
+    assert!(swarm.formation_complete(0.1));
+}
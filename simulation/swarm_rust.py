@@ -140,15 +140,16 @@ class SwarmWorldRust:
             spacing = cmd.params.get("spacing", 1.0)
             radius = cmd.params.get("radius", 1.5)
             axis = cmd.params.get("axis", "x")
+            ids = None if cmd.drone_ids == "all" else drone_ids
 
             if pattern == "line":
-                self.swarm.formation_line(center, spacing, axis)
+                self.swarm.formation_line(center, spacing, axis, ids=ids)
             elif pattern == "circle":
-                self.swarm.formation_circle(center, radius)
+                self.swarm.formation_circle(center, radius, ids=ids)
             elif pattern == "grid":
-                self.swarm.formation_grid(center, spacing)
+                self.swarm.formation_grid(center, spacing, ids=ids)
             elif pattern == "v":
-                self.swarm.formation_v(center, spacing)
+                self.swarm.formation_v(center, spacing, ids=ids)
             else:
                 print(f"[SwarmWorldRust] Unknown formation: {pattern}")
                 return
@@ -205,7 +206,8 @@ class SwarmWorldRust:
                 "vel": list(state.vel),
                 "yaw": float(state.yaw),
                 "battery": float(state.battery),
-                "healthy": bool(state.healthy)
+                "healthy": bool(state.healthy),
+                "selected": bool(state.selected)
             })
 
         return {
@@ -0,0 +1,35 @@
+import sys
+import types
+
+# SwarmWorldRust only needs the extension's RustSwarm; stand in a recorder for it
+drone_physics = types.ModuleType("drone_physics")
+
+
+class RecordingSwarm:
+    def __init__(self, num_drones, physics_hz):
+        self.calls = []
+
+    def __getattr__(self, name):
+        return lambda *args, **kwargs: self.calls.append((name, args, kwargs))
+
+
+drone_physics.RustSwarm = RecordingSwarm
+sys.modules.setdefault("drone_physics", drone_physics)
+
+from simulation.swarm_rust import SwarmWorldRust, DroneCommand  # noqa: E402
+
+
+def test_formation_passes_drone_subset_as_ids():
+    world = SwarmWorldRust(num_drones=6, gui=False)
+    if not isinstance(world.swarm, RecordingSwarm):
+        return  # the real extension is installed; this test covers the plumbing only
+    world.enqueue_command(DroneCommand("formation", [0, 1, 2], {"pattern": "circle", "center": [-3, 0, 1]}))
+    world.enqueue_command(DroneCommand("formation", [3, 4, 5], {"pattern": "circle", "center": [3, 0, 1]}))
+    world.enqueue_command(DroneCommand("formation", "all", {"pattern": "grid", "center": [0, 0, 1]}))
+    world._process_commands()
+
+    assert world.swarm.calls == [
+        ("formation_circle", ([-3, 0, 1], 1.5), {"ids": [0, 1, 2]}),
+        ("formation_circle", ([3, 0, 1], 1.5), {"ids": [3, 4, 5]}),
+        ("formation_grid", ([0, 0, 1], 1.0), {"ids": None}),
+    ]